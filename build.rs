@@ -0,0 +1,5 @@
+// `sqlx::migrate!` embeds src/common/migrations at compile time; rebuild when a
+// migration is added or edited.
+fn main() {
+    println!("cargo:rerun-if-changed=src/common/migrations");
+}
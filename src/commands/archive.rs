@@ -0,0 +1,99 @@
+use anyhow::Error;
+use chrono::{DateTime, Days, Utc};
+use clap::{Args, Subcommand};
+
+use crate::{
+    commands::{EXIT_OK, load_maintenance_pool},
+    common::{
+        archive::{Manifest, archive, restore, stray_files},
+        config::Config,
+        output::{send_group, send_message},
+    },
+};
+
+#[derive(Args)]
+pub struct ArchiveArgs {
+    #[command(subcommand)]
+    pub action: ArchiveAction,
+}
+
+#[derive(Subcommand)]
+pub enum ArchiveAction {
+    /// Export whole periods of old events to Parquet and delete them from Postgres
+    Run {
+        /// Archive periods that end before this RFC 3339 timestamp instead of
+        /// archive.older_than_days ago
+        #[arg(long)]
+        before: Option<DateTime<Utc>>,
+    },
+    /// Show the archived ranges from the manifest
+    List,
+    /// Load archived ranges back into events and remove their files
+    Restore {
+        /// Only ranges that end after this RFC 3339 timestamp
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+
+        /// Only ranges that start before this RFC 3339 timestamp
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+    },
+}
+
+pub async fn run(config: Config, args: ArchiveArgs) -> Result<u8, Error> {
+    let dir = &config.archive.dir;
+
+    match args.action {
+        ArchiveAction::Run { before } => {
+            let pool = load_maintenance_pool(&config).await?;
+            let before =
+                before.unwrap_or_else(|| Utc::now() - Days::new(config.archive.older_than_days));
+
+            send_group(format!(
+                "Archiving {:?} periods before {} to {}",
+                config.partitions.interval,
+                before.to_rfc3339(),
+                dir.display()
+            ));
+            let archived = archive(
+                &pool,
+                dir,
+                config.partitions.interval,
+                before,
+                config.archive.batch_size,
+            )
+            .await?;
+            for range in &archived {
+                send_message(format!("{} {} events", range.file, range.rows));
+            }
+            send_message(format!("Archived {} periods", archived.len()));
+        }
+        ArchiveAction::List => {
+            let manifest = Manifest::load(dir)?;
+            for range in &manifest.ranges {
+                println!(
+                    "{:<28} {} {} {:>10}",
+                    range.file,
+                    range.from.to_rfc3339(),
+                    range.to.to_rfc3339(),
+                    range.rows
+                );
+            }
+            for path in stray_files(dir, &manifest)? {
+                println!("{:<28} not in the manifest", path.display());
+            }
+        }
+        ArchiveAction::Restore { from, to } => {
+            let pool = load_maintenance_pool(&config).await?;
+
+            send_group(format!("Restoring from {}", dir.display()));
+            let restored = restore(&pool, dir, config.partitions.interval, from, to).await?;
+            for range in &restored {
+                send_message(format!("{} {} events", range.file, range.rows));
+            }
+            send_message(format!("Restored {} periods", restored.len()));
+        }
+    }
+
+    Ok(EXIT_OK)
+}
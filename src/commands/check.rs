@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use redis::AsyncConnectionConfig;
+
+use crate::{
+    commands::{EXIT_FAILURE, EXIT_OK, EXIT_UNAVAILABLE, load_maintenance_pool, redis_client},
+    common::{
+        config::Config,
+        durability::{DurabilityProfile, OnUnsafe, unsafe_settings},
+        schema::{MIGRATOR, schema_version, status},
+    },
+};
+
+// Config was already validated by the time a command runs, so this only has
+// to prove the dependencies answer.
+pub async fn run(config: Config) -> Result<u8, Error> {
+    println!("config      ok");
+
+    let mut healthy = true;
+    let mut durable = true;
+
+    let started = Instant::now();
+    let postgres = async {
+        let pool = load_maintenance_pool(&config).await?;
+        sqlx::query("SELECT 1").execute(&pool).await?;
+        Ok::<_, Error>(pool)
+    }
+    .await;
+
+    let pool = match postgres {
+        Ok(pool) => {
+            report("postgres", started, Ok(()));
+            Some(pool)
+        }
+        Err(error) => {
+            healthy &= report("postgres", started, Err(error));
+            None
+        }
+    };
+
+    if let Some(pool) = pool {
+        let pending = status(&MIGRATOR, &pool)
+            .await?
+            .iter()
+            .filter(|migration| !migration.applied)
+            .count();
+        let version = schema_version(&pool).await?;
+
+        match version {
+            Some(version) => println!("schema      version {}, {} pending", version, pending),
+            None => println!("schema      empty, {} pending", pending),
+        }
+
+        let problems = unsafe_settings(&pool).await?;
+        let profile = config.durability.profile;
+        if problems.is_empty() {
+            println!("durability  {:?}, crash safe", profile);
+        } else {
+            println!("durability  {:?}, not crash safe:", profile);
+            for problem in &problems {
+                println!("              {}", problem);
+            }
+            durable = profile == DurabilityProfile::Benchmark
+                || config.durability.on_unsafe == OnUnsafe::Warn;
+        }
+    }
+
+    if config.cache.store.needs_redis() {
+        let started = Instant::now();
+        let redis = async {
+            let timeout = Duration::from_millis(config.redis.timeout_ms);
+            let settings = AsyncConnectionConfig::new()
+                .set_connection_timeout(timeout)
+                .set_response_timeout(timeout);
+
+            let mut conn = redis_client(&config)?
+                .get_multiplexed_async_connection_with_config(&settings)
+                .await?;
+            redis::cmd("PING").query_async::<()>(&mut conn).await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+        healthy &= report("redis", started, redis);
+    } else {
+        println!("redis       disabled");
+    }
+
+    Ok(match (healthy, durable) {
+        (false, _) => EXIT_UNAVAILABLE,
+        (true, false) => EXIT_FAILURE,
+        (true, true) => EXIT_OK,
+    })
+}
+
+fn report(name: &str, started: Instant, result: Result<(), Error>) -> bool {
+    let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(()) => {
+            println!("{:<11} ok ({:.1} ms)", name, elapsed);
+            true
+        }
+        Err(error) => {
+            println!("{:<11} failed: {:#}", name, error);
+            false
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Error;
+use clap::{Args, Subcommand};
+
+use crate::{commands::EXIT_OK, common::config::Config};
+
+#[derive(Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the merged configuration with secrets redacted
+    Print,
+}
+
+pub fn run(config: Config, args: ConfigArgs) -> Result<u8, Error> {
+    match args.action {
+        ConfigAction::Print => print!("{}", config.redacted().to_toml()?),
+    }
+
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Error;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Args, ValueEnum};
+use futures::TryStreamExt;
+use serde::Serialize;
+use sqlx::types::JsonValue;
+
+use crate::{
+    commands::{EXIT_OK, load_maintenance_pool},
+    common::{config::Config, tenant::DEFAULT_TENANT},
+    contexts::events::infrastructure::repo::EventsRepo,
+};
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ExportFormat::Jsonl)]
+    pub format: ExportFormat,
+
+    /// File to write, `-` for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: PathBuf,
+
+    /// Only events at or after this RFC 3339 timestamp
+    #[arg(long)]
+    pub from: Option<DateTime<Utc>>,
+
+    /// Only events before this RFC 3339 timestamp
+    #[arg(long)]
+    pub to: Option<DateTime<Utc>>,
+
+    /// Tenant whose events are exported
+    #[arg(long, default_value_t = DEFAULT_TENANT, value_parser = clap::value_parser!(i64).range(0..))]
+    pub tenant: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+}
+
+#[derive(Serialize)]
+pub struct ExportedEvent<'a> {
+    pub id: i64,
+    pub user_id: i64,
+    pub event_type: &'a str,
+    pub timestamp: DateTime<Utc>,
+    pub metadata: &'a JsonValue,
+}
+
+pub async fn run(config: Config, args: ExportArgs) -> Result<u8, Error> {
+    let pool = load_maintenance_pool(&config).await?;
+    let repo = EventsRepo::create(pool);
+
+    let types: HashMap<i64, String> = repo
+        .get_types(args.tenant)
+        .await?
+        .into_iter()
+        .map(|row| (row.id, row.name))
+        .collect();
+
+    let output: Box<dyn Write> = if args.output.as_os_str() == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(&args.output)?)
+    };
+    let mut writer = BufWriter::new(output);
+
+    if args.format == ExportFormat::Csv {
+        writeln!(writer, "id,user_id,event_type,timestamp,metadata")?;
+    }
+
+    let mut events = repo.stream_events(args.tenant, args.from, args.to);
+    let mut exported = 0u64;
+
+    while let Some(event) = events.try_next().await? {
+        let record = ExportedEvent {
+            id: event.id,
+            user_id: event.user_id,
+            event_type: types.get(&event.type_id).map_or("", String::as_str),
+            timestamp: event.timestamp,
+            metadata: &event.metadata,
+        };
+
+        write_event(&mut writer, args.format, &record)?;
+        exported += 1;
+    }
+
+    writer.flush()?;
+    eprintln!("Exported {} events", exported);
+
+    Ok(EXIT_OK)
+}
+
+pub fn write_event(
+    writer: &mut impl Write,
+    format: ExportFormat,
+    event: &ExportedEvent,
+) -> Result<(), Error> {
+    match format {
+        ExportFormat::Jsonl => {
+            serde_json::to_writer(&mut *writer, event)?;
+            writeln!(writer)?;
+        }
+        ExportFormat::Csv => writeln!(
+            writer,
+            "{},{},{},{},{}",
+            event.id,
+            event.user_id,
+            csv_field(event.event_type),
+            event.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            csv_field(&event.metadata.to_string()),
+        )?,
+    }
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
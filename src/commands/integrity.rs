@@ -0,0 +1,71 @@
+use anyhow::Error;
+use clap::{Args, Subcommand};
+
+use crate::{
+    commands::{EXIT_FAILURE, EXIT_OK, load_maintenance_pool},
+    common::{
+        config::Config,
+        integrity::{apply, delete_orphans, scan},
+        output::{send_group, send_message},
+    },
+};
+
+#[derive(Args)]
+pub struct IntegrityArgs {
+    #[command(subcommand)]
+    pub action: IntegrityAction,
+}
+
+#[derive(Subcommand)]
+pub enum IntegrityAction {
+    /// Count events whose user or event type does not exist
+    Scan {
+        /// Orphaned event ids to print
+        #[arg(long, default_value_t = 10)]
+        samples: i64,
+
+        /// Delete the orphaned events
+        #[arg(long)]
+        delete: bool,
+    },
+    /// Install the configured delete policy and add or drop the foreign keys
+    Apply,
+}
+
+pub async fn run(config: Config, args: IntegrityArgs) -> Result<u8, Error> {
+    let pool = load_maintenance_pool(&config).await?;
+
+    match args.action {
+        IntegrityAction::Scan { samples, delete } => {
+            let report = scan(&pool, samples).await?;
+
+            println!("events               {}", report.events);
+            println!("missing user         {}", report.orphan_users);
+            println!("missing event type   {}", report.orphan_types);
+            for id in &report.samples {
+                println!("  orphan event {}", id);
+            }
+
+            if report.is_clean() {
+                return Ok(EXIT_OK);
+            }
+
+            if delete {
+                println!("deleted              {}", delete_orphans(&pool).await?);
+                return Ok(EXIT_OK);
+            }
+
+            Ok(EXIT_FAILURE)
+        }
+        IntegrityAction::Apply => {
+            send_group(format!(
+                "Applying integrity: foreign keys {}, on delete {:?}",
+                config.integrity.foreign_keys, config.integrity.on_delete
+            ));
+            apply(&pool, &config.integrity).await?;
+            send_message("Successful".to_owned());
+
+            Ok(EXIT_OK)
+        }
+    }
+}
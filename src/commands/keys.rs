@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use anyhow::{Error, bail};
+use clap::{Args, Subcommand};
+
+use crate::{
+    commands::{EXIT_OK, load_maintenance_pool},
+    common::{
+        auth::Scope,
+        cache::{LeveledCache, envelope::Envelope, noop_store::NoopStore},
+        config::Config,
+        output::send_message,
+    },
+    contexts::auth::infrastructure::{api_keys::ApiKeys, api_keys_repo::ApiKeysRepo},
+};
+
+#[derive(Args)]
+pub struct KeysArgs {
+    #[command(subcommand)]
+    pub action: KeysAction,
+}
+
+#[derive(Subcommand)]
+pub enum KeysAction {
+    /// Show every key, revoked ones included
+    List,
+    /// Create a key and print it; it cannot be shown again
+    Create {
+        /// Who or what the key is for
+        #[arg(long)]
+        name: String,
+
+        /// events:write, events:read, stats:read or admin; repeat for more
+        #[arg(long = "scope", required = true, value_parser = |value: &str| value.parse::<Scope>())]
+        scopes: Vec<Scope>,
+
+        /// Bind the key to this tenant; unbound keys pick one with X-Tenant-Id
+        #[arg(long, value_parser = clap::value_parser!(i64).range(0..))]
+        tenant: Option<i64>,
+    },
+    /// Replace a key and print the new one; the old one works for
+    /// auth.rotation_grace_secs more
+    Rotate { id: i64 },
+    /// Stop accepting a key
+    Revoke { id: i64 },
+}
+
+pub async fn run(config: Config, args: KeysArgs) -> Result<u8, Error> {
+    let pool = load_maintenance_pool(&config).await?;
+
+    // Servers keep lookups for auth.cache_ttl, so there is nothing to drop
+    // from here.
+    let keys = ApiKeys::create(
+        ApiKeysRepo::create(pool),
+        LeveledCache::create(
+            Arc::new(NoopStore),
+            Envelope::create(config.cache.codec, config.cache.compress_threshold),
+        ),
+        config.auth.clone(),
+    );
+
+    match args.action {
+        KeysAction::List => {
+            for key in keys.list().await? {
+                println!(
+                    "{:<20} {:<24} {:<14} {:<8} {}{}",
+                    key.id,
+                    key.name,
+                    key.prefix,
+                    key.tenant_id
+                        .map_or("*".to_owned(), |tenant| tenant.to_string()),
+                    key.scopes.join(","),
+                    if key.revoked_at.is_some() {
+                        "  revoked"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        KeysAction::Create {
+            name,
+            scopes,
+            tenant,
+        } => {
+            if name.trim().is_empty() {
+                bail!("The name must not be empty");
+            }
+            let (row, key) = keys.issue(name.trim(), &scopes, tenant).await?;
+            send_message(format!("Key {} created", row.id));
+            println!("{}", key);
+        }
+        KeysAction::Rotate { id } => {
+            let Some((_, key)) = keys.rotate(id).await? else {
+                bail!("No live API key {}", id);
+            };
+            send_message(format!(
+                "Old key accepted for {} more seconds",
+                config.auth.rotation_grace_secs
+            ));
+            println!("{}", key);
+        }
+        KeysAction::Revoke { id } => {
+            if !keys.revoke(id).await? {
+                bail!("No API key {}", id);
+            }
+            send_message(format!(
+                "Revoked; servers may accept it for up to {} seconds",
+                config.auth.cache_ttl
+            ));
+        }
+    }
+
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use clap::{Args, Subcommand};
+use sqlx::{
+    Pool, Postgres,
+    migrate::{Migrate, Migrator},
+};
+
+use crate::{
+    commands::{EXIT_OK, load_maintenance_pool},
+    common::{
+        config::Config,
+        durability,
+        integrity::apply,
+        output::{send_group, send_message},
+        schema::{MIGRATOR, status},
+    },
+};
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    /// Read migrations from this directory instead of the ones built into the binary
+    #[arg(long, global = true, value_name = "DIR")]
+    pub source: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub action: MigrateAction,
+}
+
+#[derive(Subcommand)]
+pub enum MigrateAction {
+    /// Apply every pending migration
+    Up,
+    /// Revert applied migrations; the latest one unless --target is given
+    Down {
+        /// Keep migrations up to and including this version
+        #[arg(long)]
+        target: Option<i64>,
+    },
+    /// List migrations and whether they are applied
+    Status,
+}
+
+pub async fn run(config: Config, args: MigrateArgs) -> Result<u8, Error> {
+    let loaded;
+    let migrator = match &args.source {
+        Some(source) => {
+            loaded = Migrator::new(source.as_path()).await?;
+            &loaded
+        }
+        None => &MIGRATOR,
+    };
+    let pool = load_maintenance_pool(&config).await?;
+
+    match args.action {
+        MigrateAction::Up => {
+            send_group("Applying migrations".to_owned());
+            migrator.run(&pool).await?;
+            apply(&pool, &config.integrity).await?;
+            for table in durability::apply(&pool, config.durability.profile).await? {
+                send_message(format!(
+                    "{} follows the {:?} profile",
+                    table, config.durability.profile
+                ));
+            }
+            send_message("Successful".to_owned());
+        }
+        MigrateAction::Down { target } => {
+            let target = match target {
+                Some(target) => target,
+                None => previous_version(&pool).await?,
+            };
+
+            send_group(format!("Reverting migrations above version {}", target));
+            migrator.undo(&pool, target).await?;
+            send_message("Successful".to_owned());
+        }
+        MigrateAction::Status => {
+            for migration in status(migrator, &pool).await? {
+                let state = match (migration.applied, migration.checksum_mismatch) {
+                    (true, true) => "applied (checksum mismatch)",
+                    (true, false) => "applied",
+                    (false, _) => "pending",
+                };
+                println!(
+                    "{:>6}  {:<28} {}",
+                    migration.version, migration.description, state
+                );
+            }
+        }
+    }
+
+    Ok(EXIT_OK)
+}
+
+async fn previous_version(pool: &Pool<Postgres>) -> Result<i64, Error> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let mut versions: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort_unstable();
+    versions.pop();
+
+    Ok(versions.pop().unwrap_or(-1))
+}
@@ -0,0 +1,192 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use anyhow::Error;
+use clap::{Parser, Subcommand};
+use redis::Client;
+use sqlx::{
+    Executor, Pool, Postgres,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+
+use crate::common::{
+    cache::redis_store::{RedisSettings, RedisStore},
+    config::Config,
+    logging,
+    output::send_message,
+};
+
+pub mod archive;
+pub mod check;
+pub mod config;
+pub mod export;
+pub mod integrity;
+pub mod keys;
+pub mod migrate;
+pub mod partitions;
+pub mod retention;
+pub mod seed;
+pub mod serve;
+
+pub const EXIT_OK: u8 = 0;
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_USAGE: u8 = 2;
+pub const EXIT_UNAVAILABLE: u8 = 3;
+
+#[derive(Parser)]
+#[command(
+    name = "w_collider",
+    version,
+    about = "Event collector API",
+    after_help = "Exit codes: 0 success, 1 command failed, 2 invalid usage or configuration, 3 dependency unavailable"
+)]
+pub struct Cli {
+    /// TOML config file; defaults to $APP_CONFIG, then ./config.toml when present
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Override a config value after file and env, e.g. --set bus.chunk_size=5000
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default when no subcommand is given)
+    Serve(serve::ServeArgs),
+    /// Fill the database with generated users, types and events
+    Seed(seed::SeedArgs),
+    /// Apply, revert or inspect database migrations
+    Migrate(migrate::MigrateArgs),
+    /// Validate configuration and reach Postgres and Redis
+    Check,
+    /// Find orphaned events and manage foreign keys and the delete policy
+    Integrity(integrity::IntegrityArgs),
+    /// List, create and expire the time partitions of events
+    Partitions(partitions::PartitionsArgs),
+    /// Manage per event type retention rules and apply them
+    Retention(retention::RetentionArgs),
+    /// Move old events to Parquet files and load them back
+    Archive(archive::ArchiveArgs),
+    /// Create, list, rotate and revoke API keys
+    Keys(keys::KeysArgs),
+    /// Dump events as JSON lines or CSV
+    Export(export::ExportArgs),
+    /// Inspect the effective configuration
+    Config(config::ConfigArgs),
+}
+
+pub async fn run(cli: Cli) -> ExitCode {
+    let mut config = match Config::load(cli.config.as_deref(), &cli.overrides) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let command = cli
+        .command
+        .unwrap_or(Command::Serve(serve::ServeArgs::default()));
+
+    if let Command::Serve(args) = &command
+        && let Err(error) = args.apply(&mut config)
+    {
+        eprintln!("Error: {:#}", error);
+        return ExitCode::from(EXIT_USAGE);
+    }
+
+    // Export and config print write data to stdout, so they stay silent.
+    if !matches!(command, Command::Export(_) | Command::Config(_)) {
+        logging::init(config.log.format, &config.log.level);
+    }
+
+    let result = match command {
+        Command::Serve(_) => serve::run(config).await,
+        Command::Seed(args) => seed::run(config, args).await,
+        Command::Migrate(args) => migrate::run(config, args).await,
+        Command::Check => check::run(config).await,
+        Command::Integrity(args) => integrity::run(config, args).await,
+        Command::Partitions(args) => partitions::run(config, args).await,
+        Command::Retention(args) => retention::run(config, args).await,
+        Command::Archive(args) => archive::run(config, args).await,
+        Command::Keys(args) => keys::run(config, args).await,
+        Command::Export(args) => export::run(config, args).await,
+        Command::Config(args) => config::run(config, args),
+    };
+
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+pub fn postgres_options(config: &Config) -> Result<PgConnectOptions, Error> {
+    let parts = config.postgres.parts()?;
+
+    Ok(PgConnectOptions::new()
+        .host(parts.host.as_str())
+        .port(parts.port)
+        .username(parts.user.as_str())
+        .password(parts.password.as_str())
+        .database(parts.database.as_str())
+        .statement_cache_capacity(config.postgres.statement_cache_capacity))
+}
+
+pub async fn load_postgres_pool(config: &Config) -> Result<Pool<Postgres>, Error> {
+    let pool = PgPoolOptions::new()
+        .min_connections(config.postgres.min_connections)
+        .max_connections(config.postgres.max_connections)
+        .max_lifetime(Duration::from_secs(3600))
+        .after_connect(|conn, _meta| {
+            Box::pin(async move {
+                conn.execute("SET search_path TO public").await?;
+                Ok(())
+            })
+        })
+        .connect_with(postgres_options(config)?)
+        .await;
+
+    pool.map_err(|error| {
+        send_message(format!("Error: {}", error));
+        error.into()
+    })
+}
+
+// One short-lived connection for maintenance commands that do not need the
+// server pool sizes.
+pub async fn load_maintenance_pool(config: &Config) -> Result<Pool<Postgres>, Error> {
+    Ok(PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(Duration::from_secs(5))
+        .connect_with(postgres_options(config)?)
+        .await?)
+}
+
+pub async fn load_redis_store(config: &Config) -> Result<Option<RedisStore>, Error> {
+    if !config.cache.store.needs_redis() {
+        return Ok(None);
+    }
+
+    let settings = RedisSettings {
+        timeout: Duration::from_millis(config.redis.timeout_ms),
+        failure_threshold: config.redis.failure_threshold,
+        cooldown: Duration::from_millis(config.redis.cooldown_ms),
+    };
+
+    Ok(Some(
+        RedisStore::connect(redis_client(config)?, settings).await,
+    ))
+}
+
+pub fn redis_client(config: &Config) -> Result<Client, Error> {
+    Ok(Client::open(format!(
+        "redis://{}:{}/",
+        config.redis.host, config.redis.port
+    ))?)
+}
@@ -0,0 +1,91 @@
+use anyhow::{Error, bail};
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+
+use crate::{
+    commands::{EXIT_OK, load_maintenance_pool},
+    common::{
+        config::Config,
+        output::{send_group, send_message},
+        partitions::{ensure_range, is_partitioned, list, maintain},
+    },
+};
+
+#[derive(Args)]
+pub struct PartitionsArgs {
+    #[command(subcommand)]
+    pub action: PartitionsAction,
+}
+
+#[derive(Subcommand)]
+pub enum PartitionsAction {
+    /// Show the partitions of events with their bounds and estimated rows
+    List,
+    /// Create the upcoming partitions and expire old ones once
+    Maintain,
+    /// Create partitions covering a time range, e.g. before a backfill
+    Ensure {
+        #[arg(long)]
+        from: DateTime<Utc>,
+
+        #[arg(long)]
+        to: DateTime<Utc>,
+    },
+}
+
+pub async fn run(config: Config, args: PartitionsArgs) -> Result<u8, Error> {
+    let pool = load_maintenance_pool(&config).await?;
+
+    if !is_partitioned(&pool).await? {
+        bail!("events is not partitioned, run `migrate up` first");
+    }
+
+    match args.action {
+        PartitionsAction::List => {
+            let bound = |at: Option<DateTime<Utc>>, open: &str| {
+                at.map_or_else(|| open.to_owned(), |at| at.to_rfc3339())
+            };
+
+            for partition in list(&pool).await? {
+                if partition.is_default {
+                    println!("{:<24} default", partition.name);
+                } else {
+                    println!(
+                        "{:<24} {:<25} {:<25} {}",
+                        partition.name,
+                        bound(partition.from, "MINVALUE"),
+                        bound(partition.to, "MAXVALUE"),
+                        partition.rows.max(0)
+                    );
+                }
+            }
+        }
+        PartitionsAction::Maintain => {
+            send_group(format!(
+                "Maintaining {:?} partitions: premake {}, retain {}, on expire {:?}",
+                config.partitions.interval,
+                config.partitions.premake,
+                config.partitions.retain,
+                config.partitions.on_expire
+            ));
+            let report = maintain(&pool, &config.partitions, Utc::now()).await?;
+            for name in &report.created {
+                send_message(format!("Created {}", name));
+            }
+            for name in &report.expired {
+                send_message(format!("Expired {}", name));
+            }
+        }
+        PartitionsAction::Ensure { from, to } => {
+            if from > to {
+                bail!("--from must not be after --to");
+            }
+
+            for name in ensure_range(&pool, config.partitions.interval, from, to).await? {
+                send_message(format!("Created {}", name));
+            }
+        }
+    }
+
+    Ok(EXIT_OK)
+}
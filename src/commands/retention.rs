@@ -0,0 +1,103 @@
+use anyhow::{Error, bail};
+use chrono::Utc;
+use clap::{Args, Subcommand};
+
+use crate::{
+    commands::{EXIT_OK, load_maintenance_pool},
+    common::{
+        config::Config,
+        output::{send_group, send_message},
+        retention::{RetentionRule, list_rules, preview, remove_rule, run as apply, set_rule},
+    },
+};
+
+#[derive(Args)]
+pub struct RetentionArgs {
+    #[command(subcommand)]
+    pub action: RetentionAction,
+}
+
+#[derive(Subcommand)]
+pub enum RetentionAction {
+    /// Show the rules
+    List,
+    /// Add or replace the rule for an event type name or glob, e.g. 'order.*'
+    Set {
+        pattern: String,
+
+        /// Days an event is kept after its timestamp
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        ttl_days: i32,
+
+        /// Copy expired events to events_archive before deleting them
+        #[arg(long)]
+        archive: bool,
+    },
+    /// Remove the rule with exactly this pattern
+    Remove { pattern: String },
+    /// Apply the rules once
+    Run {
+        /// Only count the events that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+pub async fn run(config: Config, args: RetentionArgs) -> Result<u8, Error> {
+    let pool = load_maintenance_pool(&config).await?;
+
+    match args.action {
+        RetentionAction::List => {
+            for rule in list_rules(&pool).await? {
+                println!(
+                    "{:<32} {:>6} days{}",
+                    rule.pattern,
+                    rule.ttl_days,
+                    if rule.archive { "  archive" } else { "" }
+                );
+            }
+        }
+        RetentionAction::Set {
+            pattern,
+            ttl_days,
+            archive,
+        } => {
+            set_rule(
+                &pool,
+                &RetentionRule {
+                    pattern,
+                    ttl_days,
+                    archive,
+                },
+            )
+            .await?;
+            send_message("Successful".to_owned());
+        }
+        RetentionAction::Remove { pattern } => {
+            if !remove_rule(&pool, &pattern).await? {
+                bail!("No retention rule for `{}`", pattern);
+            }
+            send_message("Successful".to_owned());
+        }
+        RetentionAction::Run { dry_run: true } => {
+            for (rule, count) in preview(&pool, Utc::now()).await? {
+                println!("{:<32} {}", rule.pattern, count);
+            }
+        }
+        RetentionAction::Run { dry_run: false } => {
+            send_group("Applying retention rules".to_owned());
+            // The server's caches are out of reach here and expire with their TTLs.
+            let report = apply(&pool, None, &config.retention, Utc::now()).await?;
+            for (pattern, deleted) in &report.deleted {
+                send_message(format!("{} {}", pattern, deleted));
+            }
+            send_message(format!(
+                "Removed {} events, {} archived",
+                report.total(),
+                report.archived
+            ));
+        }
+    }
+
+    Ok(EXIT_OK)
+}
@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+
+use anyhow::{Error, bail};
+use chrono::{DateTime, Utc};
+use clap::Args;
+
+use crate::{
+    commands::{EXIT_OK, load_postgres_pool},
+    common::{
+        config::Config,
+        integrity::scan,
+        output::{send_group, send_message},
+        seed_profile::{DEFAULT_PROFILE, SeedProfile, SeedProfiles},
+        seeder::{SeedMode, seed},
+        tenant::{DEFAULT_TENANT, Tenant},
+    },
+};
+
+#[derive(Args)]
+pub struct SeedArgs {
+    /// Profile to generate, built-in `default` and `realistic` or one from the profiles file
+    #[arg(long, default_value = DEFAULT_PROFILE)]
+    pub profile: String,
+
+    /// TOML file with one table per profile; defaults to ./seed.toml when present
+    #[arg(long, value_name = "FILE")]
+    pub profiles: Option<PathBuf>,
+
+    /// Fixed RNG seed, overrides the profile
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// End of the generated time span, required with a seed unless the profile sets it
+    #[arg(long, value_name = "TIMESTAMP")]
+    pub end: Option<DateTime<Utc>>,
+
+    /// Users to create, overrides the profile
+    #[arg(long, value_parser = positive)]
+    pub users: Option<usize>,
+
+    /// Event types to create, taken from the built-in list (at most 100)
+    #[arg(long, value_parser = positive)]
+    pub types: Option<usize>,
+
+    /// Events to generate, overrides the profile
+    #[arg(long, value_parser = positive)]
+    pub events: Option<usize>,
+
+    /// Empty events, users and event types before seeding
+    #[arg(long, conflicts_with = "append")]
+    pub truncate: bool,
+
+    /// Add to existing events instead of refusing to seed over them
+    #[arg(long)]
+    pub append: bool,
+
+    /// Check afterwards that every event references an existing user and type
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Tenant that owns the seeded users, event types and events
+    #[arg(long, default_value_t = DEFAULT_TENANT, value_parser = clap::value_parser!(i64).range(0..))]
+    pub tenant: i64,
+}
+
+impl SeedArgs {
+    pub fn profile(&self) -> Result<SeedProfile, Error> {
+        let mut profile = SeedProfiles::load(self.profiles.as_deref())?.get(&self.profile)?;
+
+        profile.seed = self.seed.or(profile.seed);
+        profile.end = self.end.or(profile.end);
+        profile.users = self.users.unwrap_or(profile.users);
+        profile.types = self.types.unwrap_or(profile.types);
+        profile.events = self.events.unwrap_or(profile.events);
+
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    pub fn mode(&self) -> SeedMode {
+        match (self.truncate, self.append) {
+            (true, _) => SeedMode::Truncate,
+            (_, true) => SeedMode::Append,
+            _ => SeedMode::Fresh,
+        }
+    }
+}
+
+pub async fn run(config: Config, args: SeedArgs) -> Result<u8, Error> {
+    let profile = args.profile()?;
+
+    send_group("Loading databases".to_owned());
+    let pg_pool = load_postgres_pool(&config).await?;
+    send_message("Successful".to_owned());
+
+    send_group(format!("Seed database with profile {}", args.profile));
+
+    let start = Utc::now();
+
+    seed(
+        pg_pool.clone(),
+        config,
+        profile,
+        args.mode(),
+        Tenant(args.tenant),
+    )
+    .await?;
+
+    let duration = Utc::now().signed_duration_since(start).num_seconds();
+    send_message(format!("─ Total Duration {} seconds", duration));
+
+    if args.verify {
+        send_group("Verify referential integrity".to_owned());
+
+        let report = scan(&pg_pool, 0).await?;
+        if !report.is_clean() {
+            bail!(
+                "{} of {} events reference a missing user, {} a missing event type",
+                report.orphan_users,
+                report.events,
+                report.orphan_types
+            );
+        }
+
+        send_message(format!("{} events reference existing rows", report.events));
+    }
+
+    send_message("Successful".to_owned());
+
+    Ok(EXIT_OK)
+}
+
+fn positive(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_owned()),
+        Ok(value) => Ok(value),
+        Err(error) => Err(error.to_string()),
+    }
+}
@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use actix_web::{App, HttpServer, middleware::from_fn, web};
+use anyhow::{Error, bail};
+use clap::Args;
+use tokio::try_join;
+
+use crate::{
+    commands::{EXIT_OK, load_postgres_pool, load_redis_store},
+    common::{
+        cache::{
+            CacheTopology, LeveledCache, envelope::Envelope, memory_store::MemoryStore,
+            noop_store::NoopStore, redis_store::RedisStore, store::CacheStore,
+            tiered_store::TieredStore,
+        },
+        command_bus::CommandBus,
+        config::Config,
+        durability::{self, DurabilityProfile, OnUnsafe, unsafe_settings},
+        health::Health,
+        integrity::apply,
+        logging::trace_request,
+        metadata_policy::MetadataPolicies,
+        metrics::track_http,
+        output::{send_group, send_message},
+        partitions::{is_partitioned, spawn_manager},
+        rate_limit::RateLimiter,
+        request_id::request_id,
+        retention::spawn_job,
+        schema::migrate,
+    },
+    contexts::{
+        auth::infrastructure::{api_keys::ApiKeys, api_keys_repo::ApiKeysRepo},
+        events::infrastructure::{
+            cached_projection::{CacheTtls, EventsProj},
+            repo::EventsRepo,
+        },
+        users::infrastructure::{gdpr::spawn_worker, gdpr_repo::GdprRepo},
+    },
+    init_routes,
+};
+
+#[derive(Args, Default)]
+pub struct ServeArgs {
+    /// Address to listen on, overrides server.host and server.port
+    #[arg(long, value_name = "HOST:PORT")]
+    pub bind: Option<String>,
+
+    /// HTTP worker threads, overrides server.workers (0 = one per core)
+    #[arg(long, value_name = "N")]
+    pub workers: Option<usize>,
+}
+
+impl ServeArgs {
+    pub fn apply(&self, config: &mut Config) -> Result<(), Error> {
+        if let Some(bind) = &self.bind {
+            let Some((host, port)) = bind.rsplit_once(':') else {
+                bail!("Invalid --bind `{}`, expected HOST:PORT", bind);
+            };
+
+            config.set("server.host", host.trim_matches(['[', ']']))?;
+            config.set("server.port", port)?;
+        }
+
+        if let Some(workers) = self.workers {
+            config.server.workers = workers;
+        }
+
+        config.validate()
+    }
+}
+
+pub async fn run(config: Config) -> Result<u8, Error> {
+    send_group("Loading configuration".to_owned());
+    send_message("Successful".to_owned());
+
+    send_group("Loading databases".to_owned());
+
+    let (pg_pool, redis_store) = try_join!(load_postgres_pool(&config), load_redis_store(&config))?;
+
+    send_message("Successful".to_owned());
+
+    if config.postgres.migrate_on_boot {
+        send_group("Applying migrations".to_owned());
+        migrate(&pg_pool).await?;
+        apply(&pg_pool, &config.integrity).await?;
+        durability::apply(&pg_pool, config.durability.profile).await?;
+        send_message("Successful".to_owned());
+    }
+
+    if config.durability.profile == DurabilityProfile::Production {
+        send_group("Checking durability".to_owned());
+        let problems = unsafe_settings(&pg_pool).await?;
+        if !problems.is_empty() && config.durability.on_unsafe == OnUnsafe::Fail {
+            bail!(
+                "Unsafe for durability.profile = \"production\":\n  - {}",
+                problems.join("\n  - ")
+            );
+        }
+        for problem in &problems {
+            tracing::warn!(%problem, "durability at risk");
+        }
+        send_message("Successful".to_owned());
+    }
+
+    if config.partitions.manage && is_partitioned(&pg_pool).await? {
+        send_group("Starting partition manager".to_owned());
+        spawn_manager(pg_pool.clone(), config.partitions.clone());
+        send_message(format!(
+            "{:?}, premake {}, retain {}",
+            config.partitions.interval, config.partitions.premake, config.partitions.retain
+        ));
+    }
+
+    send_group("Creating leveled cache".to_owned());
+    send_message(format!("Topology {:?}", config.cache.store));
+
+    let cache = LeveledCache::create(
+        create_cache_store(&config, redis_store.clone())?,
+        Envelope::create(config.cache.codec, config.cache.compress_threshold),
+    );
+
+    send_message("Successful".to_owned());
+
+    if config.retention.enabled {
+        send_group("Starting retention job".to_owned());
+        spawn_job(pg_pool.clone(), cache.clone(), config.retention.clone());
+        send_message(format!(
+            "Every {} ms in batches of {}",
+            config.retention.interval_ms, config.retention.batch_size
+        ));
+    }
+
+    send_group("Creating command bus thread".to_owned());
+
+    let bus = Arc::new(CommandBus::init(
+        config.bus_interval(),
+        config.bus.chunk_size,
+        config.durability.profile.synchronous_commit(),
+        pg_pool.clone(),
+    ));
+
+    send_message("Successful".to_owned());
+
+    send_group("Creating repository and projection".to_owned());
+
+    let repo = EventsRepo::create(pg_pool.clone());
+    let proj = EventsProj::create(cache.clone(), repo.clone(), CacheTtls::from(&config.cache));
+    let gdpr = GdprRepo::create(pg_pool.clone());
+    let policies = MetadataPolicies::create(&config.metadata)?;
+    let limiter = RateLimiter::create(config.rate_limit.clone(), redis_store.clone());
+    let keys = ApiKeys::create(
+        ApiKeysRepo::create(pg_pool.clone()),
+        cache.clone(),
+        config.auth.clone(),
+    );
+
+    send_message("Successful".to_owned());
+
+    send_group("Starting GDPR worker".to_owned());
+    spawn_worker(
+        repo.clone(),
+        gdpr.clone(),
+        cache.clone(),
+        config.gdpr.clone(),
+        config.archive.clone(),
+    );
+    send_message(format!(
+        "Erasure {:?}, exports in {}",
+        config.gdpr.erasure,
+        config.gdpr.export_dir.display()
+    ));
+
+    if !config.auth.enabled {
+        tracing::warn!("auth.enabled is false, every route is open");
+    }
+
+    send_group("Server will be started".to_owned());
+    send_message(format!("IP {}:{}", config.server.host, config.server.port));
+
+    let health = web::Data::new(Health::create(config.bus_lag_threshold()));
+    let drain = config.shutdown_drain();
+    let server_health = health.clone();
+    let server_bus = bus.clone();
+    let gdpr_config = config.gdpr.clone();
+
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .wrap(from_fn(trace_request))
+            .wrap(from_fn(track_http))
+            .wrap(from_fn(request_id))
+            .app_data(web::Data::new(repo.clone()))
+            .app_data(web::Data::new(proj.clone()))
+            .app_data(web::Data::new(gdpr.clone()))
+            .app_data(web::Data::new(policies.clone()))
+            .app_data(web::Data::new(keys.clone()))
+            .app_data(web::Data::new(limiter.clone()))
+            .app_data(web::Data::new(gdpr_config.clone()))
+            .app_data(web::Data::new(pg_pool.clone()))
+            .app_data(web::Data::new(server_bus.clone()))
+            .app_data(web::Data::new(cache.clone()))
+            .app_data(server_health.clone())
+            .configure(|cfg| {
+                if let Some(redis) = &redis_store {
+                    cfg.app_data(web::Data::new(redis.clone()));
+                }
+            })
+            .configure(init_routes)
+    })
+    .disable_signals();
+
+    if config.server.workers > 0 {
+        server = server.workers(config.server.workers);
+    }
+
+    let server = server
+        .bind((config.server.host.as_str(), config.server.port))?
+        .run();
+
+    let handle = server.handle();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+
+        tracing::info!(
+            drain_ms = drain.as_millis() as u64,
+            "draining before shutdown"
+        );
+        health.start_draining();
+        tokio::time::sleep(drain).await;
+
+        handle.stop(true).await;
+    });
+
+    let served = server.await;
+
+    // Events accepted up to the stop, the drain window included, are still
+    // queued; write them before exiting.
+    bus.shutdown().await;
+    tracing::info!("command bus flushed, shutting down");
+
+    served?;
+
+    Ok(EXIT_OK)
+}
+
+async fn shutdown_signal() {
+    let interrupt = tokio::signal::ctrl_c();
+
+    #[cfg(unix)]
+    {
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(_) => std::future::pending::<()>().await,
+            }
+        };
+
+        tokio::select! {
+            _ = interrupt => {}
+            _ = terminate => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = interrupt.await;
+    }
+}
+
+fn create_cache_store(
+    config: &Config,
+    redis_store: Option<RedisStore>,
+) -> Result<Arc<dyn CacheStore>, Error> {
+    let redis_store = || -> Result<Arc<dyn CacheStore>, Error> {
+        let store = redis_store
+            .clone()
+            .ok_or_else(|| Error::msg("Redis store is required"))?;
+
+        Ok(Arc::new(store))
+    };
+    Ok(match config.cache.store {
+        CacheTopology::Tiered => Arc::new(TieredStore::create(
+            Arc::new(MemoryStore::create(config.cache.memory_mb)),
+            redis_store()?,
+            config.cache.backfill_ttl,
+        )),
+        CacheTopology::Memory => Arc::new(MemoryStore::create(config.cache.memory_mb)),
+        CacheTopology::Redis => redis_store()?,
+        CacheTopology::None => Arc::new(NoopStore),
+    })
+}
@@ -0,0 +1,685 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Error, bail};
+use arrow_array::{
+    Array, ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+    builder::{Int64Builder, StringBuilder, TimestampMicrosecondBuilder},
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use parquet::{
+    arrow::{ArrowWriter, arrow_reader::ParquetRecordBatchReaderBuilder},
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, FromRow, Pool, Postgres, query, query_as, types::JsonValue};
+
+use crate::{
+    common::{
+        partitions::{PartitionInterval, ensure_range, is_partitioned},
+        tenant::DEFAULT_TENANT,
+    },
+    contexts::events::infrastructure::repo::Event,
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Prefix of the flattened metadata columns, e.g. `metadata.page`.
+pub const METADATA_PREFIX: &str = "metadata.";
+
+// Arrow field metadata telling restore how to read a metadata column back.
+const KIND_KEY: &str = "w_collider.kind";
+
+/// How a top-level metadata key is stored in its column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataKind {
+    /// Every value in the file is a JSON string and is stored as is.
+    Text,
+    /// Values are stored as JSON text.
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedRange {
+    /// File name relative to the archive directory.
+    pub file: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub rows: u64,
+    pub sha256: String,
+    pub archived_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub ranges: Vec<ArchivedRange>,
+}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Manifest, Error> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    // Written next to the final name and renamed, so a crash leaves either the
+    // old or the new manifest.
+    pub fn save(&self, dir: &Path) -> Result<(), Error> {
+        let path = dir.join(MANIFEST_FILE);
+        let temporary = dir.join(format!("{}.tmp", MANIFEST_FILE));
+
+        fs::write(&temporary, serde_json::to_vec_pretty(self)?)?;
+        File::open(&temporary)?.sync_all()?;
+        fs::rename(&temporary, &path)
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+
+        Ok(())
+    }
+
+    pub fn covers(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        self.ranges
+            .iter()
+            .any(|range| range.from < to && range.to > from)
+    }
+
+    fn insert(&mut self, range: ArchivedRange) {
+        self.ranges.push(range);
+        self.ranges.sort_by_key(|range| range.from);
+    }
+}
+
+/// An event together with the tenant it belongs to.
+#[derive(Debug, FromRow)]
+pub struct ArchivedEvent {
+    pub tenant_id: i64,
+    #[sqlx(flatten)]
+    pub event: Event,
+}
+
+/// Writes events to a Parquet file with one column per top-level metadata key.
+pub struct ArchiveWriter {
+    writer: ArrowWriter<File>,
+    schema: Arc<Schema>,
+    keys: Vec<(String, MetadataKind)>,
+    batch_size: usize,
+    pending: Vec<ArchivedEvent>,
+    rows: u64,
+}
+
+impl ArchiveWriter {
+    pub fn create(
+        path: &Path,
+        keys: Vec<(String, MetadataKind)>,
+        batch_size: usize,
+    ) -> Result<ArchiveWriter, Error> {
+        let mut fields = vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("user_id", DataType::Int64, false),
+            Field::new("type_id", DataType::Int64, false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("tenant_id", DataType::Int64, false),
+        ];
+        for (key, kind) in &keys {
+            let kind = match kind {
+                MetadataKind::Text => "text",
+                MetadataKind::Json => "json",
+            };
+            fields.push(
+                Field::new(format!("{}{}", METADATA_PREFIX, key), DataType::Utf8, true)
+                    .with_metadata(HashMap::from([(KIND_KEY.to_owned(), kind.to_owned())])),
+            );
+        }
+        let schema = Arc::new(Schema::new(fields));
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_max_row_group_size(batch_size)
+            .build();
+        let file =
+            File::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
+
+        Ok(ArchiveWriter {
+            writer: ArrowWriter::try_new(file, schema.clone(), Some(properties))?,
+            schema,
+            keys,
+            batch_size,
+            pending: Vec::with_capacity(batch_size),
+            rows: 0,
+        })
+    }
+
+    pub fn push(&mut self, event: ArchivedEvent) -> Result<(), Error> {
+        self.pending.push(event);
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes the file and returns the number of rows written.
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.flush()?;
+        self.writer.into_inner()?.sync_all()?;
+
+        Ok(self.rows)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut ids = Int64Builder::with_capacity(self.pending.len());
+        let mut users = Int64Builder::with_capacity(self.pending.len());
+        let mut types = Int64Builder::with_capacity(self.pending.len());
+        let mut timestamps =
+            TimestampMicrosecondBuilder::with_capacity(self.pending.len()).with_timezone("UTC");
+        let mut tenants = Int64Builder::with_capacity(self.pending.len());
+        let mut metadata: Vec<StringBuilder> =
+            self.keys.iter().map(|_| StringBuilder::new()).collect();
+
+        for ArchivedEvent { tenant_id, event } in self.pending.drain(..) {
+            ids.append_value(event.id);
+            users.append_value(event.user_id);
+            types.append_value(event.type_id);
+            timestamps.append_value(event.timestamp.timestamp_micros());
+            tenants.append_value(tenant_id);
+
+            for ((key, kind), column) in self.keys.iter().zip(metadata.iter_mut()) {
+                match (event.metadata.get(key), kind) {
+                    (None, _) => column.append_null(),
+                    (Some(JsonValue::String(text)), MetadataKind::Text) => {
+                        column.append_value(text)
+                    }
+                    (Some(value), _) => column.append_value(value.to_string()),
+                }
+            }
+        }
+
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(ids.finish()),
+            Arc::new(users.finish()),
+            Arc::new(types.finish()),
+            Arc::new(timestamps.finish()),
+            Arc::new(tenants.finish()),
+        ];
+        columns.extend(
+            metadata
+                .into_iter()
+                .map(|mut column| Arc::new(column.finish()) as ArrayRef),
+        );
+
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.rows += batch.num_rows() as u64;
+        self.writer.write(&batch)?;
+
+        Ok(())
+    }
+}
+
+/// Reads an archive file back into events, one record batch at a time.
+/// Files written before tenants existed read back as the default tenant.
+pub fn read_archive(
+    path: &Path,
+) -> Result<impl Iterator<Item = Result<Vec<ArchivedEvent>, Error>> + use<>, Error> {
+    let file = File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+
+    Ok(reader.map(|batch| events_from_batch(&batch?)))
+}
+
+/// Reads the file of an archived range, after checking it against the
+/// checksum in the manifest.
+pub fn read_range(
+    dir: &Path,
+    range: &ArchivedRange,
+) -> Result<impl Iterator<Item = Result<Vec<ArchivedEvent>, Error>> + use<>, Error> {
+    let path = dir.join(&range.file);
+    if file_sha256(&path)? != range.sha256 {
+        bail!("{} does not match its manifest checksum", path.display());
+    }
+
+    read_archive(&path)
+}
+
+/// Writes the file of `range` again with every event passed through `edit`,
+/// which returns `None` to drop it, and records the new row count and
+/// checksum in the manifest. A file left without events is removed together
+/// with its range.
+pub fn rewrite_range(
+    dir: &Path,
+    manifest: &mut Manifest,
+    range: &ArchivedRange,
+    batch_size: usize,
+    mut edit: impl FnMut(ArchivedEvent) -> Option<ArchivedEvent>,
+) -> Result<Option<ArchivedRange>, Error> {
+    let path = dir.join(&range.file);
+    let temporary = dir.join(format!("{}.tmp", range.file));
+    let keys = metadata_keys(&path)?;
+
+    let mut writer = ArchiveWriter::create(&temporary, keys, batch_size)?;
+    for events in read_range(dir, range)? {
+        for event in events? {
+            if let Some(event) = edit(event) {
+                writer.push(event)?;
+            }
+        }
+    }
+    let rows = writer.finish()?;
+
+    let position = manifest
+        .ranges
+        .iter()
+        .position(|archived| archived == range)
+        .ok_or_else(|| Error::msg(format!("{} is not in the manifest", range.file)))?;
+
+    if rows == 0 {
+        manifest.ranges.remove(position);
+        manifest.save(dir)?;
+        fs::remove_file(&temporary)?;
+        fs::remove_file(&path)?;
+        return Ok(None);
+    }
+
+    let rewritten = ArchivedRange {
+        rows,
+        sha256: file_sha256(&temporary)?,
+        ..range.clone()
+    };
+    fs::rename(&temporary, &path)?;
+    manifest.ranges[position] = rewritten.clone();
+    manifest
+        .save(dir)
+        .with_context(|| format!("{} was rewritten but the manifest was not", range.file))?;
+
+    Ok(Some(rewritten))
+}
+
+// The metadata columns of an existing file, so a rewrite keeps its layout.
+fn metadata_keys(path: &Path) -> Result<Vec<(String, MetadataKind)>, Error> {
+    let file = File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+
+    Ok(builder
+        .schema()
+        .fields()
+        .iter()
+        .filter_map(|field| {
+            let key = field.name().strip_prefix(METADATA_PREFIX)?;
+            let kind = match field.metadata().get(KIND_KEY).map(String::as_str) {
+                Some("text") => MetadataKind::Text,
+                _ => MetadataKind::Json,
+            };
+            Some((key.to_owned(), kind))
+        })
+        .collect())
+}
+
+fn events_from_batch(batch: &RecordBatch) -> Result<Vec<ArchivedEvent>, Error> {
+    let int64 = |name: &str| {
+        batch
+            .column_by_name(name)
+            .and_then(|column| column.as_any().downcast_ref::<Int64Array>())
+            .ok_or_else(|| Error::msg(format!("Archive has no int64 column {}", name)))
+    };
+    let (ids, users, types) = (int64("id")?, int64("user_id")?, int64("type_id")?);
+    let timestamps = batch
+        .column_by_name("timestamp")
+        .and_then(|column| column.as_any().downcast_ref::<TimestampMicrosecondArray>())
+        .ok_or_else(|| Error::msg("Archive has no timestamp column"))?;
+    let tenants = batch
+        .column_by_name("tenant_id")
+        .map(|_| int64("tenant_id"))
+        .transpose()?;
+
+    let mut metadata = vec![];
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        let Some(key) = field.name().strip_prefix(METADATA_PREFIX) else {
+            continue;
+        };
+        let kind = match field.metadata().get(KIND_KEY).map(String::as_str) {
+            Some("text") => MetadataKind::Text,
+            _ => MetadataKind::Json,
+        };
+        let values = column
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| Error::msg(format!("Archive column {} is not text", field.name())))?;
+        metadata.push((key.to_owned(), kind, values));
+    }
+
+    let mut events = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        let mut object = serde_json::Map::new();
+        for (key, kind, values) in &metadata {
+            if values.is_null(row) {
+                continue;
+            }
+            let value = match kind {
+                MetadataKind::Text => JsonValue::String(values.value(row).to_owned()),
+                MetadataKind::Json => serde_json::from_str(values.value(row))?,
+            };
+            object.insert(key.clone(), value);
+        }
+
+        events.push(ArchivedEvent {
+            tenant_id: tenants.map_or(DEFAULT_TENANT, |tenants| tenants.value(row)),
+            event: Event {
+                id: ids.value(row),
+                user_id: users.value(row),
+                type_id: types.value(row),
+                timestamp: DateTime::from_timestamp_micros(timestamps.value(row))
+                    .ok_or_else(|| Error::msg("Archive timestamp out of range"))?,
+                metadata: JsonValue::Object(object),
+            },
+        });
+    }
+
+    Ok(events)
+}
+
+pub fn file_sha256(path: &Path) -> Result<String, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Archives every whole period before `before` that still has events and is
+/// not in the manifest. Each period is exported and deleted inside one
+/// repeatable read transaction, so events written meanwhile stay in Postgres.
+pub async fn archive(
+    pool: &Pool<Postgres>,
+    dir: &Path,
+    interval: PartitionInterval,
+    before: DateTime<Utc>,
+    batch_size: usize,
+) -> Result<Vec<ArchivedRange>, Error> {
+    fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    let mut manifest = Manifest::load(dir)?;
+    let end = interval.start_of(before);
+
+    let (oldest,): (Option<DateTime<Utc>>,) =
+        query_as("SELECT min(timestamp) FROM events WHERE timestamp < $1")
+            .bind(end)
+            .fetch_one(pool)
+            .await?;
+
+    let mut archived = vec![];
+    let Some(oldest) = oldest else {
+        return Ok(archived);
+    };
+
+    let mut start = interval.start_of(oldest);
+    while start < end {
+        let next = interval.next(start);
+
+        if manifest.covers(start, next) {
+            tracing::warn!(%start, "period already archived but has events again, skipping");
+        } else if let Some(range) =
+            archive_period(pool, dir, interval, start, next, batch_size).await?
+        {
+            manifest.insert(range.clone());
+            manifest.save(dir).with_context(|| {
+                format!(
+                    "{} was archived and deleted but is missing from the manifest",
+                    range.file
+                )
+            })?;
+            archived.push(range);
+        }
+
+        start = next;
+    }
+
+    Ok(archived)
+}
+
+async fn archive_period(
+    pool: &Pool<Postgres>,
+    dir: &Path,
+    interval: PartitionInterval,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    batch_size: usize,
+) -> Result<Option<ArchivedRange>, Error> {
+    let mut tx = pool.begin().await?;
+    tx.execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .await?;
+
+    let keys: Vec<(String, bool)> = query_as(
+        r#"
+        SELECT m.key, bool_and(jsonb_typeof(m.value) = 'string')
+        FROM events e, jsonb_each(CASE jsonb_typeof(e.metadata) WHEN 'object' THEN e.metadata ELSE '{}' END) m
+        WHERE e.timestamp >= $1 AND e.timestamp < $2
+        GROUP BY m.key
+        ORDER BY m.key
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *tx)
+    .await?;
+    let keys = keys
+        .into_iter()
+        .map(|(key, text)| match text {
+            true => (key, MetadataKind::Text),
+            false => (key, MetadataKind::Json),
+        })
+        .collect();
+
+    let file = format!("{}.parquet", interval.name(from));
+    let path = dir.join(&file);
+    let temporary = dir.join(format!("{}.tmp", file));
+    let mut writer = ArchiveWriter::create(&temporary, keys, batch_size)?;
+
+    {
+        let mut events = query_as::<_, ArchivedEvent>(
+            r#"
+            SELECT id, user_id, type_id, timestamp, metadata, tenant_id FROM events
+            WHERE timestamp >= $1 AND timestamp < $2
+            ORDER BY timestamp, id
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .fetch(&mut *tx);
+
+        while let Some(event) = events.try_next().await? {
+            writer.push(event)?;
+        }
+    }
+
+    let rows = writer.finish()?;
+    if rows == 0 {
+        fs::remove_file(&temporary)?;
+        return Ok(None);
+    }
+
+    let deleted = query("DELETE FROM events WHERE timestamp >= $1 AND timestamp < $2")
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if deleted != rows {
+        fs::remove_file(&temporary)?;
+        bail!(
+            "Exported {} events from {} but the delete matched {}",
+            rows,
+            file,
+            deleted
+        );
+    }
+
+    fs::rename(&temporary, &path)?;
+    tx.commit().await?;
+
+    Ok(Some(ArchivedRange {
+        sha256: file_sha256(&path)?,
+        file,
+        from,
+        to,
+        rows,
+        archived_at: Utc::now(),
+    }))
+}
+
+/// Loads the archived ranges overlapping `from..to` back into `events` and
+/// removes them from the archive. Returns the restored ranges.
+pub async fn restore(
+    pool: &Pool<Postgres>,
+    dir: &Path,
+    interval: PartitionInterval,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<ArchivedRange>, Error> {
+    let mut manifest = Manifest::load(dir)?;
+    let selected: Vec<ArchivedRange> = manifest
+        .ranges
+        .iter()
+        .filter(|range| {
+            from.is_none_or(|from| range.to > from) && to.is_none_or(|to| range.from < to)
+        })
+        .cloned()
+        .collect();
+
+    let partitioned = is_partitioned(pool).await?;
+    let mut restored = vec![];
+
+    for range in selected {
+        if partitioned {
+            ensure_range(
+                pool,
+                interval,
+                range.from,
+                range.to - chrono::Duration::microseconds(1),
+            )
+            .await?;
+        }
+
+        let mut tx = pool.begin().await?;
+        let mut inserted: u64 = 0;
+        for events in read_range(dir, &range)? {
+            inserted += insert_events(&mut tx, &events?).await?;
+        }
+        if inserted != range.rows {
+            bail!(
+                "{} holds {} events but only {} were new, nothing restored from it",
+                range.file,
+                range.rows,
+                inserted
+            );
+        }
+        tx.commit().await?;
+
+        manifest.ranges.retain(|archived| archived != &range);
+        manifest.save(dir)?;
+        fs::remove_file(dir.join(&range.file))?;
+        restored.push(range);
+    }
+
+    Ok(restored)
+}
+
+async fn insert_events(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    events: &[ArchivedEvent],
+) -> Result<u64, Error> {
+    Ok(query(
+        r#"
+        INSERT INTO events (id, user_id, type_id, timestamp, metadata, tenant_id)
+        SELECT * FROM UNNEST(
+            $1::bigint[], $2::bigint[], $3::bigint[], $4::timestamptz[], $5::jsonb[], $6::bigint[]
+        )
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(events.iter().map(|row| row.event.id).collect::<Vec<_>>())
+    .bind(
+        events
+            .iter()
+            .map(|row| row.event.user_id)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        events
+            .iter()
+            .map(|row| row.event.type_id)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        events
+            .iter()
+            .map(|row| row.event.timestamp)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        events
+            .iter()
+            .map(|row| &row.event.metadata)
+            .collect::<Vec<_>>(),
+    )
+    .bind(events.iter().map(|row| row.tenant_id).collect::<Vec<_>>())
+    .execute(&mut **tx)
+    .await?
+    .rows_affected())
+}
+
+/// Files in the archive directory that the manifest does not know about.
+pub fn stray_files(dir: &Path, manifest: &Manifest) -> Result<Vec<PathBuf>, Error> {
+    let known: HashSet<&str> = manifest
+        .ranges
+        .iter()
+        .map(|range| range.file.as_str())
+        .collect();
+
+    let mut stray = vec![];
+    if !dir.exists() {
+        return Ok(stray);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name != MANIFEST_FILE && !known.contains(name) {
+            stray.push(path);
+        }
+    }
+    stray.sort();
+
+    Ok(stray)
+}
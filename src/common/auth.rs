@@ -0,0 +1,209 @@
+use std::{fmt, str::FromStr};
+
+use actix_web::{
+    Error, HttpMessage, ResponseError,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::{Method, header::AUTHORIZATION},
+    middleware::Next,
+    web,
+};
+use anyhow::bail;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    common::{
+        error::AppError,
+        rate_limit::limit_key_lookup,
+        tenant::{TENANT_HEADER, Tenant},
+    },
+    contexts::auth::infrastructure::api_keys::ApiKeys,
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const KEY_PREFIX: &str = "wck_";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "events:write")]
+    EventsWrite,
+    #[serde(rename = "events:read")]
+    EventsRead,
+    #[serde(rename = "stats:read")]
+    StatsRead,
+    /// Every other scope, plus key management and GDPR requests.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::EventsWrite => "events:write",
+            Scope::EventsRead => "events:read",
+            Scope::StatsRead => "stats:read",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "events:write" => Scope::EventsWrite,
+            "events:read" => Scope::EventsRead,
+            "stats:read" => Scope::StatsRead,
+            "admin" => Scope::Admin,
+            other => bail!(
+                "Unknown scope `{}`, expected events:write, events:read, stats:read or admin",
+                other
+            ),
+        })
+    }
+}
+
+/// The key a request was made with, kept in the request extensions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuthenticatedKey {
+    pub id: i64,
+    pub scopes: Vec<Scope>,
+    /// Set for keys that may only act for this tenant.
+    #[serde(default)]
+    pub tenant_id: Option<i64>,
+}
+
+impl AuthenticatedKey {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes
+            .iter()
+            .any(|granted| *granted == scope || *granted == Scope::Admin)
+    }
+}
+
+// Routes behind authentication and the scope each needs. Anything missing
+// here needs `admin`.
+const ROUTE_SCOPES: [(Method, &str, Scope); 4] = [
+    (Method::POST, "/event", Scope::EventsWrite),
+    (Method::GET, "/events", Scope::EventsRead),
+    (Method::GET, "/users/{user_id}/events", Scope::EventsRead),
+    (Method::GET, "/stats", Scope::StatsRead),
+];
+
+pub fn required_scope(method: &Method, pattern: &str) -> Scope {
+    ROUTE_SCOPES
+        .iter()
+        .find(|(m, p, _)| m == method && *p == pattern)
+        .map_or(Scope::Admin, |(_, _, scope)| *scope)
+}
+
+/// A new random key and the SHA-256 that gets stored instead of it.
+pub fn generate_key() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+
+    let key = format!("{}{}", KEY_PREFIX, hex(&bytes));
+    let hash = hash_key(&key);
+    (key, hash)
+}
+
+pub fn hash_key(key: &str) -> String {
+    hex(&Sha256::digest(key.as_bytes()))
+}
+
+/// Enough of a key to recognise it in listings.
+pub fn display_prefix(key: &str) -> String {
+    key.chars().take(KEY_PREFIX.len() + 8).collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Takes the key from `Authorization: Bearer` or `X-Api-Key`, checks it
+/// grants the scope of the matched route, resolves the tenant and records
+/// both on the request span. Without `ApiKeys` in the app data every request
+/// is refused.
+pub async fn authenticate<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    // Answered here rather than returned, so the request id middleware still
+    // tags the response.
+    match check(&req).await {
+        Ok(()) => Ok(next.call(req).await?.map_into_left_body()),
+        Err(error) => Ok(req
+            .into_response(error.error_response())
+            .map_into_right_body()),
+    }
+}
+
+async fn check(req: &ServiceRequest) -> Result<(), AppError> {
+    let Some(keys) = req.app_data::<web::Data<ApiKeys>>() else {
+        return Err(AppError::Internal(anyhow::Error::msg(
+            "API keys are not configured",
+        )));
+    };
+
+    if !keys.enabled() {
+        let tenant = Tenant::resolve(req.headers().get(TENANT_HEADER), None)?;
+        tracing::Span::current().record("tenant_id", tenant.0);
+
+        req.extensions_mut().insert(tenant);
+        return Ok(());
+    }
+
+    let presented = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            req.headers()
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or(AppError::Unauthorized)?;
+
+    // Only lookups that reach Postgres spend from the client's budget.
+    let key = match keys.cached(presented).await {
+        Some(key) => key,
+        None => {
+            limit_key_lookup(req.request()).await?;
+            keys.lookup(presented)
+                .await?
+                .ok_or(AppError::Unauthorized)?
+        }
+    };
+
+    tracing::Span::current().record("key_id", key.id);
+
+    let pattern = req.match_pattern().unwrap_or_default();
+    let scope = required_scope(req.method(), &pattern);
+    if !key.allows(scope) {
+        return Err(AppError::Forbidden(scope));
+    }
+    // Keys are shared by every tenant, so only unbound keys manage them.
+    if key.tenant_id.is_some() && pattern.starts_with("/admin/keys") {
+        return Err(AppError::Forbidden(Scope::Admin));
+    }
+
+    let tenant = Tenant::resolve(req.headers().get(TENANT_HEADER), key.tenant_id)?;
+    tracing::Span::current().record("tenant_id", tenant.0);
+
+    let mut extensions = req.extensions_mut();
+    extensions.insert(key);
+    extensions.insert(tenant);
+    Ok(())
+}
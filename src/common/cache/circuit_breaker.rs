@@ -0,0 +1,47 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+pub struct CircuitBreaker {
+    failures: AtomicU32,
+    open: AtomicBool,
+    threshold: u32,
+}
+
+impl CircuitBreaker {
+    pub fn create(threshold: u32) -> CircuitBreaker {
+        CircuitBreaker {
+            failures: AtomicU32::new(0),
+            open: AtomicBool::new(false),
+            threshold: threshold.max(1),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Acquire)
+    }
+
+    pub fn record_success(&self) {
+        self.failures.store(0, Ordering::Release);
+    }
+
+    // Returns true only for the failure that trips the breaker, so exactly one
+    // caller becomes responsible for scheduling the recovery.
+    pub fn record_failure(&self) -> bool {
+        let failures = self.failures.fetch_add(1, Ordering::AcqRel) + 1;
+        if failures < self.threshold {
+            return false;
+        }
+
+        self.trip()
+    }
+
+    pub fn trip(&self) -> bool {
+        self.open
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    pub fn close(&self) {
+        self.failures.store(0, Ordering::Release);
+        self.open.store(false, Ordering::Release);
+    }
+}
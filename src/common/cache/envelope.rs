@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+// Bump whenever a cached payload type (EventWithType, Stat, PaginatedEvents,
+// EventTypeRow...) changes shape, so entries written by the previous deploy
+// are read as misses instead of being served to clients.
+pub const CACHE_SCHEMA_VERSION: u16 = 2;
+
+const MAGIC: [u8; 2] = *b"WC";
+const HEADER_LEN: usize = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl Codec {
+    fn id(self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Lz4 => 1,
+            Codec::Zstd => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Codec> {
+        match id {
+            0 => Some(Codec::None),
+            1 => Some(Codec::Lz4),
+            2 => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Codec {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(Codec::None),
+            "lz4" => Ok(Codec::Lz4),
+            "zstd" => Ok(Codec::Zstd),
+            other => Err(Error::msg(format!("Unknown cache codec `{}`", other))),
+        }
+    }
+}
+
+// Layout: magic (2) | schema version (u16 LE) | codec id (1) | crc32 of the
+// decoded payload (u32 LE) | body.
+#[derive(Clone, Copy, Debug)]
+pub struct Envelope {
+    pub codec: Codec,
+    pub threshold: usize,
+    pub version: u16,
+}
+
+impl Envelope {
+    pub fn create(codec: Codec, threshold: usize) -> Envelope {
+        Envelope {
+            codec,
+            threshold,
+            version: CACHE_SCHEMA_VERSION,
+        }
+    }
+
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let codec = if payload.len() >= self.threshold {
+            self.codec
+        } else {
+            Codec::None
+        };
+
+        let (codec, body) = match codec {
+            Codec::None => (Codec::None, payload.to_vec()),
+            Codec::Lz4 => (Codec::Lz4, lz4_flex::compress_prepend_size(payload)),
+            Codec::Zstd => match zstd::bulk::compress(payload, 0) {
+                Ok(body) => (Codec::Zstd, body),
+                Err(_) => (Codec::None, payload.to_vec()),
+            },
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.push(codec.id());
+        bytes.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Option<Vec<u8>> {
+        if bytes.len() < HEADER_LEN || bytes[..2] != MAGIC {
+            return None;
+        }
+
+        let version = u16::from_le_bytes([bytes[2], bytes[3]]);
+        if version != self.version {
+            return None;
+        }
+
+        let codec = Codec::from_id(bytes[4])?;
+        let checksum = u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+        let body = &bytes[HEADER_LEN..];
+
+        let payload = match codec {
+            Codec::None => body.to_vec(),
+            Codec::Lz4 => lz4_flex::decompress_size_prepended(body).ok()?,
+            Codec::Zstd => zstd::stream::decode_all(body).ok()?,
+        };
+
+        if crc32fast::hash(&payload) != checksum {
+            return None;
+        }
+
+        Some(payload)
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use moka::{Expiry, future::Cache, notification::RemovalCause};
+
+use crate::common::{cache::store::CacheStore, metrics::METRICS};
+
+#[derive(Clone)]
+struct MemoryEntry {
+    bytes: Vec<u8>,
+    ttl: Duration,
+}
+
+struct EntryExpiry;
+
+impl Expiry<String, MemoryEntry> for EntryExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &MemoryEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &MemoryEntry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+}
+
+type Tags = Arc<Mutex<HashMap<String, HashSet<String>>>>;
+
+pub struct MemoryStore {
+    lru: Cache<String, MemoryEntry>,
+    tags: Tags,
+}
+
+impl MemoryStore {
+    pub fn create(capacity_mb: u64) -> MemoryStore {
+        let tags: Tags = Arc::new(Mutex::new(HashMap::new()));
+
+        // A key that leaves the cache leaves its tags too, so they only ever
+        // hold live entries. A replaced key is still cached and keeps them.
+        let listener_tags = tags.clone();
+        let lru: Cache<String, MemoryEntry> = Cache::builder()
+            .weigher(|_, v: &MemoryEntry| v.bytes.len().try_into().unwrap_or(u32::MAX))
+            .max_capacity(capacity_mb * 1024 * 1024)
+            .expire_after(EntryExpiry)
+            .eviction_listener(move |key: Arc<String>, _, cause| {
+                if cause != RemovalCause::Replaced {
+                    untag_key(&listener_tags, &key);
+                }
+            })
+            .build();
+
+        MemoryStore { lru, tags }
+    }
+}
+
+fn untag_key(tags: &Tags, key: &str) {
+    let Ok(mut tags) = tags.lock() else {
+        return;
+    };
+
+    tags.retain(|_, keys| {
+        keys.remove(key);
+        !keys.is_empty()
+    });
+}
+
+#[async_trait]
+impl CacheStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.lru.get(key).await.map(|entry| entry.bytes);
+        METRICS.observe_cache("l1", key, value.is_some());
+
+        Ok(value)
+    }
+
+    async fn set_ex(&self, key: &str, value: Vec<u8>, seconds: u64) -> Result<(), Error> {
+        let entry = MemoryEntry {
+            bytes: value,
+            ttl: Duration::from_secs(seconds),
+        };
+
+        self.lru.insert(key.to_owned(), entry).await;
+        Ok(())
+    }
+
+    async fn del(&self, key: &str) -> Result<(), Error> {
+        self.lru.invalidate(key).await;
+        Ok(())
+    }
+
+    async fn tag(&self, tag: &str, key: &str, _seconds: u64) -> Result<(), Error> {
+        let mut tags = self.tags.lock().map_err(|e| Error::msg(e.to_string()))?;
+        tags.entry(tag.to_owned())
+            .or_default()
+            .insert(key.to_owned());
+        Ok(())
+    }
+
+    async fn tagged(&self, tag: &str) -> Result<Vec<String>, Error> {
+        // Expired entries are only evicted, and untagged, on maintenance.
+        self.lru.run_pending_tasks().await;
+
+        let tags = self.tags.lock().map_err(|e| Error::msg(e.to_string()))?;
+        Ok(tags
+            .get(tag)
+            .map(|keys| keys.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn untag(&self, tag: &str) -> Result<(), Error> {
+        let mut tags = self.tags.lock().map_err(|e| Error::msg(e.to_string()))?;
+        tags.remove(tag);
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::Error;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+
+use crate::common::cache::{envelope::Envelope, store::CacheStore};
+
+pub mod circuit_breaker;
+pub mod envelope;
+pub mod memory_store;
+pub mod noop_store;
+pub mod redis_store;
+pub mod store;
+pub mod tiered_store;
+
+pub enum CacheSetKey {
+    Exact(String),
+    Pattern(String, Vec<String>),
+}
+
+pub enum CacheDeleteKey {
+    Exact(String),
+    Pattern(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheTopology {
+    Tiered,
+    Memory,
+    Redis,
+    None,
+}
+
+impl CacheTopology {
+    pub fn needs_redis(&self) -> bool {
+        matches!(self, CacheTopology::Tiered | CacheTopology::Redis)
+    }
+}
+
+impl FromStr for CacheTopology {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "tiered" => Ok(CacheTopology::Tiered),
+            "memory" => Ok(CacheTopology::Memory),
+            "redis" => Ok(CacheTopology::Redis),
+            "none" => Ok(CacheTopology::None),
+            other => Err(Error::msg(format!("Unknown cache topology `{}`", other))),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LeveledCache {
+    store: Arc<dyn CacheStore>,
+    envelope: Envelope,
+}
+
+impl LeveledCache {
+    pub fn create(store: Arc<dyn CacheStore>, envelope: Envelope) -> LeveledCache {
+        LeveledCache { store, envelope }
+    }
+
+    pub async fn try_get(&self, key: String) -> Option<Vec<u8>> {
+        let bytes = self.store.get(&key).await.ok().flatten()?;
+
+        self.envelope.decode(&bytes)
+    }
+
+    pub async fn save(&self, key: CacheSetKey, value: Vec<u8>, seconds: u64) -> Result<(), Error> {
+        let value = self.envelope.encode(&value);
+
+        match key {
+            CacheSetKey::Exact(key) => self.store.set_ex(&key, value, seconds).await,
+            CacheSetKey::Pattern(pattern, values) => {
+                let key = fill_placeholders(pattern.clone(), &values);
+
+                self.store.set_ex(&key, value, seconds).await?;
+                self.store.tag(&pattern, &key, seconds).await
+            }
+        }
+    }
+
+    pub async fn invalidate(&self, key: CacheDeleteKey) -> Result<(), Error> {
+        match key {
+            CacheDeleteKey::Exact(key) => self.store.del(&key).await,
+            CacheDeleteKey::Pattern(pattern) => {
+                let cache_keys = self.store.tagged(&pattern).await?;
+
+                try_join_all(cache_keys.iter().map(|cache_key| self.store.del(cache_key))).await?;
+
+                self.store.untag(&pattern).await
+            }
+        }
+    }
+}
+
+fn fill_placeholders(mut template: String, values: &[String]) -> String {
+    for val in values {
+        template = template.replacen("{}", val, 1);
+    }
+    template
+}
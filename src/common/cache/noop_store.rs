@@ -0,0 +1,33 @@
+use anyhow::Error;
+use async_trait::async_trait;
+
+use crate::common::cache::store::CacheStore;
+
+pub struct NoopStore;
+
+#[async_trait]
+impl CacheStore for NoopStore {
+    async fn get(&self, _key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    async fn set_ex(&self, _key: &str, _value: Vec<u8>, _seconds: u64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn del(&self, _key: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn tag(&self, _tag: &str, _key: &str, _seconds: u64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn tagged(&self, _tag: &str) -> Result<Vec<String>, Error> {
+        Ok(vec![])
+    }
+
+    async fn untag(&self, _tag: &str) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use redis::{
+    AsyncCommands, AsyncConnectionConfig, Client, FromRedisValue, RedisResult, Script,
+    aio::MultiplexedConnection,
+};
+
+use crate::common::{
+    cache::{circuit_breaker::CircuitBreaker, store::CacheStore},
+    metrics::METRICS,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RedisSettings {
+    pub timeout: Duration,
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+#[derive(Clone)]
+pub struct RedisStore {
+    inner: Arc<RedisInner>,
+}
+
+struct RedisInner {
+    client: Client,
+    settings: RedisSettings,
+    connection: RwLock<Option<MultiplexedConnection>>,
+    breaker: CircuitBreaker,
+}
+
+impl RedisStore {
+    pub async fn connect(client: Client, settings: RedisSettings) -> RedisStore {
+        let store = RedisStore {
+            inner: Arc::new(RedisInner {
+                client,
+                settings,
+                connection: RwLock::new(None),
+                breaker: CircuitBreaker::create(settings.failure_threshold),
+            }),
+        };
+
+        match store.inner.open_connection().await {
+            Ok(connection) => store.inner.replace_connection(Some(connection)),
+            Err(error) => {
+                tracing::warn!(error = %error, "redis unavailable, running without L2");
+                if store.inner.breaker.trip() {
+                    store.spawn_recovery();
+                }
+            }
+        }
+
+        store
+    }
+
+    pub fn is_available(&self) -> bool {
+        !self.inner.breaker.is_open()
+    }
+
+    pub async fn ping(&self) -> Result<(), Error> {
+        self.call(|mut conn| async move { redis::cmd("PING").query_async::<()>(&mut conn).await })
+            .await
+    }
+
+    pub fn connection(&self) -> Option<MultiplexedConnection> {
+        if self.inner.breaker.is_open() {
+            return None;
+        }
+
+        self.inner.current_connection()
+    }
+
+    /// Runs a Lua script through the shared connection, loading it on the
+    /// first `NOSCRIPT`. Failures count towards the circuit breaker.
+    pub async fn eval_script<T: FromRedisValue>(
+        &self,
+        script: &Script,
+        keys: &[&str],
+        args: &[String],
+    ) -> Result<T, Error> {
+        self.call(|mut conn| async move {
+            let mut invocation = script.prepare_invoke();
+            for key in keys {
+                invocation.key(*key);
+            }
+            for arg in args {
+                invocation.arg(arg);
+            }
+            invocation.invoke_async(&mut conn).await
+        })
+        .await
+    }
+
+    async fn call<T, F, Fut>(&self, command: F) -> Result<T, Error>
+    where
+        F: FnOnce(MultiplexedConnection) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let connection = self
+            .connection()
+            .ok_or_else(|| Error::msg("Redis circuit is open"))?;
+
+        match command(connection).await {
+            Ok(value) => {
+                self.inner.breaker.record_success();
+                Ok(value)
+            }
+            Err(error) => {
+                if self.inner.breaker.record_failure() {
+                    tracing::warn!(error = %error, "redis circuit opened");
+                    self.inner.replace_connection(None);
+                    self.spawn_recovery();
+                }
+                Err(Error::from(error))
+            }
+        }
+    }
+
+    fn spawn_recovery(&self) {
+        let inner = Arc::clone(&self.inner);
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(inner.settings.cooldown).await;
+
+                let Ok(mut connection) = inner.open_connection().await else {
+                    continue;
+                };
+
+                if redis::cmd("PING")
+                    .query_async::<()>(&mut connection)
+                    .await
+                    .is_ok()
+                {
+                    inner.replace_connection(Some(connection));
+                    inner.breaker.close();
+                    tracing::info!("redis circuit closed");
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl RedisInner {
+    async fn open_connection(&self) -> RedisResult<MultiplexedConnection> {
+        let config = AsyncConnectionConfig::new()
+            .set_connection_timeout(self.settings.timeout)
+            .set_response_timeout(self.settings.timeout);
+
+        self.client
+            .get_multiplexed_async_connection_with_config(&config)
+            .await
+    }
+
+    fn current_connection(&self) -> Option<MultiplexedConnection> {
+        self.connection
+            .read()
+            .ok()
+            .and_then(|connection| connection.clone())
+    }
+
+    fn replace_connection(&self, connection: Option<MultiplexedConnection>) {
+        if let Ok(mut current) = self.connection.write() {
+            *current = connection;
+        }
+    }
+}
+
+fn tag_key(tag: &str) -> String {
+    format!("tag:{}", tag)
+}
+
+#[async_trait]
+impl CacheStore for RedisStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let value: Result<Option<Vec<u8>>, Error> = self
+            .call(|mut conn| async move { conn.get(key).await })
+            .await;
+        METRICS.observe_cache("l2", key, matches!(value, Ok(Some(_))));
+
+        value
+    }
+
+    async fn set_ex(&self, key: &str, value: Vec<u8>, seconds: u64) -> Result<(), Error> {
+        self.call(|mut conn| async move { conn.set_ex(key, value, seconds).await })
+            .await
+    }
+
+    async fn del(&self, key: &str) -> Result<(), Error> {
+        self.call(|mut conn| async move { conn.del(key).await })
+            .await
+    }
+
+    // NX gives a new set the member's TTL and GT only ever lengthens it, so
+    // the set outlives its longest-lived member and then goes away with it.
+    async fn tag(&self, tag: &str, key: &str, seconds: u64) -> Result<(), Error> {
+        let tag = tag_key(tag);
+
+        self.call(|mut conn| async move {
+            redis::pipe()
+                .sadd(&tag, key)
+                .ignore()
+                .cmd("EXPIRE")
+                .arg(&tag)
+                .arg(seconds)
+                .arg("NX")
+                .ignore()
+                .cmd("EXPIRE")
+                .arg(&tag)
+                .arg(seconds)
+                .arg("GT")
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        })
+        .await
+    }
+
+    async fn tagged(&self, tag: &str) -> Result<Vec<String>, Error> {
+        self.call(|mut conn| async move { conn.smembers(tag_key(tag)).await })
+            .await
+    }
+
+    async fn untag(&self, tag: &str) -> Result<(), Error> {
+        self.call(|mut conn| async move { conn.del(tag_key(tag)).await })
+            .await
+    }
+}
@@ -0,0 +1,19 @@
+use anyhow::Error;
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    async fn set_ex(&self, key: &str, value: Vec<u8>, seconds: u64) -> Result<(), Error>;
+
+    async fn del(&self, key: &str) -> Result<(), Error>;
+
+    /// Records `key` under `tag`; the record lasts at least as long as the
+    /// `seconds` the key was stored for.
+    async fn tag(&self, tag: &str, key: &str, seconds: u64) -> Result<(), Error>;
+
+    async fn tagged(&self, tag: &str) -> Result<Vec<String>, Error>;
+
+    async fn untag(&self, tag: &str) -> Result<(), Error>;
+}
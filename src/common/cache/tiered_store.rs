@@ -0,0 +1,169 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use anyhow::Error;
+use async_trait::async_trait;
+use tokio::join;
+
+use crate::common::{
+    cache::store::CacheStore,
+    metrics::{METRICS, key_family},
+};
+
+const L2_RETRIES: u32 = 3;
+const L2_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// L1 is authoritative for the process: an L2 failure is treated as a miss on
+// reads and ignored on writes, so a Redis outage degrades to memory and then
+// to Postgres instead of failing the request. A failed L2 delete or untag
+// would leave other instances serving the stale entry, so it is retried in
+// the background and counted until it succeeds or runs out of attempts.
+pub struct TieredStore {
+    l1: Arc<dyn CacheStore>,
+    l2: Arc<dyn CacheStore>,
+    backfill_seconds: u64,
+}
+
+impl TieredStore {
+    pub fn create(
+        l1: Arc<dyn CacheStore>,
+        l2: Arc<dyn CacheStore>,
+        backfill_seconds: u64,
+    ) -> TieredStore {
+        TieredStore {
+            l1,
+            l2,
+            backfill_seconds,
+        }
+    }
+
+    fn retry_on_l2(&self, invalidation: Invalidation, target: &str, result: Result<(), Error>) {
+        let Err(error) = result else {
+            return;
+        };
+        invalidation.failed(target, &error, "retrying");
+
+        let l2 = self.l2.clone();
+        let target = target.to_owned();
+        tokio::spawn(async move {
+            let mut delay = L2_RETRY_DELAY;
+
+            for attempt in 1..=L2_RETRIES {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+
+                let Err(error) = invalidation.run(l2.as_ref(), &target).await else {
+                    tracing::info!(
+                        op = invalidation.name(),
+                        key = %target,
+                        attempt,
+                        "l2 invalidation retried"
+                    );
+                    return;
+                };
+                let outcome = if attempt == L2_RETRIES {
+                    "dropped"
+                } else {
+                    "retrying"
+                };
+                invalidation.failed(&target, &error, outcome);
+            }
+        });
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Invalidation {
+    Del,
+    Untag,
+}
+
+impl Invalidation {
+    fn name(self) -> &'static str {
+        match self {
+            Invalidation::Del => "del",
+            Invalidation::Untag => "untag",
+        }
+    }
+
+    async fn run(self, store: &dyn CacheStore, target: &str) -> Result<(), Error> {
+        match self {
+            Invalidation::Del => store.del(target).await,
+            Invalidation::Untag => store.untag(target).await,
+        }
+    }
+
+    fn failed(self, target: &str, error: &Error, outcome: &str) {
+        METRICS
+            .cache_invalidation_failures
+            .with_label_values(&[self.name(), key_family(target), outcome])
+            .inc();
+
+        if outcome == "dropped" {
+            tracing::error!(
+                op = self.name(),
+                key = %target,
+                error = %error,
+                "l2 invalidation gave up, the entry stays until its ttl"
+            );
+        } else {
+            tracing::warn!(op = self.name(), key = %target, error = %error, "l2 invalidation failed");
+        }
+    }
+}
+
+#[async_trait]
+impl CacheStore for TieredStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        if let Some(bytes) = self.l1.get(key).await? {
+            return Ok(Some(bytes));
+        }
+
+        let value = self.l2.get(key).await.unwrap_or(None);
+        if let Some(bytes) = &value {
+            self.l1
+                .set_ex(key, bytes.clone(), self.backfill_seconds)
+                .await?;
+        }
+
+        Ok(value)
+    }
+
+    async fn set_ex(&self, key: &str, value: Vec<u8>, seconds: u64) -> Result<(), Error> {
+        let (_, l1) = join!(
+            self.l2.set_ex(key, value.clone(), seconds),
+            self.l1.set_ex(key, value, seconds)
+        );
+        l1
+    }
+
+    async fn del(&self, key: &str) -> Result<(), Error> {
+        let (l2, l1) = join!(self.l2.del(key), self.l1.del(key));
+        self.retry_on_l2(Invalidation::Del, key, l2);
+        l1
+    }
+
+    async fn tag(&self, tag: &str, key: &str, seconds: u64) -> Result<(), Error> {
+        let (_, l1) = join!(
+            self.l2.tag(tag, key, seconds),
+            self.l1.tag(tag, key, seconds)
+        );
+        l1
+    }
+
+    async fn tagged(&self, tag: &str) -> Result<Vec<String>, Error> {
+        let (l2_keys, l1_keys) = join!(self.l2.tagged(tag), self.l1.tagged(tag));
+
+        let keys: HashSet<String> = l2_keys
+            .unwrap_or_default()
+            .into_iter()
+            .chain(l1_keys?)
+            .collect();
+        Ok(keys.into_iter().collect())
+    }
+
+    async fn untag(&self, tag: &str) -> Result<(), Error> {
+        let (l2, l1) = join!(self.l2.untag(tag), self.l1.untag(tag));
+        self.retry_on_l2(Invalidation::Untag, tag, l2);
+        l1
+    }
+}
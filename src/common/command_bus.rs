@@ -1,11 +1,27 @@
-use std::{any::Any, collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use chrono::{DateTime, Utc};
 use serde_json::Value;
-use sqlx::{Executor, Pool, Postgres, postgres::PgArguments, query::Query};
-use tokio::sync::{Notify, RwLock};
+use sqlx::{
+    Executor, Pool, Postgres,
+    postgres::{PgArguments, PgRow},
+    query::Query,
+};
+use tokio::{
+    sync::{Notify, RwLock},
+    task::JoinHandle,
+};
+use tracing::{Instrument, Span};
 
-use crate::common::output::send_group;
+use crate::common::metrics::METRICS;
 use sqlx::types::JsonValue;
 
 #[derive(Clone, Debug)]
@@ -16,25 +32,51 @@ pub enum CommandValue {
     Timestamp(DateTime<Utc>),
 }
 
+pub type CommandCallback = Box<dyn Fn(&dyn Any) + Send + Sync>;
+
+type QueryQueue = Arc<RwLock<HashMap<String, Vec<Vec<CommandValue>>>>>;
+type CallbackMap = Arc<RwLock<HashMap<String, CommandCallback>>>;
+type SpanLinks = Arc<Mutex<Vec<Span>>>;
+type OldestPush = Arc<Mutex<Option<Instant>>>;
+
+// Upper bound on request spans linked from a single flush span.
+const MAX_SPAN_LINKS: usize = 256;
+
 pub struct CommandBus {
-    queries: Arc<RwLock<HashMap<String, Vec<Vec<CommandValue>>>>>,
-    callbacks: Arc<RwLock<HashMap<String, Box<dyn Fn(&dyn Any) + Send + Sync>>>>,
+    queries: QueryQueue,
+    callbacks: CallbackMap,
+    spans: SpanLinks,
+    oldest: OldestPush,
+    notifier: Arc<Notify>,
+    stopping: Arc<AtomicBool>,
+    worker: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl CommandBus {
-    pub fn init(duration: Duration, postgres: Pool<Postgres>) -> CommandBus {
-        let queries: Arc<RwLock<HashMap<String, Vec<Vec<CommandValue>>>>> =
-            Arc::new(RwLock::new(HashMap::new()));
-        let callbacks: Arc<RwLock<HashMap<String, Box<dyn Fn(&dyn Any) + Send + Sync>>>> =
-            Arc::new(RwLock::new(HashMap::new()));
+    // `synchronous_commit` is set per flush transaction, whatever the server
+    // default is.
+    pub fn init(
+        duration: Duration,
+        chunk_size: usize,
+        synchronous_commit: bool,
+        postgres: Pool<Postgres>,
+    ) -> CommandBus {
+        let queries: QueryQueue = Arc::new(RwLock::new(HashMap::new()));
+        let callbacks: CallbackMap = Arc::new(RwLock::new(HashMap::new()));
+        let spans: SpanLinks = Arc::new(Mutex::new(Vec::new()));
+        let oldest: OldestPush = Arc::new(Mutex::new(None));
         let notifier = Arc::new(Notify::new());
+        let stopping = Arc::new(AtomicBool::new(false));
 
         let queries_clone = Arc::clone(&queries);
         let callbacks_clone = Arc::clone(&callbacks);
+        let spans_clone = Arc::clone(&spans);
+        let oldest_clone = Arc::clone(&oldest);
         let postgres_clone = postgres.clone();
         let notifier_clone = Arc::clone(&notifier);
+        let stopping_clone = Arc::clone(&stopping);
 
-        tokio::spawn(async move {
+        let worker = tokio::spawn(async move {
             let mut interval = tokio::time::interval(duration);
 
             loop {
@@ -45,66 +87,184 @@ impl CommandBus {
                     }
                 }
 
+                // Set before the wake-up, so this round takes the last rows.
+                let stopping = stopping_clone.load(Ordering::Acquire);
+
                 let mut queries = queries_clone.write().await;
                 if queries.is_empty() {
+                    if stopping {
+                        break;
+                    }
                     continue;
                 }
 
-                for (query, param_sets) in queries.drain() {
-                    for chunk in param_sets.chunks(2000) {
-                        let chunk = chunk.to_vec();
-
-                        if query.to_lowercase().contains("unnest") {
-                            let q = bind_unnest(sqlx::query(&query), &chunk);
-                            match q.fetch_one(&postgres_clone).await {
-                                Ok(row) => {
-                                    let read_callback = callbacks_clone.read().await;
-                                    if let Some(function) = read_callback.get(&query) {
-                                        function(&row);
-                                    }
-                                }
-                                Err(e) => send_group(e.to_string()),
-                            }
-                        } else {
-                            for _ in &chunk {
-                                match postgres_clone.fetch_one(query.as_str()).await {
-                                    Ok(row) => {
-                                        let read_callback = callbacks_clone.read().await;
-                                        if let Some(function) = read_callback.get(&query) {
-                                            function(&row);
-                                        }
-                                    }
-                                    Err(e) => send_group(e.to_string()),
-                                }
-                            }
-                        }
+                let flush_timer = METRICS.bus_flush_duration.start_timer();
+                let rows: usize = queries.values().map(Vec::len).sum();
+                METRICS.bus_queue_depth.sub(rows as i64);
+                METRICS.bus_rows_per_flush.observe(rows as f64);
+
+                let flush_span = tracing::info_span!("command_bus.flush", rows);
+                if let Ok(mut parents) = spans_clone.lock() {
+                    for parent in parents.drain(..) {
+                        flush_span.follows_from(&parent);
                     }
                 }
 
+                flush(
+                    &mut queries,
+                    chunk_size,
+                    synchronous_commit,
+                    &callbacks_clone,
+                    &postgres_clone,
+                )
+                .instrument(flush_span)
+                .await;
+
+                flush_timer.observe_duration();
+
+                if let Ok(mut oldest) = oldest_clone.lock() {
+                    *oldest = None;
+                }
+
                 let mut callbacks_map = callbacks_clone.write().await;
                 callbacks_map.clear();
+
+                if stopping {
+                    break;
+                }
             }
         });
 
-        CommandBus { queries, callbacks }
+        CommandBus {
+            queries,
+            callbacks,
+            spans,
+            oldest,
+            notifier,
+            stopping,
+            worker: Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Flushes whatever is still queued and stops the flush task. Rows pushed
+    /// afterwards are never written, so call it once nothing pushes anymore.
+    pub async fn shutdown(&self) {
+        self.stopping.store(true, Ordering::Release);
+        self.notifier.notify_one();
+
+        let worker = self.worker.lock().ok().and_then(|mut worker| worker.take());
+        if let Some(worker) = worker
+            && let Err(error) = worker.await
+        {
+            tracing::error!(error = %error, "command bus flush task failed");
+        }
+    }
+
+    // Age of the oldest row still waiting for a flush; zero when the queue is empty.
+    pub fn lag(&self) -> Duration {
+        self.oldest
+            .lock()
+            .ok()
+            .and_then(|oldest| *oldest)
+            .map(|pushed| pushed.elapsed())
+            .unwrap_or_default()
     }
 
     pub async fn push(
         &self,
         query: &str,
         params: Vec<CommandValue>,
-        callback: Option<Box<dyn Fn(&dyn Any) + Send + Sync>>,
+        callback: Option<CommandCallback>,
     ) {
         let mut queries = self.queries.write().await;
         queries.entry(query.to_string()).or_default().push(params);
+        METRICS.bus_queue_depth.inc();
+
+        if let Ok(mut oldest) = self.oldest.lock() {
+            oldest.get_or_insert_with(Instant::now);
+        }
+
+        let current = Span::current();
+        if !current.is_disabled()
+            && let Ok(mut spans) = self.spans.lock()
+            && spans.len() < MAX_SPAN_LINKS
+        {
+            spans.push(current);
+        }
 
-        if callback.is_some() {
+        if let Some(callback) = callback {
             let mut callbacks = self.callbacks.write().await;
-            callbacks.insert(query.to_string(), callback.unwrap());
+            callbacks.insert(query.to_string(), callback);
         }
     }
 }
 
+async fn flush(
+    queries: &mut HashMap<String, Vec<Vec<CommandValue>>>,
+    chunk_size: usize,
+    synchronous_commit: bool,
+    callbacks: &CallbackMap,
+    postgres: &Pool<Postgres>,
+) {
+    for (query, param_sets) in queries.drain() {
+        for chunk in param_sets.chunks(chunk_size.max(1)) {
+            let chunk = chunk.to_vec();
+
+            if query.to_lowercase().contains("unnest") {
+                let q = bind_unnest(sqlx::query(&query), &chunk);
+                match fetch_one(postgres, synchronous_commit, q).await {
+                    Ok(row) => {
+                        let read_callback = callbacks.read().await;
+                        if let Some(function) = read_callback.get(&query) {
+                            function(&row);
+                        }
+                    }
+                    Err(e) => {
+                        METRICS.bus_flush_failures.inc();
+                        let rows = chunk.len();
+                        tracing::error!(error = %e, rows, "command bus flush failed");
+                    }
+                }
+            } else {
+                for _ in &chunk {
+                    match fetch_one(postgres, synchronous_commit, sqlx::query(&query)).await {
+                        Ok(row) => {
+                            let read_callback = callbacks.read().await;
+                            if let Some(function) = read_callback.get(&query) {
+                                function(&row);
+                            }
+                        }
+                        Err(e) => {
+                            METRICS.bus_flush_failures.inc();
+                            tracing::error!(error = %e, "command bus statement failed");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    tracing::debug!("command bus flushed");
+}
+
+async fn fetch_one(
+    postgres: &Pool<Postgres>,
+    synchronous_commit: bool,
+    query: Query<'_, Postgres, PgArguments>,
+) -> Result<PgRow, sqlx::Error> {
+    let mut tx = postgres.begin().await?;
+    tx.execute(match synchronous_commit {
+        true => "SET LOCAL synchronous_commit = on",
+        false => "SET LOCAL synchronous_commit = off",
+    })
+    .await?;
+
+    let row = query.fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+
+    Ok(row)
+}
+
 fn bind_unnest<'q>(
     mut q: Query<'q, Postgres, PgArguments>,
     rows: &[Vec<CommandValue>],
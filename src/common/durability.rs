@@ -0,0 +1,129 @@
+use anyhow::{Context, Error};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Pool, Postgres, query_as, query_scalar};
+
+/// How much a crash may cost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurabilityProfile {
+    /// Unlogged event tables and asynchronous commits; a crash empties them.
+    Benchmark,
+    /// Logged tables, synchronous commits and a server that fsyncs.
+    Production,
+}
+
+/// What the startup check does when a production setup is unsafe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnUnsafe {
+    Fail,
+    Warn,
+}
+
+impl DurabilityProfile {
+    pub fn synchronous_commit(self) -> bool {
+        self == DurabilityProfile::Production
+    }
+
+    fn logged(self) -> bool {
+        self == DurabilityProfile::Production
+    }
+}
+
+// Server settings that must be on before a crash can be survived.
+const REQUIRED_SETTINGS: [&str; 2] = ["fsync", "full_page_writes"];
+
+/// Makes the event tables logged or unlogged to match the profile; returns
+/// the tables that changed. `users` and `event_types` are only ever made
+/// logged, since the partitioned `events` table may reference them.
+pub async fn apply(
+    pool: &Pool<Postgres>,
+    profile: DurabilityProfile,
+) -> Result<Vec<String>, Error> {
+    let mut tables: Vec<(String, bool)> = query_as(
+        r#"
+        SELECT c.relname::text, c.relpersistence = 'p'
+        FROM pg_class c
+        WHERE c.relkind = 'r'
+          AND (c.oid IN (SELECT inhrelid FROM pg_inherits WHERE inhparent = to_regclass('events'))
+               OR c.oid = to_regclass('events')
+               OR c.oid = to_regclass('events_archive')
+               OR c.oid = to_regclass('retention_rules'))
+        ORDER BY c.relname
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Referenced tables go first when switching to logged.
+    if profile.logged() {
+        let referenced: Vec<(String, bool)> = query_as(
+            r#"
+            SELECT relname::text, relpersistence = 'p' FROM pg_class
+            WHERE oid IN (to_regclass('users'), to_regclass('event_types'))
+            ORDER BY relname DESC
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+        tables.splice(0..0, referenced);
+    }
+
+    let persistence = if profile.logged() {
+        "LOGGED"
+    } else {
+        "UNLOGGED"
+    };
+    let mut changed = vec![];
+
+    for (table, logged) in tables {
+        if logged == profile.logged() {
+            continue;
+        }
+
+        pool.execute(format!("ALTER TABLE {} SET {}", table, persistence).as_str())
+            .await
+            .with_context(|| format!("Cannot make {} {}", table, persistence.to_lowercase()))?;
+        changed.push(table);
+    }
+
+    Ok(changed)
+}
+
+/// Reasons a crash would lose or corrupt data: server settings that are off
+/// and application tables that are unlogged.
+pub async fn unsafe_settings(pool: &Pool<Postgres>) -> Result<Vec<String>, Error> {
+    let mut problems = vec![];
+
+    for setting in REQUIRED_SETTINGS {
+        let value: String = query_scalar(&format!("SHOW {}", setting))
+            .fetch_one(pool)
+            .await?;
+        if value != "on" {
+            problems.push(format!("{} is {}", setting, value));
+        }
+    }
+
+    let unlogged: Vec<String> = query_scalar(
+        r#"
+        SELECT c.relname::text FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = 'public' AND c.relkind = 'r' AND c.relpersistence = 'u'
+        ORDER BY c.relname
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    if let Some(first) = unlogged.first() {
+        let tables = match unlogged.len() {
+            1 => first.clone(),
+            n => format!("{} and {} more", first, n - 1),
+        };
+        problems.push(format!(
+            "unlogged tables ({}); `migrate up` with the production profile makes them logged",
+            tables
+        ));
+    }
+
+    Ok(problems)
+}
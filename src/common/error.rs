@@ -0,0 +1,182 @@
+use std::fmt;
+
+use actix_web::{
+    HttpResponse, ResponseError,
+    error::{JsonPayloadError, PathError, QueryPayloadError},
+    http::{StatusCode, header::WWW_AUTHENTICATE},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::common::{auth::Scope, rate_limit::Decision, request_id::current_request_id};
+
+#[derive(Debug)]
+pub enum AppError {
+    InvalidJson,
+    InvalidQuery(String),
+    Validation {
+        field: &'static str,
+        message: &'static str,
+    },
+    UserNotFound,
+    EventTypeNotFound,
+    JobNotFound,
+    ExportNotReady,
+    ExportExpired,
+    Unauthorized,
+    Forbidden(Scope),
+    WrongTenant,
+    KeyNotFound,
+    RateLimited(Decision),
+    Internal(anyhow::Error),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    #[schema(value_type = String, example = "validation_failed")]
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub field: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::InvalidJson => "invalid_json",
+            AppError::InvalidQuery(_) => "invalid_query",
+            AppError::Validation { .. } => "validation_failed",
+            AppError::UserNotFound => "user_not_found",
+            AppError::EventTypeNotFound => "event_type_not_found",
+            AppError::JobNotFound => "job_not_found",
+            AppError::ExportNotReady => "export_not_ready",
+            AppError::ExportExpired => "export_expired",
+            AppError::Unauthorized => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::WrongTenant => "wrong_tenant",
+            AppError::KeyNotFound => "key_not_found",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            error: self.to_string(),
+            code: self.code(),
+            field: match self {
+                AppError::Validation { field, .. } => Some(field),
+                _ => None,
+            },
+            request_id: current_request_id(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::InvalidJson => write!(f, "Request body is not valid JSON"),
+            AppError::InvalidQuery(message) => write!(f, "{}", message),
+            AppError::Validation { message, .. } => write!(f, "{}", message),
+            AppError::UserNotFound => write!(f, "User not exist"),
+            AppError::EventTypeNotFound => write!(f, "Type not exist"),
+            AppError::JobNotFound => write!(f, "Job not exist"),
+            AppError::ExportNotReady => write!(f, "Export is not finished"),
+            AppError::ExportExpired => write!(f, "Export was deleted, request a new one"),
+            AppError::Unauthorized => write!(f, "Missing, unknown or revoked API key"),
+            AppError::Forbidden(scope) => write!(f, "API key lacks the `{}` scope", scope),
+            AppError::WrongTenant => write!(f, "API key belongs to another tenant"),
+            AppError::KeyNotFound => write!(f, "API key not exist"),
+            AppError::RateLimited(decision) => write!(
+                f,
+                "Rate limit exceeded, retry in {} s",
+                decision.retry_after
+            ),
+            // Never leak driver or database text to clients.
+            AppError::Internal(_) => write!(f, "Internal server error"),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::JobNotFound => StatusCode::NOT_FOUND,
+            AppError::ExportNotReady => StatusCode::CONFLICT,
+            AppError::ExportExpired => StatusCode::GONE,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) | AppError::WrongTenant => StatusCode::FORBIDDEN,
+            AppError::KeyNotFound => StatusCode::NOT_FOUND,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let AppError::Internal(error) = self {
+            tracing::error!(
+                error = format!("{:#}", error),
+                code = self.code(),
+                "internal error"
+            );
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let AppError::Unauthorized = self {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+
+        let mut response = response.json(self.body());
+        if let AppError::RateLimited(decision) = self {
+            decision.write_headers(response.headers_mut());
+        }
+        response
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        AppError::Internal(error)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+impl From<simd_json::Error> for AppError {
+    fn from(error: simd_json::Error) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Internal(error.into())
+    }
+}
+
+impl From<QueryPayloadError> for AppError {
+    fn from(error: QueryPayloadError) -> Self {
+        AppError::InvalidQuery(error.to_string())
+    }
+}
+
+impl From<PathError> for AppError {
+    fn from(error: PathError) -> Self {
+        AppError::InvalidQuery(error.to_string())
+    }
+}
+
+impl From<JsonPayloadError> for AppError {
+    fn from(_: JsonPayloadError) -> Self {
+        AppError::InvalidJson
+    }
+}
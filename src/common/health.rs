@@ -0,0 +1,38 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+pub struct Health {
+    started: Instant,
+    draining: AtomicBool,
+    bus_lag_threshold: Duration,
+}
+
+impl Health {
+    pub fn create(bus_lag_threshold: Duration) -> Health {
+        Health {
+            started: Instant::now(),
+            draining: AtomicBool::new(false),
+            bus_lag_threshold,
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    // Readiness fails from here on so the orchestrator stops routing new
+    // traffic while in-flight requests and the command bus finish.
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn bus_lag_threshold(&self) -> Duration {
+        self.bus_lag_threshold
+    }
+}
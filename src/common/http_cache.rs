@@ -0,0 +1,51 @@
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, VARY},
+};
+use sha2::{Digest, Sha256};
+
+pub fn etag(payload: &[u8]) -> String {
+    let digest = Sha256::digest(payload);
+
+    let mut tag = String::with_capacity(34);
+    tag.push('"');
+    for byte in &digest[..16] {
+        tag.push_str(&format!("{:02x}", byte));
+    }
+    tag.push('"');
+    tag
+}
+
+// If-None-Match uses the weak comparison, so `W/"x"` matches our strong `"x"`.
+pub fn is_fresh(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get_all(IF_NONE_MATCH)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+// The same URL answers differently per tenant, so shared caches must key on
+// whatever picks the tenant.
+const VARY_ON: &str = "Authorization, X-Api-Key, X-Tenant-Id";
+
+pub fn cached_response(req: &HttpRequest, payload: Vec<u8>, max_age: u64) -> HttpResponse {
+    let etag = etag(&payload);
+    let cache_control = format!("max-age={}", max_age);
+
+    if is_fresh(req, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .insert_header((CACHE_CONTROL, cache_control))
+            .insert_header((VARY, VARY_ON))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((ETAG, etag))
+        .insert_header((CACHE_CONTROL, cache_control))
+        .insert_header((VARY, VARY_ON))
+        .body(payload)
+}
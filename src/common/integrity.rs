@@ -0,0 +1,180 @@
+use anyhow::{Context, Error};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Pool, Postgres, query_as, query_scalar};
+
+use crate::common::config::IntegrityConfig;
+
+/// The tables `events` references, with the referencing column.
+const REFERENCES: [(&str, &str); 2] = [("users", "user_id"), ("event_types", "type_id")];
+
+/// What deleting a user or event type does to its events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnDelete {
+    /// Fail the delete while events still reference the row.
+    Reject,
+    /// Delete the events together with the row.
+    Cascade,
+    /// Keep the row with `deleted_at` set; it takes no new events.
+    Tombstone,
+}
+
+impl OnDelete {
+    fn as_str(self) -> &'static str {
+        match self {
+            OnDelete::Reject => "reject",
+            OnDelete::Cascade => "cascade",
+            OnDelete::Tombstone => "tombstone",
+        }
+    }
+}
+
+/// Events whose user or type row does not exist.
+pub struct OrphanReport {
+    pub events: i64,
+    pub orphan_users: i64,
+    pub orphan_types: i64,
+    /// A few orphaned event ids, oldest first.
+    pub samples: Vec<i64>,
+}
+
+impl OrphanReport {
+    pub fn is_clean(&self) -> bool {
+        self.orphan_users == 0 && self.orphan_types == 0
+    }
+}
+
+/// Installs the delete triggers for the configured policy and adds or drops
+/// the foreign keys. On a plain table new keys are added NOT VALID and
+/// validated afterwards, so writers are only blocked for the catalog change.
+pub async fn apply(pool: &Pool<Postgres>, config: &IntegrityConfig) -> Result<(), Error> {
+    for (table, column) in REFERENCES {
+        pool.execute(
+            format!(
+                "DROP TRIGGER IF EXISTS {table}_on_delete ON {table};
+                 CREATE TRIGGER {table}_on_delete BEFORE DELETE ON {table}
+                     FOR EACH ROW EXECUTE FUNCTION integrity_on_delete('{column}', '{}')",
+                config.on_delete.as_str()
+            )
+            .as_str(),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Cannot install the delete trigger on {}, run `migrate up` first",
+                table
+            )
+        })?;
+    }
+
+    if !config.foreign_keys {
+        return drop_foreign_keys(pool).await;
+    }
+
+    // Postgres cannot add NOT VALID foreign keys to a partitioned table, so
+    // there the key is validated while it is added.
+    let partitioned: bool =
+        query_scalar("SELECT relkind = 'p' FROM pg_class WHERE oid = 'events'::regclass")
+            .fetch_one(pool)
+            .await?;
+    let not_valid = if partitioned { "" } else { "NOT VALID" };
+
+    for (table, column) in REFERENCES {
+        let constraint = format!("events_{}_fkey", column);
+        let exists: bool = query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = $1 AND convalidated)",
+        )
+        .bind(&constraint)
+        .fetch_one(pool)
+        .await?;
+
+        if exists {
+            continue;
+        }
+
+        let orphans = || {
+            format!(
+                "events has rows without a matching {} row, see `integrity scan`",
+                table
+            )
+        };
+
+        pool.execute(
+            format!(
+                "ALTER TABLE events DROP CONSTRAINT IF EXISTS {constraint};
+                 ALTER TABLE events ADD CONSTRAINT {constraint}
+                     FOREIGN KEY ({column}) REFERENCES {table} (id) {not_valid}"
+            )
+            .as_str(),
+        )
+        .await
+        .with_context(orphans)?;
+
+        if !partitioned {
+            pool.execute(format!("ALTER TABLE events VALIDATE CONSTRAINT {constraint}").as_str())
+                .await
+                .with_context(orphans)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn drop_foreign_keys(pool: &Pool<Postgres>) -> Result<(), Error> {
+    for (_, column) in REFERENCES {
+        pool.execute(
+            format!("ALTER TABLE events DROP CONSTRAINT IF EXISTS events_{column}_fkey").as_str(),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn scan(pool: &Pool<Postgres>, samples: i64) -> Result<OrphanReport, Error> {
+    let (events, orphan_users, orphan_types): (i64, i64, i64) = query_as(
+        r#"
+        SELECT
+            count(*),
+            count(*) FILTER (WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = e.user_id)),
+            count(*) FILTER (WHERE NOT EXISTS (SELECT 1 FROM event_types t WHERE t.id = e.type_id))
+        FROM events e
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let samples: Vec<i64> = query_scalar(
+        r#"
+        SELECT e.id FROM events e
+        WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = e.user_id)
+           OR NOT EXISTS (SELECT 1 FROM event_types t WHERE t.id = e.type_id)
+        ORDER BY e.timestamp
+        LIMIT $1
+        "#,
+    )
+    .bind(samples)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(OrphanReport {
+        events,
+        orphan_users,
+        orphan_types,
+        samples,
+    })
+}
+
+pub async fn delete_orphans(pool: &Pool<Postgres>) -> Result<u64, Error> {
+    let result = pool
+        .execute(
+            r#"
+            DELETE FROM events e
+            WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = e.user_id)
+               OR NOT EXISTS (SELECT 1 FROM event_types t WHERE t.id = e.type_id)
+            "#,
+        )
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,104 @@
+use std::{str::FromStr, time::Instant};
+
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tracing::{Instrument, field::Empty};
+use tracing_subscriber::{EnvFilter, fmt};
+
+use crate::common::request_id::RequestId;
+
+static FORMAT: OnceCell<LogFormat> = OnceCell::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "pretty" => Ok(LogFormat::Pretty),
+            other => Err(anyhow::Error::msg(format!(
+                "Unknown log format `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+pub fn format() -> LogFormat {
+    FORMAT.get().copied().unwrap_or(LogFormat::Pretty)
+}
+
+// `filter` follows the RUST_LOG syntax, e.g. `info,sqlx=warn`.
+pub fn init(format: LogFormat, filter: &str) {
+    if FORMAT.set(format).is_err() {
+        return;
+    }
+
+    let filter = EnvFilter::try_new(filter).unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let _ = match format {
+        LogFormat::Json => fmt()
+            .json()
+            .with_env_filter(filter)
+            .with_current_span(true)
+            .with_span_list(false)
+            .flatten_event(true)
+            .try_init(),
+        LogFormat::Pretty => fmt().compact().with_env_filter(filter).try_init(),
+    };
+}
+
+pub async fn trace_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        route = Empty,
+        user_id = Empty,
+        key_id = Empty,
+        tenant_id = Empty,
+    );
+
+    let started = Instant::now();
+    let response = next.call(req).instrument(span.clone()).await?;
+
+    let route = response
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    span.record("route", route.as_str());
+
+    let status = response.status().as_u16();
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+    span.in_scope(|| {
+        if status >= 500 {
+            tracing::error!(status, elapsed_ms, "request failed");
+        } else {
+            tracing::info!(status, elapsed_ms, "request completed");
+        }
+    });
+
+    Ok(response)
+}
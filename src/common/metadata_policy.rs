@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use anyhow::{Error, bail};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+use crate::common::{config::MetadataConfig, metrics::METRICS, retention::glob_matches};
+
+/// What ingest does with one metadata field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldAction {
+    Allow,
+    Drop,
+    /// Replace the value with its hex HMAC-SHA256 under `metadata.hmac_key`.
+    Hmac,
+    /// Keep the /24 of an IPv4 or the /48 of an IPv6 address, drop anything
+    /// else.
+    Truncate,
+}
+
+/// Field actions of one event type pattern. Fields it does not list are
+/// dropped unless `keep_unlisted` is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub keep_unlisted: bool,
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, FieldAction>,
+}
+
+impl<const N: usize> From<[(String, FieldAction); N]> for Policy {
+    fn from(fields: [(String, FieldAction); N]) -> Policy {
+        Policy {
+            keep_unlisted: false,
+            fields: BTreeMap::from(fields),
+        }
+    }
+}
+
+// Governs types no pattern matches: the stats need `page`, nothing else is
+// kept.
+static PAGE_ONLY: Lazy<Policy> =
+    Lazy::new(|| Policy::from([("page".to_owned(), FieldAction::Allow)]));
+
+#[derive(Clone)]
+pub struct MetadataPolicies {
+    /// Most specific pattern first.
+    policies: Vec<(String, Policy)>,
+    key: Vec<u8>,
+}
+
+impl MetadataPolicies {
+    pub fn create(config: &MetadataConfig) -> Result<MetadataPolicies, Error> {
+        let uses_hmac = config.policies.values().any(|policy| {
+            policy
+                .fields
+                .values()
+                .any(|action| *action == FieldAction::Hmac)
+        });
+        if uses_hmac && config.hmac_key.is_empty() {
+            bail!("metadata.hmac_key is required by `hmac` fields");
+        }
+
+        // The stats group by `page`, and a stored event without it breaks them.
+        for (pattern, policy) in &config.policies {
+            match policy.fields.get("page") {
+                Some(FieldAction::Drop | FieldAction::Truncate) => bail!(
+                    "metadata.policies.\"{}\" must not drop or truncate `page`",
+                    pattern
+                ),
+                None if !policy.keep_unlisted => bail!(
+                    "metadata.policies.\"{}\" drops unlisted fields, so it must list `page`",
+                    pattern
+                ),
+                _ => {}
+            }
+        }
+
+        let mut policies: Vec<(String, Policy)> = config
+            .policies
+            .iter()
+            .map(|(pattern, policy)| (pattern.clone(), policy.clone()))
+            .collect();
+        // Literal characters decide, like retention rules.
+        policies.sort_by_key(|(pattern, _)| {
+            std::cmp::Reverse(pattern.chars().filter(|c| *c != '*').count())
+        });
+
+        Ok(MetadataPolicies {
+            policies,
+            key: config.hmac_key.as_bytes().to_vec(),
+        })
+    }
+
+    /// The pattern and policy that govern `event_type`, if any.
+    pub fn policy_for(&self, event_type: &str) -> Option<(&str, &Policy)> {
+        self.policies
+            .iter()
+            .find(|(pattern, _)| glob_matches(pattern, event_type))
+            .map(|(pattern, policy)| (pattern.as_str(), policy))
+    }
+
+    /// Applies the event type's policy to a metadata object and counts every
+    /// field it changed or removed. Without a policy only `page` is kept.
+    pub fn apply(&self, event_type: &str, metadata: Map<String, Value>) -> Map<String, Value> {
+        let (pattern, policy) = self.policy_for(event_type).unwrap_or(("", &PAGE_ONLY));
+
+        let mut kept = Map::with_capacity(metadata.len());
+
+        for (field, value) in metadata {
+            let value = match policy.fields.get(&field) {
+                Some(FieldAction::Allow) => Some(value),
+                Some(FieldAction::Drop) => {
+                    count(pattern, "dropped", 1);
+                    None
+                }
+                Some(FieldAction::Hmac) => {
+                    count(pattern, "hashed", 1);
+                    Some(Value::String(self.hmac(&value)))
+                }
+                Some(FieldAction::Truncate) => match truncate_ip(&value) {
+                    Some(truncated) => {
+                        count(pattern, "truncated", 1);
+                        Some(Value::String(truncated))
+                    }
+                    None => {
+                        count(pattern, "dropped", 1);
+                        None
+                    }
+                },
+                None if policy.keep_unlisted => Some(value),
+                None => {
+                    count(pattern, "unlisted", 1);
+                    None
+                }
+            };
+
+            if let Some(value) = value {
+                kept.insert(field, value);
+            }
+        }
+
+        kept
+    }
+
+    /// `apply` for metadata kept as JSON text, as the seeder does. Anything
+    /// but an object comes out as `{}`.
+    pub fn apply_str(&self, event_type: &str, metadata: &str) -> String {
+        let object = match serde_json::from_str(metadata) {
+            Ok(Value::Object(object)) => object,
+            _ => Map::new(),
+        };
+
+        Value::Object(self.apply(event_type, object)).to_string()
+    }
+
+    fn hmac(&self, value: &Value) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        match value {
+            Value::String(text) => mac.update(text.as_bytes()),
+            other => mac.update(other.to_string().as_bytes()),
+        }
+
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+fn truncate_ip(value: &Value) -> Option<String> {
+    let address: IpAddr = value.as_str()?.trim().parse().ok()?;
+
+    Some(match address {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            Ipv4Addr::new(a, b, c, 0).to_string()
+        }
+        IpAddr::V6(v6) => {
+            let [a, b, c, ..] = v6.segments();
+            Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0).to_string()
+        }
+    })
+}
+
+fn count(policy: &str, action: &str, fields: usize) {
+    METRICS
+        .metadata_violations
+        .with_label_values(&[policy, action])
+        .inc_by(fields as u64);
+}
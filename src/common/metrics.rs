@@ -0,0 +1,267 @@
+use std::time::Instant;
+
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder, exponential_buckets,
+};
+use sqlx::{Pool, Postgres};
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::create);
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_duration: HistogramVec,
+    pub bus_queue_depth: IntGauge,
+    pub bus_flush_duration: Histogram,
+    pub bus_rows_per_flush: Histogram,
+    pub bus_flush_failures: IntCounter,
+    pub cache_lookups: IntCounterVec,
+    pub cache_invalidation_failures: IntCounterVec,
+    pub pool_connections: IntGaugeVec,
+    pub retention_events: IntCounterVec,
+    pub retention_run_duration: Histogram,
+    pub retention_failures: IntCounter,
+    pub metadata_violations: IntCounterVec,
+    pub rate_limited_requests: IntCounterVec,
+}
+
+impl Metrics {
+    // Every metric lives in a registry owned by this instance, so tests can
+    // build their own and render it without touching the process-wide one.
+    pub fn create() -> Metrics {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP responses by route and status"),
+            &["method", "route", "status"],
+        )
+        .expect("valid http_requests_total");
+
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route",
+            ),
+            &["method", "route"],
+        )
+        .expect("valid http_request_duration_seconds");
+
+        let bus_queue_depth = IntGauge::new(
+            "command_bus_queue_depth",
+            "Rows waiting in the command bus for the next flush",
+        )
+        .expect("valid command_bus_queue_depth");
+
+        let bus_flush_duration = Histogram::with_opts(HistogramOpts::new(
+            "command_bus_flush_duration_seconds",
+            "Time spent writing one command bus flush to Postgres",
+        ))
+        .expect("valid command_bus_flush_duration_seconds");
+
+        let bus_rows_per_flush = Histogram::with_opts(
+            HistogramOpts::new(
+                "command_bus_rows_per_flush",
+                "Rows written by one command bus flush",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 10).expect("valid buckets")),
+        )
+        .expect("valid command_bus_rows_per_flush");
+
+        let bus_flush_failures = IntCounter::new(
+            "command_bus_flush_failures_total",
+            "Command bus statements that failed in Postgres",
+        )
+        .expect("valid command_bus_flush_failures_total");
+
+        let cache_lookups = IntCounterVec::new(
+            Opts::new(
+                "cache_lookups_total",
+                "Cache lookups by level, key family and result",
+            ),
+            &["level", "family", "result"],
+        )
+        .expect("valid cache_lookups_total");
+
+        let cache_invalidation_failures = IntCounterVec::new(
+            Opts::new(
+                "cache_invalidation_failures_total",
+                "Failed L2 deletes and untags, by operation, key family and whether a retry follows",
+            ),
+            &["op", "family", "outcome"],
+        )
+        .expect("valid cache_invalidation_failures_total");
+
+        let pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "postgres_pool_connections",
+                "sqlx pool connections by state",
+            ),
+            &["state"],
+        )
+        .expect("valid postgres_pool_connections");
+
+        let retention_events = IntCounterVec::new(
+            Opts::new(
+                "retention_events_total",
+                "Events removed by retention rules, by rule pattern and action",
+            ),
+            &["rule", "action"],
+        )
+        .expect("valid retention_events_total");
+
+        let retention_run_duration = Histogram::with_opts(HistogramOpts::new(
+            "retention_run_duration_seconds",
+            "Time spent applying every retention rule once",
+        ))
+        .expect("valid retention_run_duration_seconds");
+
+        let retention_failures = IntCounter::new(
+            "retention_run_failures_total",
+            "Retention runs that stopped with an error",
+        )
+        .expect("valid retention_run_failures_total");
+
+        let metadata_violations = IntCounterVec::new(
+            Opts::new(
+                "metadata_policy_violations_total",
+                "Metadata fields changed or removed on ingest, by policy pattern and action",
+            ),
+            &["policy", "action"],
+        )
+        .expect("valid metadata_policy_violations_total");
+
+        let rate_limited_requests = IntCounterVec::new(
+            Opts::new(
+                "rate_limited_requests_total",
+                "Requests refused with 429, by route and the bucket that ran out",
+            ),
+            &["route", "per"],
+        )
+        .expect("valid rate_limited_requests_total");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_duration.clone()),
+            Box::new(bus_queue_depth.clone()),
+            Box::new(bus_flush_duration.clone()),
+            Box::new(bus_rows_per_flush.clone()),
+            Box::new(bus_flush_failures.clone()),
+            Box::new(cache_lookups.clone()),
+            Box::new(cache_invalidation_failures.clone()),
+            Box::new(pool_connections.clone()),
+            Box::new(retention_events.clone()),
+            Box::new(retention_run_duration.clone()),
+            Box::new(retention_failures.clone()),
+            Box::new(metadata_violations.clone()),
+            Box::new(rate_limited_requests.clone()),
+        ] {
+            registry.register(collector).expect("unique metric names");
+        }
+
+        Metrics {
+            registry,
+            http_requests,
+            http_duration,
+            bus_queue_depth,
+            bus_flush_duration,
+            bus_rows_per_flush,
+            bus_flush_failures,
+            cache_lookups,
+            cache_invalidation_failures,
+            pool_connections,
+            retention_events,
+            retention_run_duration,
+            retention_failures,
+            metadata_violations,
+            rate_limited_requests,
+        }
+    }
+
+    pub fn observe_cache(&self, level: &str, key: &str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.cache_lookups
+            .with_label_values(&[level, key_family(key), result])
+            .inc();
+    }
+
+    pub fn observe_pool(&self, pool: &Pool<Postgres>) {
+        let size = pool.size() as i64;
+        let idle = pool.num_idle() as i64;
+
+        self.pool_connections
+            .with_label_values(&["active"])
+            .set(size - idle);
+        self.pool_connections.with_label_values(&["idle"]).set(idle);
+        self.pool_connections
+            .with_label_values(&["max"])
+            .set(pool.options().get_max_connections() as i64);
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = vec![];
+        let encoder = TextEncoder::new();
+
+        if encoder
+            .encode(&self.registry.gather(), &mut buffer)
+            .is_err()
+        {
+            return String::new();
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+// Collapses concrete cache keys (`page_0_3_100`, `user_events_0_42`) into the
+// family they belong to, keeping label cardinality bounded.
+pub fn key_family(key: &str) -> &'static str {
+    const FAMILIES: [&str; 7] = [
+        "events_stat",
+        "user_events",
+        "page",
+        "total_events",
+        "event_types",
+        "users_id",
+        "api_key",
+    ];
+
+    FAMILIES
+        .into_iter()
+        .find(|family| key.starts_with(family))
+        .unwrap_or("other")
+}
+
+pub async fn track_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+
+    let response = next.call(req).await?;
+
+    let route = response
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let status = response.status().as_u16().to_string();
+
+    METRICS
+        .http_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    METRICS
+        .http_requests
+        .with_label_values(&[&method, &route, &status])
+        .inc();
+
+    Ok(response)
+}
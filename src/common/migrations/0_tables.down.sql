@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS events;
+
+DROP TABLE IF EXISTS event_types;
+
+DROP TABLE IF EXISTS users;
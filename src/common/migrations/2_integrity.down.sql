@@ -0,0 +1,9 @@
+ALTER TABLE events DROP CONSTRAINT IF EXISTS events_user_id_fkey;
+ALTER TABLE events DROP CONSTRAINT IF EXISTS events_type_id_fkey;
+
+DROP TRIGGER IF EXISTS users_on_delete ON users;
+DROP TRIGGER IF EXISTS event_types_on_delete ON event_types;
+DROP FUNCTION IF EXISTS integrity_on_delete();
+
+ALTER TABLE users DROP COLUMN IF EXISTS deleted_at;
+ALTER TABLE event_types DROP COLUMN IF EXISTS deleted_at;
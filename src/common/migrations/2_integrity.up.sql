@@ -0,0 +1,33 @@
+-- Tombstoned rows stay referenced by their events but take no new ones.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE event_types ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+-- BEFORE DELETE trigger for users and event_types. Arguments: the events
+-- column that references the table, then reject | cascade | tombstone.
+-- `w_collider integrity apply` installs it with the configured policy.
+CREATE OR REPLACE FUNCTION integrity_on_delete() RETURNS trigger AS $$
+DECLARE
+    referenced BOOLEAN;
+BEGIN
+    IF TG_ARGV[1] = 'tombstone' THEN
+        EXECUTE format('UPDATE %I SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL', TG_TABLE_NAME)
+            USING OLD.id;
+        RETURN NULL;
+    END IF;
+
+    IF TG_ARGV[1] = 'cascade' THEN
+        EXECUTE format('DELETE FROM events WHERE %I = $1', TG_ARGV[0]) USING OLD.id;
+        RETURN OLD;
+    END IF;
+
+    EXECUTE format('SELECT EXISTS (SELECT 1 FROM events WHERE %I = $1)', TG_ARGV[0])
+        INTO referenced
+        USING OLD.id;
+    IF referenced THEN
+        RAISE EXCEPTION '% % is still referenced by events', TG_TABLE_NAME, OLD.id
+            USING ERRCODE = 'foreign_key_violation';
+    END IF;
+
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
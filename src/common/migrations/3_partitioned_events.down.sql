@@ -0,0 +1,31 @@
+-- Copies every partition back into one heap table.
+CREATE UNLOGGED TABLE events_heap (LIKE events INCLUDING DEFAULTS);
+INSERT INTO events_heap SELECT * FROM events;
+
+ALTER SEQUENCE events_id_seq OWNED BY NONE;
+DROP TABLE events CASCADE;
+
+ALTER TABLE events_heap RENAME TO events;
+ALTER TABLE users SET UNLOGGED;
+ALTER TABLE event_types SET UNLOGGED;
+ALTER TABLE events ADD CONSTRAINT events_pkey PRIMARY KEY (id);
+ALTER SEQUENCE events_id_seq OWNED BY events.id;
+
+CREATE INDEX IF NOT EXISTS idx_events_count
+    ON events USING btree (id);
+
+CREATE INDEX IF NOT EXISTS idx_events_user_timestamp
+    ON events USING btree (user_id, timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_timestamp_desc
+    ON events USING btree (timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_type_timestamp
+    ON events USING btree (type_id, timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_stats
+    ON events USING btree (user_id, (metadata->>'page'), type_id);
+
+CREATE INDEX IF NOT EXISTS idx_events_covering
+    ON events USING btree (user_id, type_id, timestamp DESC)
+    INCLUDE (id, metadata);
@@ -0,0 +1,83 @@
+-- Range partitioning by timestamp. The old heap table becomes the partition
+-- `events_legacy` for everything before the next month boundary, so no rows
+-- are copied; `w_collider partitions` manages the partitions after it.
+-- A partitioned table is always permanent and may only reference permanent
+-- tables, so the small lookup tables become logged for the foreign keys.
+ALTER TABLE users SET LOGGED;
+ALTER TABLE event_types SET LOGGED;
+
+ALTER TABLE events DROP CONSTRAINT IF EXISTS events_user_id_fkey;
+ALTER TABLE events DROP CONSTRAINT IF EXISTS events_type_id_fkey;
+
+ALTER TABLE events RENAME TO events_legacy;
+ALTER INDEX events_pkey RENAME TO events_legacy_pkey;
+ALTER INDEX IF EXISTS idx_events_count RENAME TO idx_events_legacy_count;
+ALTER INDEX IF EXISTS idx_events_user_timestamp RENAME TO idx_events_legacy_user_timestamp;
+ALTER INDEX IF EXISTS idx_events_timestamp_desc RENAME TO idx_events_legacy_timestamp_desc;
+ALTER INDEX IF EXISTS idx_events_type_timestamp RENAME TO idx_events_legacy_type_timestamp;
+ALTER INDEX IF EXISTS idx_events_stats RENAME TO idx_events_legacy_stats;
+ALTER INDEX IF EXISTS idx_events_covering RENAME TO idx_events_legacy_covering;
+
+CREATE TABLE events (
+    id         BIGINT       NOT NULL DEFAULT nextval('events_id_seq'),
+    user_id    BIGINT       NOT NULL,
+    type_id    BIGINT       NOT NULL,
+    timestamp  TIMESTAMPTZ  NOT NULL,
+    metadata   JSONB,
+    PRIMARY KEY (id, timestamp)
+) PARTITION BY RANGE (timestamp);
+
+ALTER SEQUENCE events_id_seq OWNED BY events.id;
+
+-- Rows outside every partition land here until a partition takes them over.
+CREATE UNLOGGED TABLE events_default PARTITION OF events DEFAULT;
+
+DO $$
+DECLARE
+    cutover TIMESTAMPTZ;
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM events_legacy) THEN
+        DROP TABLE events_legacy;
+        RETURN;
+    END IF;
+
+    SELECT date_trunc('month', GREATEST(max(timestamp), now()), 'UTC') + INTERVAL '1 month'
+        INTO cutover
+        FROM events_legacy;
+
+    -- The partitioned primary key (id, timestamp) replaces the one on id.
+    ALTER TABLE events_legacy DROP CONSTRAINT events_legacy_pkey;
+
+    -- The CHECK lets ATTACH skip its own validation scan.
+    EXECUTE format(
+        'ALTER TABLE events_legacy ADD CONSTRAINT events_legacy_range CHECK (timestamp < %L)',
+        cutover
+    );
+    EXECUTE format(
+        'ALTER TABLE events ATTACH PARTITION events_legacy FOR VALUES FROM (MINVALUE) TO (%L)',
+        cutover
+    );
+    ALTER TABLE events_legacy DROP CONSTRAINT events_legacy_range;
+END;
+$$;
+
+-- Same indexes as 1_indexes, now partitioned; matching legacy indexes are
+-- attached instead of rebuilt.
+CREATE INDEX IF NOT EXISTS idx_events_count
+    ON events USING btree (id);
+
+CREATE INDEX IF NOT EXISTS idx_events_user_timestamp
+    ON events USING btree (user_id, timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_timestamp_desc
+    ON events USING btree (timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_type_timestamp
+    ON events USING btree (type_id, timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_stats
+    ON events USING btree (user_id, (metadata->>'page'), type_id);
+
+CREATE INDEX IF NOT EXISTS idx_events_covering
+    ON events USING btree (user_id, type_id, timestamp DESC)
+    INCLUDE (id, metadata);
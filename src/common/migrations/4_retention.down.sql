@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS events_archive;
+DROP TABLE IF EXISTS retention_rules;
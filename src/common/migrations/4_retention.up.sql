@@ -0,0 +1,21 @@
+-- One row per event type pattern: an exact name such as `user.login` or a
+-- glob such as `order.*`. The most specific matching pattern wins.
+CREATE UNLOGGED TABLE IF NOT EXISTS retention_rules (
+    pattern    TEXT        PRIMARY KEY,
+    ttl_days   INTEGER     NOT NULL CHECK (ttl_days > 0),
+    archive    BOOLEAN     NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Expired events of rules with `archive` set are moved here before delete.
+CREATE UNLOGGED TABLE IF NOT EXISTS events_archive (
+    id          BIGINT      NOT NULL,
+    user_id     BIGINT      NOT NULL,
+    type_id     BIGINT      NOT NULL,
+    timestamp   TIMESTAMPTZ NOT NULL,
+    metadata    JSONB,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_events_archive_timestamp
+    ON events_archive USING btree (timestamp);
@@ -0,0 +1,22 @@
+-- Erasure and export requests. Rows are the audit trail and outlive the data
+-- they describe, so nothing here references users.
+CREATE TABLE IF NOT EXISTS gdpr_requests (
+    id           BIGINT      PRIMARY KEY,
+    kind         TEXT        NOT NULL CHECK (kind IN ('erasure', 'export')),
+    user_id      BIGINT      NOT NULL,
+    status       TEXT        NOT NULL DEFAULT 'pending'
+                             CHECK (status IN ('pending', 'running', 'done', 'failed')),
+    request_id   TEXT,
+    client_addr  TEXT,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    started_at   TIMESTAMPTZ,
+    finished_at  TIMESTAMPTZ,
+    events       BIGINT,
+    error        TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_gdpr_requests_open
+    ON gdpr_requests USING btree (requested_at) WHERE status IN ('pending', 'running');
+
+CREATE INDEX IF NOT EXISTS idx_gdpr_requests_user
+    ON gdpr_requests USING btree (user_id);
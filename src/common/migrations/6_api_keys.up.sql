@@ -0,0 +1,15 @@
+-- API keys. Only the SHA-256 of a key is stored; `previous_hash` keeps the
+-- key replaced by a rotation working until `previous_expires_at`.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id                  BIGINT      PRIMARY KEY,
+    name                TEXT        NOT NULL,
+    prefix              TEXT        NOT NULL,
+    hash                TEXT        NOT NULL UNIQUE,
+    scopes              TEXT[]      NOT NULL
+                                    CHECK (scopes <@ ARRAY['events:write', 'events:read', 'stats:read', 'admin']),
+    previous_hash       TEXT        UNIQUE,
+    previous_expires_at TIMESTAMPTZ,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    rotated_at          TIMESTAMPTZ,
+    revoked_at          TIMESTAMPTZ
+);
@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS idx_events_tenant_user_timestamp;
+DROP INDEX IF EXISTS idx_events_tenant_timestamp;
+DROP INDEX IF EXISTS idx_users_tenant;
+
+ALTER TABLE event_types DROP CONSTRAINT IF EXISTS event_types_tenant_name_key;
+ALTER TABLE event_types ADD CONSTRAINT event_types_name_key UNIQUE (name);
+
+ALTER TABLE api_keys DROP COLUMN IF EXISTS tenant_id;
+ALTER TABLE gdpr_requests DROP COLUMN IF EXISTS tenant_id;
+ALTER TABLE events_archive DROP COLUMN IF EXISTS tenant_id;
+ALTER TABLE events DROP COLUMN IF EXISTS tenant_id;
+ALTER TABLE event_types DROP COLUMN IF EXISTS tenant_id;
+ALTER TABLE users DROP COLUMN IF EXISTS tenant_id;
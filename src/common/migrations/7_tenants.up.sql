@@ -0,0 +1,24 @@
+-- Every user, event type and event belongs to one tenant. Rows written
+-- before tenants existed, and requests that name none, belong to tenant 0.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tenant_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE event_types ADD COLUMN IF NOT EXISTS tenant_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE events ADD COLUMN IF NOT EXISTS tenant_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE events_archive ADD COLUMN IF NOT EXISTS tenant_id BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE gdpr_requests ADD COLUMN IF NOT EXISTS tenant_id BIGINT NOT NULL DEFAULT 0;
+
+-- A key bound to a tenant only ever sees that tenant; NULL lets the
+-- `X-Tenant-Id` header choose.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS tenant_id BIGINT;
+
+-- Type names are unique per tenant.
+ALTER TABLE event_types DROP CONSTRAINT IF EXISTS event_types_name_key;
+ALTER TABLE event_types ADD CONSTRAINT event_types_tenant_name_key UNIQUE (tenant_id, name);
+
+CREATE INDEX IF NOT EXISTS idx_users_tenant
+    ON users USING btree (tenant_id);
+
+CREATE INDEX IF NOT EXISTS idx_events_tenant_timestamp
+    ON events USING btree (tenant_id, timestamp DESC);
+
+CREATE INDEX IF NOT EXISTS idx_events_tenant_user_timestamp
+    ON events USING btree (tenant_id, user_id, timestamp DESC);
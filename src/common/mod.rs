@@ -1,6 +1,25 @@
+pub mod archive;
+pub mod auth;
 pub mod cache;
 pub mod command_bus;
-pub mod env;
+pub mod config;
+pub mod durability;
+pub mod error;
+pub mod health;
+pub mod http_cache;
+pub mod integrity;
+pub mod logging;
+pub mod metadata_policy;
+pub mod metrics;
+pub mod openapi;
 pub mod output;
+pub mod partitions;
+pub mod rate_limit;
+pub mod request_id;
+pub mod retention;
+pub mod schema;
+pub mod seed_loader;
+pub mod seed_profile;
 pub mod seeder;
 pub mod snowflake;
+pub mod tenant;
@@ -0,0 +1,142 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        ContentBuilder, ObjectBuilder, Ref, RefOr, ResponseBuilder, Type,
+        path::{ParameterBuilder, ParameterIn},
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+};
+
+use crate::{
+    common::{auth::API_KEY_HEADER, error::ErrorBody, tenant::TENANT_HEADER},
+    contexts::{
+        auth::{
+            features::create_api_key::{CreateKeyRequest, IssuedKey},
+            infrastructure::api_keys_repo::ApiKeyRow,
+        },
+        events::{
+            features::create_event::CreateEventRequest,
+            infrastructure::{
+                cached_projection::{EventWithType, PaginatedEvents, Stat},
+                repo::Event,
+            },
+        },
+        users::infrastructure::gdpr_repo::GdprJob,
+    },
+};
+
+/// The OpenAPI document of every route, served at `/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "w_collider", description = "Event ingestion and analytics API"),
+    paths(
+        crate::contexts::events::features::create_event::create_event,
+        crate::contexts::events::features::read_paginated_events::read_paginated_events,
+        crate::contexts::events::features::read_last_user_events::read_last_user_events,
+        crate::contexts::events::features::read_events_stat::read_events_stat,
+        crate::contexts::users::features::erase_user_data::erase_user_data,
+        crate::contexts::users::features::export_user_data::export_user_data,
+        crate::contexts::users::features::read_gdpr_job::read_gdpr_job,
+        crate::contexts::users::features::read_gdpr_job::download_export,
+        crate::contexts::auth::features::create_api_key::create_api_key,
+        crate::contexts::auth::features::read_api_keys::read_api_keys,
+        crate::contexts::auth::features::rotate_api_key::rotate_api_key,
+        crate::contexts::auth::features::revoke_api_key::revoke_api_key,
+        crate::contexts::system::features::read_liveness::read_liveness,
+        crate::contexts::system::features::read_readiness::read_readiness,
+        crate::contexts::system::features::read_metrics::read_metrics,
+        crate::contexts::system::features::read_openapi::read_openapi,
+    ),
+    components(schemas(
+        CreateEventRequest,
+        Event,
+        EventWithType,
+        PaginatedEvents,
+        Stat,
+        GdprJob,
+        CreateKeyRequest,
+        IssuedKey,
+        ApiKeyRow,
+        ErrorBody,
+    )),
+    modifiers(&Authenticated),
+    security(("bearer" = []), ("api_key" = [])),
+    tags(
+        (name = "events", description = "Ingest and read events"),
+        (name = "users", description = "GDPR erasure and export"),
+        (name = "auth", description = "API keys, `admin` scope only"),
+        (name = "system", description = "Probes, metrics and this document; no key needed"),
+    )
+)]
+pub struct ApiDoc;
+
+// Adds the key schemes, and to every operation behind `authenticate` the
+// tenant header and the answers it may give before the handler runs.
+struct Authenticated;
+
+impl Modify for Authenticated {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+
+        let error = |description: &str| {
+            RefOr::T(
+                ResponseBuilder::new()
+                    .description(description)
+                    .content(
+                        "application/json",
+                        ContentBuilder::new()
+                            .schema(Some(Ref::from_schema_name("ErrorBody")))
+                            .build(),
+                    )
+                    .build(),
+            )
+        };
+
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.post,
+                &mut item.put,
+                &mut item.patch,
+                &mut item.delete,
+            ];
+            for operation in operations.into_iter().flatten() {
+                // Open routes declare `security(())`; the rest inherit the keys.
+                if operation.security.is_some() {
+                    continue;
+                }
+
+                operation.parameters.get_or_insert_with(Vec::new).push(
+                    ParameterBuilder::new()
+                        .name(TENANT_HEADER)
+                        .parameter_in(ParameterIn::Header)
+                        .description(Some(
+                            "Tenant to act for; a key bound to a tenant only accepts its own",
+                        ))
+                        .schema(Some(
+                            ObjectBuilder::new()
+                                .schema_type(Type::Integer)
+                                .minimum(Some(0)),
+                        ))
+                        .build(),
+                );
+
+                let responses = &mut operation.responses.responses;
+                responses
+                    .entry("401".to_owned())
+                    .or_insert_with(|| error("Missing, unknown or revoked API key"));
+                responses.entry("403".to_owned()).or_insert_with(|| {
+                    error("The key lacks the scope or belongs to another tenant")
+                });
+            }
+        }
+    }
+}
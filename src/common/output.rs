@@ -1,37 +1,49 @@
 use once_cell::sync::Lazy;
 use std::{
-    collections::HashMap,
     io::{self, Write},
     sync::Mutex,
 };
 
-static GROUPS: Lazy<Mutex<HashMap<String, Vec<String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-static CURRENT_GROUP: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+use crate::common::logging::{LogFormat, format};
+
+// Console formatter for the startup tree. With JSON logging the same lines go
+// through tracing instead, so container logs stay one object per line.
+#[derive(Default)]
+struct Tree {
+    group: Option<String>,
+    last: Option<String>,
+}
+
+static TREE: Lazy<Mutex<Tree>> = Lazy::new(|| Mutex::new(Tree::default()));
 
 pub fn send_group(group_name: String) {
-    let mut groups = GROUPS.lock().unwrap();
-    groups.entry(group_name.clone()).or_default();
-    *CURRENT_GROUP.lock().unwrap() = Some(group_name.clone());
+    if format() == LogFormat::Json {
+        tracing::info!(group = %group_name, "{}", group_name);
+        return;
+    }
+
+    let mut tree = TREE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    tree.group = Some(group_name.clone());
+    tree.last = None;
+
     println!("\x1B[94m• {}\x1B[0m", group_name);
 }
 
 pub fn send_message(message: String) {
-    let mut groups = GROUPS.lock().unwrap();
-    let group = CURRENT_GROUP
-        .lock()
-        .unwrap()
-        .as_ref()
-        .expect("WHERE IS GROUP FAGGOT")
-        .clone();
-    let msgs = groups.get_mut(&group).unwrap();
+    let mut tree = TREE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if format() == LogFormat::Json {
+        let group = tree.group.clone().unwrap_or_default();
+        tracing::info!(group = %group, "{}", message.trim_start_matches(['├', '└', '─', ' ']));
+        return;
+    }
 
     let gray = "\x1B[90m";
     let reset = "\x1B[0m";
 
-    if !msgs.is_empty() {
+    if let Some(prev) = &tree.last {
         print!("\x1B[1A\r\x1B[K");
 
-        let prev = msgs.last().unwrap();
         let prefix = if need_prefix(prev) { "├─" } else { "│ " };
 
         println!("{}{}{}{}", gray, prefix, prev, reset);
@@ -43,9 +55,9 @@ pub fn send_message(message: String) {
         "│ "
     };
     println!("{}{}{}{}", gray, prefix, message, reset);
-    io::stdout().flush().unwrap();
+    let _ = io::stdout().flush();
 
-    msgs.push(message);
+    tree.last = Some(message);
 }
 
 pub fn need_prefix(string: &str) -> bool {
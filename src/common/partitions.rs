@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, Datelike, Days, Months, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Pool, Postgres, query_as, query_scalar};
+
+use crate::common::config::PartitionsConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PartitionInterval {
+    Daily,
+    Monthly,
+}
+
+/// What happens to partitions that fall out of `partitions.retain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExpire {
+    /// Detach and keep the table, e.g. for archiving.
+    Detach,
+    Drop,
+}
+
+impl PartitionInterval {
+    pub fn start_of(self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let day = match self {
+            PartitionInterval::Daily => at.day(),
+            PartitionInterval::Monthly => 1,
+        };
+
+        Utc.with_ymd_and_hms(at.year(), at.month(), day, 0, 0, 0)
+            .single()
+            .unwrap_or(at)
+    }
+
+    pub fn next(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            PartitionInterval::Daily => start + Days::new(1),
+            PartitionInterval::Monthly => start + Months::new(1),
+        }
+    }
+
+    pub fn previous(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            PartitionInterval::Daily => start - Days::new(1),
+            PartitionInterval::Monthly => start - Months::new(1),
+        }
+    }
+
+    pub fn name(self, start: DateTime<Utc>) -> String {
+        match self {
+            PartitionInterval::Daily => start.format("events_p%Y_%m_%d").to_string(),
+            PartitionInterval::Monthly => start.format("events_p%Y_%m").to_string(),
+        }
+    }
+}
+
+/// Lower and upper bound of a range partition; None stands for MINVALUE or
+/// MAXVALUE.
+pub type Bounds = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+pub struct Partition {
+    pub name: String,
+    /// None for MINVALUE and for the default partition.
+    pub from: Option<DateTime<Utc>>,
+    /// None for MAXVALUE and for the default partition.
+    pub to: Option<DateTime<Utc>>,
+    pub is_default: bool,
+    /// Planner estimate, -1 before the first ANALYZE.
+    pub rows: i64,
+}
+
+impl Partition {
+    fn overlaps(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        !self.is_default
+            && self.from.is_none_or(|start| start < to)
+            && self.to.is_none_or(|end| end > from)
+    }
+}
+
+#[derive(Default)]
+pub struct MaintenanceReport {
+    pub created: Vec<String>,
+    pub expired: Vec<String>,
+}
+
+pub async fn is_partitioned(pool: &Pool<Postgres>) -> Result<bool, Error> {
+    Ok(query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_class WHERE oid = to_regclass('events') AND relkind = 'p')",
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn list(pool: &Pool<Postgres>) -> Result<Vec<Partition>, Error> {
+    let rows: Vec<(String, String, f32)> = query_as(
+        r#"
+        SELECT c.relname::text, pg_get_expr(c.relpartbound, c.oid), c.reltuples
+        FROM pg_inherits i
+        JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE i.inhparent = 'events'::regclass
+        ORDER BY c.relname
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut partitions: Vec<Partition> = rows
+        .into_iter()
+        .map(|(name, bound, rows)| {
+            let (from, to) = parse_bound(&bound).unwrap_or_default();
+            Partition {
+                name,
+                from,
+                to,
+                is_default: bound == "DEFAULT",
+                rows: rows as i64,
+            }
+        })
+        .collect();
+    partitions.sort_by_key(|partition| (partition.is_default, partition.from));
+
+    Ok(partitions)
+}
+
+/// Reads `FOR VALUES FROM ('…') TO ('…')` as printed by `pg_get_expr` in a
+/// UTC session. Returns None for the default partition.
+pub fn parse_bound(bound: &str) -> Option<Bounds> {
+    let rest = bound.strip_prefix("FOR VALUES FROM (")?;
+    let (from, to) = rest.split_once(") TO (")?;
+    let to = to.strip_suffix(')')?;
+
+    let value = |literal: &str| {
+        DateTime::parse_from_str(literal.trim_matches('\''), "%Y-%m-%d %H:%M:%S%#z")
+            .ok()
+            .map(|at| at.with_timezone(&Utc))
+    };
+
+    Some((value(from), value(to)))
+}
+
+/// Creates the partitions for every period that overlaps `from..=to` and is
+/// not covered yet. Rows already in the default partition move over.
+pub async fn ensure_range(
+    pool: &Pool<Postgres>,
+    interval: PartitionInterval,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<String>, Error> {
+    let existing = list(pool).await?;
+    let mut created = vec![];
+
+    let mut start = interval.start_of(from);
+    while start <= to {
+        let end = interval.next(start);
+
+        if existing
+            .iter()
+            .any(|partition| partition.overlaps(start, end))
+        {
+            if !existing.iter().any(|partition| {
+                partition.overlaps(start, end)
+                    && partition.from.is_none_or(|s| s <= start)
+                    && partition.to.is_none_or(|e| e >= end)
+            }) {
+                tracing::warn!(
+                    %start,
+                    %end,
+                    "period only partly covered by existing partitions, not creating one"
+                );
+            }
+        } else {
+            let name = interval.name(start);
+            create_partition(pool, &name, start, end)
+                .await
+                .with_context(|| format!("Cannot create partition {}", name))?;
+            created.push(name);
+        }
+
+        start = end;
+    }
+
+    Ok(created)
+}
+
+async fn create_partition(
+    pool: &Pool<Postgres>,
+    name: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(), Error> {
+    let (from, to) = (from.to_rfc3339(), to.to_rfc3339());
+
+    // New partitions follow events_default, which `durability::apply` keeps
+    // in line with the durability profile.
+    let unlogged: Option<bool> = query_scalar(
+        "SELECT relpersistence = 'u' FROM pg_class WHERE oid = to_regclass('events_default')",
+    )
+    .fetch_optional(pool)
+    .await?;
+    let persistence = if unlogged == Some(true) {
+        "UNLOGGED "
+    } else {
+        ""
+    };
+
+    let mut tx = pool.begin().await?;
+
+    tx.execute(
+        format!(
+            "CREATE {persistence}TABLE {name} (LIKE events INCLUDING DEFAULTS);
+             WITH moved AS (
+                 DELETE FROM events_default
+                 WHERE timestamp >= '{from}' AND timestamp < '{to}'
+                 RETURNING *
+             )
+             INSERT INTO {name} SELECT * FROM moved;
+             ALTER TABLE events ATTACH PARTITION {name} FOR VALUES FROM ('{from}') TO ('{to}')"
+        )
+        .as_str(),
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Pre-creates the current and `premake` future partitions and expires the
+/// ones older than `retain` periods.
+pub async fn maintain(
+    pool: &Pool<Postgres>,
+    config: &PartitionsConfig,
+    now: DateTime<Utc>,
+) -> Result<MaintenanceReport, Error> {
+    let interval = config.interval;
+    let current = interval.start_of(now);
+
+    let mut last = current;
+    for _ in 0..config.premake {
+        last = interval.next(last);
+    }
+
+    let mut report = MaintenanceReport {
+        created: ensure_range(pool, interval, current, last).await?,
+        ..MaintenanceReport::default()
+    };
+
+    if config.retain == 0 {
+        return Ok(report);
+    }
+
+    let mut cutoff = current;
+    for _ in 0..config.retain {
+        cutoff = interval.previous(cutoff);
+    }
+
+    for partition in list(pool).await? {
+        if partition.is_default || partition.to.is_none_or(|end| end > cutoff) {
+            continue;
+        }
+
+        let statement = match config.on_expire {
+            OnExpire::Detach => format!("ALTER TABLE events DETACH PARTITION {}", partition.name),
+            OnExpire::Drop => format!(
+                "ALTER TABLE events DETACH PARTITION {name}; DROP TABLE {name}",
+                name = partition.name
+            ),
+        };
+        pool.execute(statement.as_str())
+            .await
+            .with_context(|| format!("Cannot expire partition {}", partition.name))?;
+
+        report.expired.push(partition.name);
+    }
+
+    Ok(report)
+}
+
+/// Runs `maintain` now and then every `partitions.maintenance_interval_ms`.
+pub fn spawn_manager(pool: Pool<Postgres>, config: PartitionsConfig) {
+    tokio::spawn(async move {
+        let period = Duration::from_millis(config.maintenance_interval_ms);
+
+        loop {
+            match is_partitioned(&pool).await {
+                Ok(true) => match maintain(&pool, &config, Utc::now()).await {
+                    Ok(report) => {
+                        for name in &report.created {
+                            tracing::info!(partition = %name, "partition created");
+                        }
+                        for name in &report.expired {
+                            tracing::info!(partition = %name, on_expire = ?config.on_expire, "partition expired");
+                        }
+                    }
+                    Err(error) => {
+                        tracing::error!(error = %format!("{:#}", error), "partition maintenance failed")
+                    }
+                },
+                Ok(false) => {
+                    tracing::warn!(
+                        "events is not partitioned, run `migrate up`; partition manager stopped"
+                    );
+                    return;
+                }
+                Err(error) => tracing::error!(error = %error, "partition maintenance failed"),
+            }
+
+            tokio::time::sleep(period).await;
+        }
+    });
+}
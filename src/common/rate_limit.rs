@@ -0,0 +1,395 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    Error, HttpMessage, HttpRequest, ResponseError,
+    body::MessageBody,
+    dev::{Extensions, ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    middleware::Next,
+    web,
+};
+use moka::future::Cache;
+use once_cell::sync::Lazy;
+use redis::Script;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    auth::AuthenticatedKey, cache::redis_store::RedisStore, config::RateLimitConfig,
+    error::AppError, metrics::METRICS,
+};
+
+/// Token bucket in one round trip. Redis keeps `tokens` and the time `at`
+/// they were counted; the script refills by the elapsed time, takes one token
+/// when there is one and returns whether it did plus what is left. The count
+/// comes back as a string since Redis truncates Lua numbers to integers.
+pub const TOKEN_BUCKET: &str = r#"
+local burst = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2]) / 1000
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'at')
+local tokens = tonumber(bucket[1]) or burst
+local at = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - at) * rate)
+
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'at', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil((burst - tokens) / rate) + 1000)
+return { allowed, tostring(tokens) }
+"#;
+
+static SCRIPT: Lazy<Script> = Lazy::new(|| Script::new(TOKEN_BUCKET));
+
+// Buckets of keys, users and routes that went quiet are forgotten; a full
+// bucket and a forgotten one behave the same.
+const MEMORY_BUCKETS: u64 = 100_000;
+const MEMORY_IDLE: Duration = Duration::from_secs(600);
+
+/// Bucket size and refill rate.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    /// Requests that may pass back to back.
+    pub burst: u64,
+    /// Tokens added per second, up to `burst`.
+    pub per_second: f64,
+}
+
+/// The limits of one route; each is a separate bucket.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteLimits {
+    /// One bucket per API key.
+    pub per_key: Option<Limit>,
+    /// One bucket per `user_id` the request is about.
+    pub per_user: Option<Limit>,
+    /// One bucket shared by every caller.
+    pub total: Option<Limit>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bucket {
+    pub tokens: f64,
+    pub at_ms: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until a request may pass; 0 when this one did.
+    pub retry_after: u64,
+}
+
+impl Limit {
+    /// Same arithmetic as `TOKEN_BUCKET`, for the in-memory fallback.
+    pub fn take(&self, bucket: Option<Bucket>, now_ms: i64) -> (bool, Bucket) {
+        let burst = self.burst as f64;
+        let rate = self.per_second / 1000.0;
+
+        let Bucket { tokens, at_ms } = bucket.unwrap_or(Bucket {
+            tokens: burst,
+            at_ms: now_ms,
+        });
+        let mut tokens = burst.min(tokens + (now_ms - at_ms).max(0) as f64 * rate);
+
+        let allowed = tokens >= 1.0;
+        if allowed {
+            tokens -= 1.0;
+        }
+
+        (
+            allowed,
+            Bucket {
+                tokens,
+                at_ms: now_ms,
+            },
+        )
+    }
+
+    pub fn decision(&self, allowed: bool, tokens: f64) -> Decision {
+        let seconds = |missing: f64| (missing.max(0.0) / self.per_second).ceil() as u64;
+
+        Decision {
+            allowed,
+            limit: self.burst,
+            remaining: tokens.max(0.0).floor() as u64,
+            reset: seconds(self.burst as f64 - tokens),
+            retry_after: if allowed {
+                0
+            } else {
+                seconds(1.0 - tokens).max(1)
+            },
+        }
+    }
+}
+
+impl Decision {
+    // A refusal beats any pass; otherwise the bucket closest to empty.
+    fn tighter(self, other: Decision) -> Decision {
+        match (self.allowed, other.allowed) {
+            (true, false) => other,
+            (false, true) => self,
+            _ if other.remaining < self.remaining => other,
+            _ => self,
+        }
+    }
+
+    pub fn write_headers(&self, headers: &mut HeaderMap) {
+        for (name, value) in [
+            ("ratelimit-limit", self.limit),
+            ("ratelimit-remaining", self.remaining),
+            ("ratelimit-reset", self.reset.max(self.retry_after)),
+        ] {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+
+        if !self.allowed {
+            headers.insert(RETRY_AFTER, HeaderValue::from(self.retry_after));
+        }
+    }
+}
+
+// The tightest decision taken for the request so far.
+#[derive(Clone, Copy)]
+struct RateLimitStatus(Decision);
+
+fn record(extensions: &mut Extensions, decision: Decision) {
+    let tightest = match extensions.get::<RateLimitStatus>() {
+        Some(RateLimitStatus(current)) => current.tighter(decision),
+        None => decision,
+    };
+    extensions.insert(RateLimitStatus(tightest));
+}
+
+/// Token buckets in Redis, shared by every server, or in this process while
+/// Redis is unavailable.
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    redis: Option<RedisStore>,
+    memory: Cache<String, Arc<Mutex<Option<Bucket>>>>,
+}
+
+impl RateLimiter {
+    pub fn create(config: RateLimitConfig, redis: Option<RedisStore>) -> RateLimiter {
+        RateLimiter {
+            config: Arc::new(config),
+            redis,
+            memory: Cache::builder()
+                .max_capacity(MEMORY_BUCKETS)
+                .time_to_idle(MEMORY_IDLE)
+                .build(),
+        }
+    }
+
+    fn limits(&self, route: &str) -> Option<&RouteLimits> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        self.config
+            .routes
+            .get(route)
+            .or_else(|| self.config.routes.get("*"))
+    }
+
+    /// Takes from the route's shared bucket and the caller's key bucket. A
+    /// key listed in `rate_limit.keys` gets that limit instead of `per_key`.
+    pub async fn check_request(&self, route: &str, key_id: Option<i64>) -> Vec<Decision> {
+        let Some(limits) = self.limits(route) else {
+            return vec![];
+        };
+        let mut decisions = vec![];
+
+        if let Some(limit) = limits.total {
+            let bucket = format!("ratelimit:{}:total", route);
+            decisions.push(self.take(&bucket, limit, route, "total").await);
+        }
+
+        if let Some(key_id) = key_id {
+            let limit = self
+                .config
+                .keys
+                .get(&key_id.to_string())
+                .copied()
+                .or(limits.per_key);
+
+            if let Some(limit) = limit {
+                let bucket = format!("ratelimit:{}:key:{}", route, key_id);
+                decisions.push(self.take(&bucket, limit, route, "key").await);
+            }
+        }
+
+        decisions
+    }
+
+    /// Takes from the client address's bucket of uncached key lookups.
+    pub async fn check_lookup(&self, address: &str) -> Option<Decision> {
+        if !self.config.enabled {
+            return None;
+        }
+        let limit = self.config.key_lookups?;
+        let bucket = format!("ratelimit:key_lookup:{}", address);
+
+        Some(self.take(&bucket, limit, "key lookup", "address").await)
+    }
+
+    pub async fn check_user(&self, route: &str, user_id: i64) -> Option<Decision> {
+        let limit = self.limits(route)?.per_user?;
+        let bucket = format!("ratelimit:{}:user:{}", route, user_id);
+
+        Some(self.take(&bucket, limit, route, "user").await)
+    }
+
+    async fn take(&self, bucket: &str, limit: Limit, route: &str, per: &str) -> Decision {
+        let decision = match self.take_in_redis(bucket, limit).await {
+            Some(decision) => decision,
+            None => self.take_in_memory(bucket, limit).await,
+        };
+
+        if !decision.allowed {
+            METRICS
+                .rate_limited_requests
+                .with_label_values(&[route, per])
+                .inc();
+        }
+
+        decision
+    }
+
+    async fn take_in_redis(&self, bucket: &str, limit: Limit) -> Option<Decision> {
+        let redis = self.redis.as_ref().filter(|redis| redis.is_available())?;
+        let args = [limit.burst.to_string(), limit.per_second.to_string()];
+
+        match redis
+            .eval_script::<(i64, String)>(&SCRIPT, &[bucket], &args)
+            .await
+        {
+            Ok((allowed, tokens)) => Some(limit.decision(allowed == 1, tokens.parse().ok()?)),
+            Err(error) => {
+                tracing::debug!(error = %error, "rate limit falls back to memory");
+                None
+            }
+        }
+    }
+
+    async fn take_in_memory(&self, bucket: &str, limit: Limit) -> Decision {
+        let state = self
+            .memory
+            .get_with(bucket.to_owned(), async { Arc::new(Mutex::new(None)) })
+            .await;
+        let mut state = state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let (allowed, next) = limit.take(*state, now_ms());
+        *state = Some(next);
+
+        limit.decision(allowed, next.tokens)
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
+fn route_of(method: &str, pattern: Option<String>) -> Option<String> {
+    pattern.map(|pattern| format!("{} {}", method, pattern))
+}
+
+/// Applies the key and route limits of the matched route and adds the
+/// `RateLimit-*` headers of the tightest bucket to the response. Runs after
+/// `authenticate`, so the key is known. Without a `RateLimiter` in the app
+/// data nothing is limited.
+pub async fn rate_limit<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let limiter = req.app_data::<web::Data<RateLimiter>>().cloned();
+    let route = route_of(req.method().as_str(), req.match_pattern());
+
+    if let (Some(limiter), Some(route)) = (limiter, route) {
+        let key_id = req.extensions().get::<AuthenticatedKey>().map(|key| key.id);
+
+        for decision in limiter.check_request(&route, key_id).await {
+            if !decision.allowed {
+                return Ok(req
+                    .into_response(AppError::RateLimited(decision).error_response())
+                    .map_into_right_body());
+            }
+            record(&mut req.extensions_mut(), decision);
+        }
+    }
+
+    let mut response = next.call(req).await?;
+
+    let status = response
+        .request()
+        .extensions()
+        .get::<RateLimitStatus>()
+        .copied();
+    if let Some(RateLimitStatus(decision)) = status {
+        decision.write_headers(response.headers_mut());
+    }
+
+    Ok(response.map_into_left_body())
+}
+
+/// The per-user limit, for handlers that learn the user from the body or the
+/// path.
+pub async fn limit_user(req: &HttpRequest, user_id: i64) -> Result<(), AppError> {
+    let Some(limiter) = req.app_data::<web::Data<RateLimiter>>() else {
+        return Ok(());
+    };
+    let Some(route) = route_of(req.method().as_str(), req.match_pattern()) else {
+        return Ok(());
+    };
+
+    let Some(decision) = limiter.check_user(&route, user_id).await else {
+        return Ok(());
+    };
+    record(&mut req.extensions_mut(), decision);
+
+    if decision.allowed {
+        Ok(())
+    } else {
+        Err(AppError::RateLimited(decision))
+    }
+}
+
+/// The per-address limit on key lookups that miss the cache, for
+/// `authenticate`, which runs before `rate_limit` knows the key.
+pub async fn limit_key_lookup(req: &HttpRequest) -> Result<(), AppError> {
+    let Some(limiter) = req.app_data::<web::Data<RateLimiter>>() else {
+        return Ok(());
+    };
+    let Some(address) = req.peer_addr() else {
+        return Ok(());
+    };
+
+    let Some(decision) = limiter.check_lookup(&address.ip().to_string()).await else {
+        return Ok(());
+    };
+    record(&mut req.extensions_mut(), decision);
+
+    if decision.allowed {
+        Ok(())
+    } else {
+        Err(AppError::RateLimited(decision))
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Duration,
+};
+
+use anyhow::{Context, Error, bail};
+use chrono::{DateTime, Days, Utc};
+use sqlx::{Pool, Postgres, query, query_as, query_scalar};
+
+use crate::common::{
+    cache::{CacheDeleteKey, LeveledCache},
+    config::RetentionConfig,
+    metrics::METRICS,
+    tenant::Tenant,
+};
+
+// One batch: pick up to $3 expired rows, delete them and, when $4 is set,
+// copy them to events_archive in the same statement.
+const DELETE_BATCH: &str = r#"
+    WITH doomed AS (
+        SELECT id, timestamp FROM events
+        WHERE type_id = ANY($1) AND timestamp < $2
+        LIMIT $3
+    ),
+    deleted AS (
+        DELETE FROM events e
+        USING doomed d
+        WHERE e.id = d.id AND e.timestamp = d.timestamp
+        RETURNING e.id, e.user_id, e.type_id, e.timestamp, e.metadata, e.tenant_id
+    ),
+    archived AS (
+        INSERT INTO events_archive (id, user_id, type_id, timestamp, metadata, tenant_id)
+        SELECT * FROM deleted WHERE $4
+    ),
+    owners AS (
+        SELECT DISTINCT tenant_id, user_id FROM deleted
+    )
+    SELECT
+        (SELECT count(*) FROM deleted),
+        ARRAY(SELECT tenant_id FROM owners ORDER BY tenant_id, user_id),
+        ARRAY(SELECT user_id FROM owners ORDER BY tenant_id, user_id)
+"#;
+
+#[derive(Clone, Debug, PartialEq, Eq, sqlx::FromRow)]
+pub struct RetentionRule {
+    pub pattern: String,
+    pub ttl_days: i32,
+    pub archive: bool,
+}
+
+impl RetentionRule {
+    pub fn matches(&self, name: &str) -> bool {
+        glob_matches(&self.pattern, name)
+    }
+
+    // Literal characters decide between overlapping patterns, so `order.refund`
+    // beats `order.*`, which beats `*`.
+    fn specificity(&self) -> usize {
+        self.pattern.chars().filter(|c| *c != '*').count()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RetentionReport {
+    /// Events removed per rule pattern, archived ones included.
+    pub deleted: Vec<(String, u64)>,
+    pub archived: u64,
+    /// Tenant and id of every user who lost events.
+    pub users: BTreeSet<(i64, i64)>,
+}
+
+impl RetentionReport {
+    pub fn total(&self) -> u64 {
+        self.deleted.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// `*` matches any run of characters, everything else matches itself.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Assigns every event type to its most specific matching rule. Types without
+/// a rule are kept forever.
+pub fn resolve<'a>(
+    rules: &'a [RetentionRule],
+    types: &[(i64, String)],
+) -> Vec<(&'a RetentionRule, Vec<i64>)> {
+    let mut assigned: HashMap<&str, Vec<i64>> = HashMap::new();
+
+    for (id, name) in types {
+        let rule = rules
+            .iter()
+            .filter(|rule| rule.matches(name))
+            .max_by(|a, b| {
+                a.specificity()
+                    .cmp(&b.specificity())
+                    .then_with(|| b.pattern.cmp(&a.pattern))
+            });
+
+        if let Some(rule) = rule {
+            assigned.entry(rule.pattern.as_str()).or_default().push(*id);
+        }
+    }
+
+    rules
+        .iter()
+        .filter_map(|rule| {
+            assigned
+                .remove(rule.pattern.as_str())
+                .map(|ids| (rule, ids))
+        })
+        .collect()
+}
+
+pub async fn list_rules(pool: &Pool<Postgres>) -> Result<Vec<RetentionRule>, Error> {
+    query_as("SELECT pattern, ttl_days, archive FROM retention_rules ORDER BY pattern")
+        .fetch_all(pool)
+        .await
+        .context("Cannot read retention_rules, run `migrate up` first")
+}
+
+pub async fn set_rule(pool: &Pool<Postgres>, rule: &RetentionRule) -> Result<(), Error> {
+    if rule.pattern.is_empty() {
+        bail!("The pattern must not be empty");
+    }
+    if rule.ttl_days <= 0 {
+        bail!("The TTL must be at least one day");
+    }
+
+    query(
+        r#"
+        INSERT INTO retention_rules (pattern, ttl_days, archive) VALUES ($1, $2, $3)
+        ON CONFLICT (pattern) DO UPDATE SET ttl_days = EXCLUDED.ttl_days, archive = EXCLUDED.archive
+        "#,
+    )
+    .bind(&rule.pattern)
+    .bind(rule.ttl_days)
+    .bind(rule.archive)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_rule(pool: &Pool<Postgres>, pattern: &str) -> Result<bool, Error> {
+    let result = query("DELETE FROM retention_rules WHERE pattern = $1")
+        .bind(pattern)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Counts the events each rule would remove at `now`, without deleting.
+pub async fn preview(
+    pool: &Pool<Postgres>,
+    now: DateTime<Utc>,
+) -> Result<Vec<(RetentionRule, i64)>, Error> {
+    let rules = list_rules(pool).await?;
+    let types = event_types(pool).await?;
+    let mut counts = vec![];
+
+    for (rule, type_ids) in resolve(&rules, &types) {
+        let count: i64 =
+            query_scalar("SELECT count(*) FROM events WHERE type_id = ANY($1) AND timestamp < $2")
+                .bind(&type_ids)
+                .bind(cutoff(rule, now))
+                .fetch_one(pool)
+                .await?;
+        counts.push((rule.clone(), count));
+    }
+
+    Ok(counts)
+}
+
+/// Deletes the expired events of every rule in batches of
+/// `retention.batch_size`, each its own short statement, and drops the cache
+/// entries that may still show them.
+pub async fn run(
+    pool: &Pool<Postgres>,
+    cache: Option<&LeveledCache>,
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> Result<RetentionReport, Error> {
+    let timer = METRICS.retention_run_duration.start_timer();
+
+    let rules = list_rules(pool).await?;
+    let types = event_types(pool).await?;
+    let mut report = RetentionReport::default();
+
+    for (rule, type_ids) in resolve(&rules, &types) {
+        let cutoff = cutoff(rule, now);
+        let mut deleted: u64 = 0;
+
+        loop {
+            let (count, tenants, users): (i64, Vec<i64>, Vec<i64>) = query_as(DELETE_BATCH)
+                .bind(&type_ids)
+                .bind(cutoff)
+                .bind(config.batch_size as i64)
+                .bind(rule.archive)
+                .fetch_one(pool)
+                .await
+                .with_context(|| format!("Retention for `{}` failed", rule.pattern))?;
+
+            deleted += count as u64;
+            report.users.extend(tenants.into_iter().zip(users));
+
+            let action = if rule.archive { "archived" } else { "deleted" };
+            METRICS
+                .retention_events
+                .with_label_values(&[&rule.pattern, action])
+                .inc_by(count as u64);
+
+            if (count as u64) < config.batch_size {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(config.batch_pause_ms)).await;
+        }
+
+        if rule.archive {
+            report.archived += deleted;
+        }
+        report.deleted.push((rule.pattern.clone(), deleted));
+    }
+
+    if let Some(cache) = cache
+        && report.total() > 0
+    {
+        invalidate(cache, &report.users).await;
+    }
+
+    timer.observe_duration();
+    Ok(report)
+}
+
+/// Runs `run` now and then every `retention.interval_ms`.
+pub fn spawn_job(pool: Pool<Postgres>, cache: LeveledCache, config: RetentionConfig) {
+    tokio::spawn(async move {
+        let period = Duration::from_millis(config.interval_ms);
+
+        loop {
+            match run(&pool, Some(&cache), &config, Utc::now()).await {
+                Ok(report) => {
+                    for (pattern, deleted) in report.deleted.iter().filter(|(_, n)| *n > 0) {
+                        tracing::info!(%pattern, deleted, "retention applied");
+                    }
+                }
+                Err(error) => {
+                    METRICS.retention_failures.inc();
+                    tracing::error!(error = %format!("{:#}", error), "retention run failed");
+                }
+            }
+
+            tokio::time::sleep(period).await;
+        }
+    });
+}
+
+fn cutoff(rule: &RetentionRule, now: DateTime<Utc>) -> DateTime<Utc> {
+    now - Days::new(rule.ttl_days as u64)
+}
+
+async fn event_types(pool: &Pool<Postgres>) -> Result<Vec<(i64, String)>, Error> {
+    Ok(query_as("SELECT id, name FROM event_types")
+        .fetch_all(pool)
+        .await?)
+}
+
+async fn invalidate(cache: &LeveledCache, users: &BTreeSet<(i64, i64)>) {
+    for (tenant, user_id) in users {
+        let _ = cache
+            .invalidate(CacheDeleteKey::Exact(
+                Tenant(*tenant).user_events_key(*user_id),
+            ))
+            .await;
+    }
+
+    let tenants: BTreeSet<Tenant> = users.iter().map(|(tenant, _)| Tenant(*tenant)).collect();
+    for tenant in tenants {
+        for pattern in [tenant.stats_pattern(), tenant.page_pattern()] {
+            let _ = cache.invalidate(CacheDeleteKey::Pattern(pattern)).await;
+        }
+        let _ = cache
+            .invalidate(CacheDeleteKey::Exact(tenant.key("total_events")))
+            .await;
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::Error;
+use sqlx::{
+    Executor, Pool, Postgres,
+    migrate::{Migrate, Migrator},
+};
+
+// Applied versions live in `_sqlx_migrations`. `run` takes a Postgres advisory
+// lock for the whole batch, so replicas booting together apply each migration
+// once and the others wait.
+pub static MIGRATOR: Migrator = sqlx::migrate!("src/common/migrations");
+
+// The seeder drops these around bulk loads; keeping them as the migration
+// files means the two can never drift apart.
+pub const INDEXES_UP: &str = include_str!("migrations/1_indexes.up.sql");
+pub const INDEXES_DOWN: &str = include_str!("migrations/1_indexes.down.sql");
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    pub checksum_mismatch: bool,
+}
+
+pub async fn migrate(pool: &Pool<Postgres>) -> Result<(), Error> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+pub async fn status(
+    migrator: &Migrator,
+    pool: &Pool<Postgres>,
+) -> Result<Vec<MigrationStatus>, Error> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    Ok(migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| {
+            let record = applied.iter().find(|a| a.version == migration.version);
+
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied: record.is_some(),
+                checksum_mismatch: record.is_some_and(|a| a.checksum != migration.checksum),
+            }
+        })
+        .collect())
+}
+
+pub async fn schema_version(pool: &Pool<Postgres>) -> Result<Option<i64>, Error> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .max())
+}
+
+pub async fn create_indexes(pool: &Pool<Postgres>) -> Result<(), Error> {
+    pool.execute(INDEXES_UP).await?;
+    Ok(())
+}
+
+pub async fn drop_indexes(pool: &Pool<Postgres>) -> Result<(), Error> {
+    pool.execute(INDEXES_DOWN).await?;
+    Ok(())
+}
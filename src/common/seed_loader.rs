@@ -0,0 +1,242 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::{Context, Error, bail};
+use chrono::{DateTime, SecondsFormat, Utc};
+use itoa::Buffer;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Pool, Postgres,
+    postgres::{PgCopyIn, PgPoolCopyExt},
+    query_scalar,
+};
+use tokio::{
+    sync::{
+        Mutex,
+        mpsc::{Receiver, channel},
+    },
+    task::JoinSet,
+};
+
+use crate::common::{
+    config::Config,
+    output::send_message,
+    seed_profile::{EventGenerator, GeneratedEvent},
+    snowflake::next_id,
+    tenant::Tenant,
+};
+
+const CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+const BINARY_HEADER: &[u8] = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0";
+const BINARY_TRAILER: &[u8] = &(-1i16).to_be_bytes();
+
+// Seconds between the Unix and the Postgres epoch (2000-01-01).
+const POSTGRES_EPOCH: i64 = 946_684_800;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedLoader {
+    /// `COPY events FROM STDIN` over parallel pool connections.
+    Copy,
+    /// Rejected by `Config::validate`: `pg_bulkload` writes heap pages
+    /// directly and cannot load the partitioned `events` table.
+    PgBulkload,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyFormat {
+    Csv,
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventIds {
+    Snowflake,
+    /// Numbered from the given id plus one.
+    After(i64),
+}
+
+/// Streams the generated events into Postgres and checks that the table grew
+/// by exactly that many rows. Returns the number of events loaded.
+pub async fn load_events(
+    pool: &Pool<Postgres>,
+    config: &Config,
+    mut generator: EventGenerator,
+    ids: EventIds,
+    tenant: Tenant,
+) -> Result<u64, Error> {
+    let before = count_events(pool).await?;
+    let start = Instant::now();
+
+    let format = config.seed.copy_format;
+    let workers = config.seed.parallelism;
+
+    let (sender, receiver) = channel::<Vec<u8>>(workers * 2);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let mut loaders = JoinSet::new();
+    for _ in 0..workers {
+        loaders.spawn(copy_chunks(pool.clone(), format, receiver.clone()));
+    }
+    drop(receiver);
+
+    let mut generated: u64 = 0;
+    let mut chunk = Vec::<u8>::with_capacity(CHUNK_BYTES + 4096);
+    let mut digits = Buffer::new();
+
+    while let Some(event) = generator.next_event() {
+        generated += 1;
+        let id = match ids {
+            EventIds::Snowflake => next_id(),
+            EventIds::After(last) => last + generated as i64,
+        };
+        encode_row(&mut chunk, format, id, tenant, &event, &mut digits);
+
+        // A closed channel means every loader stopped; their errors follow below.
+        if chunk.len() >= CHUNK_BYTES && sender.send(std::mem::take(&mut chunk)).await.is_err() {
+            break;
+        }
+    }
+    if !chunk.is_empty() {
+        let _ = sender.send(chunk).await;
+    }
+    drop(sender);
+
+    while let Some(result) = loaders.join_next().await {
+        result.context("Event loader panicked")??;
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    send_message(format!(
+        "Loaded {} events with {:?} in {:.1} seconds ({:.0} rows/s)",
+        generated,
+        config.seed.loader,
+        elapsed,
+        generated as f64 / elapsed.max(f64::EPSILON)
+    ));
+
+    let added = count_events(pool).await? - before;
+    if added != generated as i64 {
+        bail!(
+            "Expected {} new events after loading, found {}",
+            generated,
+            added
+        );
+    }
+    send_message(format!("Verified {} new rows in events", added));
+
+    Ok(generated)
+}
+
+/// Appends one `events` row in the given COPY format, `tenant_id` last.
+pub fn encode_row(
+    chunk: &mut Vec<u8>,
+    format: CopyFormat,
+    id: i64,
+    tenant: Tenant,
+    event: &GeneratedEvent,
+    digits: &mut Buffer,
+) {
+    match format {
+        CopyFormat::Csv => {
+            for value in [id, event.user_id, event.type_id] {
+                chunk.extend_from_slice(digits.format(value).as_bytes());
+                chunk.push(b',');
+            }
+
+            let timestamp = DateTime::<Utc>::from_timestamp(event.timestamp, 0)
+                .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+                .to_rfc3339_opts(SecondsFormat::Secs, true);
+            chunk.extend_from_slice(timestamp.as_bytes());
+            chunk.push(b',');
+
+            chunk.push(b'"');
+            for byte in event.metadata.bytes() {
+                if byte == b'"' {
+                    chunk.push(b'"');
+                }
+                chunk.push(byte);
+            }
+            chunk.extend_from_slice(b"\",");
+            chunk.extend_from_slice(digits.format(tenant.0).as_bytes());
+            chunk.push(b'\n');
+        }
+        CopyFormat::Binary => {
+            chunk.extend_from_slice(&6i16.to_be_bytes());
+            for value in [
+                id,
+                event.user_id,
+                event.type_id,
+                (event.timestamp - POSTGRES_EPOCH) * 1_000_000,
+            ] {
+                chunk.extend_from_slice(&8i32.to_be_bytes());
+                chunk.extend_from_slice(&value.to_be_bytes());
+            }
+
+            // jsonb is a version byte followed by the JSON text.
+            chunk.extend_from_slice(&(event.metadata.len() as i32 + 1).to_be_bytes());
+            chunk.push(1);
+            chunk.extend_from_slice(event.metadata.as_bytes());
+
+            chunk.extend_from_slice(&8i32.to_be_bytes());
+            chunk.extend_from_slice(&tenant.0.to_be_bytes());
+        }
+    }
+}
+
+async fn copy_chunks(
+    pool: Pool<Postgres>,
+    format: CopyFormat,
+    chunks: Arc<Mutex<Receiver<Vec<u8>>>>,
+) -> Result<(), Error> {
+    let statement = match format {
+        CopyFormat::Csv => {
+            "COPY events (id, user_id, type_id, timestamp, metadata, tenant_id) FROM STDIN WITH (FORMAT csv)"
+        }
+        CopyFormat::Binary => {
+            "COPY events (id, user_id, type_id, timestamp, metadata, tenant_id) FROM STDIN WITH (FORMAT binary)"
+        }
+    };
+
+    let mut copy = pool.copy_in_raw(statement).await?;
+
+    if let Err(error) = send_chunks(&mut copy, format, &chunks).await {
+        // Stop the producer before the abort round trip.
+        chunks.lock().await.close();
+        copy.abort(error.to_string()).await.ok();
+        return Err(error.into());
+    }
+
+    copy.finish().await?;
+    Ok(())
+}
+
+async fn send_chunks(
+    copy: &mut PgCopyIn<sqlx::pool::PoolConnection<Postgres>>,
+    format: CopyFormat,
+    chunks: &Mutex<Receiver<Vec<u8>>>,
+) -> Result<(), sqlx::Error> {
+    if format == CopyFormat::Binary {
+        copy.send(BINARY_HEADER).await?;
+    }
+
+    loop {
+        let Some(chunk) = chunks.lock().await.recv().await else {
+            break;
+        };
+        copy.send(chunk).await?;
+    }
+
+    if format == CopyFormat::Binary {
+        copy.send(BINARY_TRAILER).await?;
+    }
+
+    Ok(())
+}
+
+async fn count_events(pool: &Pool<Postgres>) -> Result<i64, Error> {
+    Ok(query_scalar::<_, i64>("SELECT count(*) FROM events")
+        .fetch_one(pool)
+        .await?)
+}
@@ -0,0 +1,616 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    f64::consts::TAU,
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Error, bail};
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+
+use crate::common::metadata_policy::MetadataPolicies;
+
+pub const DEFAULT_PROFILES_FILE: &str = "seed.toml";
+pub const DEFAULT_PROFILE: &str = "default";
+
+const PAGES: [&str; 100] = [
+    "/registration",
+    "/login",
+    "/logout",
+    "/profile/edit",
+    "/order/create",
+    "/order/confirm",
+    "/order/shipped",
+    "/order/tracking",
+    "/payment/complete",
+    "/payment/failed",
+    "/payment/refund",
+    "/product/view",
+    "/cart/add",
+    "/cart/remove",
+    "/emails/sent",
+    "/emails/opened",
+    "/emails/click",
+    "/notifications/sent",
+    "/notifications/read",
+    "/api/request",
+    "/api/response",
+    "/api/error",
+    "/password/reset",
+    "/password/change",
+    "/security/2fa",
+    "/security/2fa/disable",
+    "/account/delete",
+    "/account/suspend",
+    "/account/reactivate",
+    "/subscription/start",
+    "/subscription/cancel",
+    "/subscription/renew",
+    "/invite/send",
+    "/invite/accept",
+    "/feedback",
+    "/profile/avatar",
+    "/profile/preferences",
+    "/email/verify",
+    "/login/failed",
+    "/profile/view",
+    "/profile/notifications",
+    "/newsletter/subscribe",
+    "/order/cancel",
+    "/order/return",
+    "/order/return/approved",
+    "/order/return/rejected",
+    "/order/review",
+    "/order/invoice",
+    "/payment/pending",
+    "/payment/dispute",
+    "/payment/settled",
+    "/cart/view",
+    "/cart/update",
+    "/cart/clear",
+    "/checkout/start",
+    "/checkout/complete",
+    "/product/review",
+    "/wishlist/add",
+    "/wishlist/remove",
+    "/product/compare",
+    "/product/share",
+    "/product/restock",
+    "/product/stock",
+    "/emails/bounced",
+    "/emails/unsubscribe",
+    "/notifications/dismiss",
+    "/notifications/failure",
+    "/session/start",
+    "/session/expired",
+    "/session/end",
+    "/admin/login",
+    "/admin/logout",
+    "/admin/user/edit",
+    "/admin/user/delete",
+    "/admin/reports",
+    "/admin/settings",
+    "/files/upload",
+    "/files/delete",
+    "/files/download",
+    "/files/preview",
+    "/support/create",
+    "/support/close",
+    "/support/reopen",
+    "/support/message",
+    "/support/rating",
+    "/search",
+    "/search/filter",
+    "/search/sort",
+    "/settings",
+    "/settings/language",
+    "/settings/timezone",
+    "/api/token",
+    "/api/token/revoke",
+    "/api/rate-limit",
+    "/cron/start",
+    "/cron/end",
+    "/cron/failure",
+    "/webhooks/incoming",
+    "/webhooks/verified",
+    "/webhooks/failure",
+];
+
+const PLACEHOLDERS: [&str; 3] = ["{user_id}", "{type_id}", "{n}"];
+
+/// How an event picks its user, type or metadata template.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pick {
+    /// The n-th event takes item `n % len`.
+    RoundRobin,
+    Uniform,
+    /// Item `k` is drawn with weight `1 / (k + 1)^exponent`.
+    Zipf(f64),
+}
+
+/// Traffic that peaks at `peak_hour` (UTC) and dips twelve hours later.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Diurnal {
+    /// 0 is flat, 1 leaves the quietest hour empty.
+    pub amplitude: f64,
+    pub peak_hour: f64,
+}
+
+impl Default for Diurnal {
+    fn default() -> Self {
+        Diurnal {
+            amplitude: 0.6,
+            peak_hour: 20.0,
+        }
+    }
+}
+
+/// Bursts of consecutive events from one user a few seconds apart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sessions {
+    /// Chance that an event opens a session.
+    pub probability: f64,
+    pub min_events: u32,
+    pub max_events: u32,
+    pub max_gap_seconds: u32,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Sessions {
+            probability: 0.3,
+            min_events: 3,
+            max_events: 12,
+            max_gap_seconds: 90,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SeedProfile {
+    /// Fixes the dataset. Without it a random seed is drawn and printed.
+    pub seed: Option<u64>,
+    pub users: usize,
+    pub types: usize,
+    pub events: usize,
+    /// Length of the time span that ends at `end`.
+    pub days: u32,
+    /// Defaults to now, so it is required together with `seed`.
+    pub end: Option<DateTime<Utc>>,
+    pub pick_users: Pick,
+    pub pick_types: Pick,
+    pub pick_templates: Pick,
+    pub diurnal: Option<Diurnal>,
+    pub sessions: Option<Sessions>,
+    /// Metadata objects; string values may use `{user_id}`, `{type_id}` and
+    /// `{n}` (1..=1000). Empty means one `{"page": ...}` per built-in page.
+    pub templates: Vec<JsonValue>,
+}
+
+impl Default for SeedProfile {
+    fn default() -> Self {
+        SeedProfile {
+            seed: None,
+            users: 1000,
+            types: 100,
+            events: 10_000_000,
+            days: 365,
+            end: None,
+            pick_users: Pick::RoundRobin,
+            pick_types: Pick::RoundRobin,
+            pick_templates: Pick::RoundRobin,
+            diurnal: None,
+            sessions: None,
+            templates: vec![],
+        }
+    }
+}
+
+impl SeedProfile {
+    pub fn realistic() -> SeedProfile {
+        SeedProfile {
+            seed: Some(42),
+            end: DateTime::from_timestamp(1_735_689_600, 0),
+            pick_users: Pick::Zipf(1.1),
+            pick_types: Pick::Zipf(0.8),
+            pick_templates: Pick::Uniform,
+            diurnal: Some(Diurnal::default()),
+            sessions: Some(Sessions::default()),
+            ..SeedProfile::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut problems = vec![];
+
+        for (key, value) in [
+            ("users", self.users),
+            ("types", self.types),
+            ("events", self.events),
+            ("days", self.days as usize),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", key));
+            }
+        }
+
+        if self.seed.is_some() && self.end.is_none() {
+            problems.push("end is required with seed, otherwise the time span moves".to_owned());
+        }
+
+        for (key, pick) in [
+            ("pick_users", &self.pick_users),
+            ("pick_types", &self.pick_types),
+            ("pick_templates", &self.pick_templates),
+        ] {
+            if let Pick::Zipf(exponent) = pick
+                && !(*exponent > 0.0 && exponent.is_finite())
+            {
+                problems.push(format!("{} zipf exponent must be greater than 0", key));
+            }
+        }
+
+        if let Some(diurnal) = &self.diurnal {
+            if !(0.0..=1.0).contains(&diurnal.amplitude) {
+                problems.push("diurnal.amplitude must be between 0 and 1".to_owned());
+            }
+            if !(0.0..24.0).contains(&diurnal.peak_hour) {
+                problems.push("diurnal.peak_hour must be between 0 and 24".to_owned());
+            }
+        }
+
+        if let Some(sessions) = &self.sessions {
+            if !(0.0..=1.0).contains(&sessions.probability) {
+                problems.push("sessions.probability must be between 0 and 1".to_owned());
+            }
+            if sessions.min_events == 0 || sessions.min_events > sessions.max_events {
+                problems.push(
+                    "sessions.min_events must be at least 1 and at most sessions.max_events"
+                        .to_owned(),
+                );
+            }
+            if sessions.max_gap_seconds == 0 {
+                problems.push("sessions.max_gap_seconds must be greater than 0".to_owned());
+            }
+        }
+
+        // The stats group events by `page`, so every template needs one.
+        for (index, template) in self.templates.iter().enumerate() {
+            if !template.is_object() {
+                problems.push(format!("templates[{}] must be a table", index));
+            } else if !template.get("page").is_some_and(JsonValue::is_string) {
+                problems.push(format!("templates[{}].page must be a string", index));
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        bail!("Invalid seed profile:\n  - {}", problems.join("\n  - "))
+    }
+
+    /// Fills in a random seed and the current time where the profile has none.
+    pub fn resolved(mut self) -> SeedProfile {
+        self.seed.get_or_insert_with(|| rand::rng().next_u64());
+        self.end.get_or_insert_with(Utc::now);
+        self
+    }
+}
+
+/// Built-in profiles, overridden or extended by the tables of a profiles file.
+pub struct SeedProfiles {
+    profiles: BTreeMap<String, SeedProfile>,
+}
+
+impl Default for SeedProfiles {
+    fn default() -> Self {
+        SeedProfiles {
+            profiles: BTreeMap::from([
+                (DEFAULT_PROFILE.to_owned(), SeedProfile::default()),
+                ("realistic".to_owned(), SeedProfile::realistic()),
+            ]),
+        }
+    }
+}
+
+impl SeedProfiles {
+    /// Reads `file`, or ./seed.toml when present, on top of the built-ins.
+    pub fn load(file: Option<&Path>) -> Result<SeedProfiles, Error> {
+        let default = Path::new(DEFAULT_PROFILES_FILE);
+        let path = match file {
+            Some(path) => path,
+            None if default.exists() => default,
+            None => return Ok(SeedProfiles::default()),
+        };
+
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read seed profiles {}", path.display()))?;
+
+        SeedProfiles::from_toml(&text)
+            .with_context(|| format!("Invalid seed profiles {}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<SeedProfiles, Error> {
+        let mut profiles = SeedProfiles::default();
+        let parsed: BTreeMap<String, SeedProfile> = toml::from_str(text)?;
+        profiles.profiles.extend(parsed);
+        Ok(profiles)
+    }
+
+    pub fn get(&self, name: &str) -> Result<SeedProfile, Error> {
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            Error::msg(format!(
+                "Unknown seed profile `{}`, available: {}",
+                name,
+                names.join(", ")
+            ))
+        })
+    }
+}
+
+pub struct GeneratedEvent<'a> {
+    pub user_id: i64,
+    pub type_id: i64,
+    pub timestamp: i64,
+    pub metadata: Cow<'a, str>,
+}
+
+enum Sampler {
+    RoundRobin(usize),
+    Uniform(usize),
+    Zipf(Vec<f64>),
+}
+
+impl Sampler {
+    fn create(pick: &Pick, len: usize) -> Sampler {
+        match pick {
+            Pick::RoundRobin => Sampler::RoundRobin(len),
+            Pick::Uniform => Sampler::Uniform(len),
+            Pick::Zipf(exponent) => {
+                let mut total = 0.0;
+                let cumulative = (1..=len)
+                    .map(|rank| {
+                        total += 1.0 / (rank as f64).powf(*exponent);
+                        total
+                    })
+                    .collect();
+                Sampler::Zipf(cumulative)
+            }
+        }
+    }
+
+    fn sample(&self, key: usize, rng: &mut ChaCha8Rng) -> usize {
+        match self {
+            Sampler::RoundRobin(len) => key % len,
+            Sampler::Uniform(len) => rng.random_range(0..*len),
+            Sampler::Zipf(cumulative) => {
+                let point = rng.random::<f64>() * cumulative[cumulative.len() - 1];
+                cumulative
+                    .partition_point(|total| *total <= point)
+                    .min(cumulative.len() - 1)
+            }
+        }
+    }
+}
+
+struct Template {
+    json: String,
+    dynamic: bool,
+}
+
+struct Redaction {
+    policies: MetadataPolicies,
+    /// Event type names in the order of the type ids.
+    type_names: Vec<String>,
+    /// Redacted static templates by type and template index.
+    done: HashMap<(usize, usize), String>,
+}
+
+struct Session {
+    user: usize,
+    left: u32,
+    timestamp: i64,
+}
+
+/// Produces the events of a resolved profile. The same seed, end and ids
+/// always yield the same sequence.
+pub struct EventGenerator {
+    rng: ChaCha8Rng,
+    key: usize,
+    count: usize,
+    start: i64,
+    end: i64,
+    users_id: Vec<i64>,
+    types_id: Vec<i64>,
+    users: Sampler,
+    types: Sampler,
+    picks: Sampler,
+    templates: Vec<Template>,
+    redaction: Option<Redaction>,
+    diurnal: Option<Diurnal>,
+    sessions: Option<Sessions>,
+    session: Option<Session>,
+}
+
+impl EventGenerator {
+    pub fn create(
+        profile: &SeedProfile,
+        users_id: Vec<i64>,
+        types_id: Vec<i64>,
+    ) -> Result<EventGenerator, Error> {
+        profile.validate()?;
+
+        let (Some(seed), Some(end)) = (profile.seed, profile.end) else {
+            bail!("Seed profile must be resolved before generating events");
+        };
+        if users_id.is_empty() || types_id.is_empty() {
+            bail!("Events need at least one user and one type");
+        }
+
+        let templates: Vec<Template> = if profile.templates.is_empty() {
+            PAGES
+                .iter()
+                .map(|page| json!({ "page": page }))
+                .map(|json| Template::create(&json))
+                .collect()
+        } else {
+            profile.templates.iter().map(Template::create).collect()
+        };
+
+        Ok(EventGenerator {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            key: 0,
+            count: profile.events,
+            start: (end - Duration::days(profile.days as i64)).timestamp(),
+            end: end.timestamp(),
+            users: Sampler::create(&profile.pick_users, users_id.len()),
+            types: Sampler::create(&profile.pick_types, types_id.len()),
+            picks: Sampler::create(&profile.pick_templates, templates.len()),
+            users_id,
+            types_id,
+            templates,
+            redaction: None,
+            diurnal: profile.diurnal.clone(),
+            sessions: profile.sessions.clone(),
+            session: None,
+        })
+    }
+
+    /// Runs the metadata of every event through the ingest policies;
+    /// `type_names` follows the order of the type ids.
+    pub fn with_policies(
+        mut self,
+        policies: MetadataPolicies,
+        type_names: Vec<String>,
+    ) -> Result<EventGenerator, Error> {
+        if type_names.len() != self.types_id.len() {
+            bail!("Every event type needs a name for the metadata policies");
+        }
+
+        self.redaction = Some(Redaction {
+            policies,
+            type_names,
+            done: HashMap::new(),
+        });
+        Ok(self)
+    }
+
+    pub fn next_event(&mut self) -> Option<GeneratedEvent<'_>> {
+        if self.key >= self.count {
+            return None;
+        }
+        let key = self.key;
+        self.key += 1;
+
+        let (user, timestamp) = match &mut self.session {
+            Some(session) if session.left > 0 => {
+                let max_gap = self.sessions.as_ref().map_or(1, |s| s.max_gap_seconds);
+                session.left -= 1;
+                session.timestamp =
+                    (session.timestamp + self.rng.random_range(1..=max_gap) as i64).min(self.end);
+                (session.user, session.timestamp)
+            }
+            _ => {
+                let user = self.users.sample(key, &mut self.rng);
+                let timestamp = self.timestamp();
+                self.session = self.open_session(user, timestamp);
+                (user, timestamp)
+            }
+        };
+
+        let user_id = self.users_id[user];
+        let type_index = self.types.sample(key, &mut self.rng);
+        let type_id = self.types_id[type_index];
+        let template_index = self.picks.sample(key, &mut self.rng);
+        let template = &self.templates[template_index];
+
+        let metadata = match (template.dynamic, &mut self.redaction) {
+            (true, redaction) => {
+                let mut json = template
+                    .json
+                    .replace("{user_id}", &user_id.to_string())
+                    .replace("{type_id}", &type_id.to_string());
+                if json.contains("{n}") {
+                    json = json.replace("{n}", &self.rng.random_range(1..=1000).to_string());
+                }
+                if let Some(redaction) = redaction {
+                    json = redaction
+                        .policies
+                        .apply_str(&redaction.type_names[type_index], &json);
+                }
+                Cow::Owned(json)
+            }
+            (false, Some(redaction)) => Cow::Borrowed(
+                redaction
+                    .done
+                    .entry((type_index, template_index))
+                    .or_insert_with(|| {
+                        redaction
+                            .policies
+                            .apply_str(&redaction.type_names[type_index], &template.json)
+                    })
+                    .as_str(),
+            ),
+            (false, None) => Cow::Borrowed(template.json.as_str()),
+        };
+
+        Some(GeneratedEvent {
+            user_id,
+            type_id,
+            timestamp,
+            metadata,
+        })
+    }
+
+    fn timestamp(&mut self) -> i64 {
+        loop {
+            let timestamp = self.rng.random_range(self.start..=self.end);
+            let Some(diurnal) = &self.diurnal else {
+                return timestamp;
+            };
+
+            let hour = timestamp.rem_euclid(86_400) as f64 / 3600.0;
+            let weight = 1.0 + diurnal.amplitude * (TAU * (hour - diurnal.peak_hour) / 24.0).cos();
+            if self.rng.random::<f64>() * (1.0 + diurnal.amplitude) < weight {
+                return timestamp;
+            }
+        }
+    }
+
+    fn open_session(&mut self, user: usize, timestamp: i64) -> Option<Session> {
+        let sessions = self.sessions.as_ref()?;
+        if self.rng.random::<f64>() >= sessions.probability {
+            return None;
+        }
+
+        Some(Session {
+            user,
+            left: self
+                .rng
+                .random_range(sessions.min_events..=sessions.max_events)
+                - 1,
+            timestamp,
+        })
+    }
+}
+
+impl Template {
+    fn create(json: &JsonValue) -> Template {
+        let json = json.to_string();
+        let dynamic = PLACEHOLDERS
+            .iter()
+            .any(|placeholder| json.contains(placeholder));
+        Template { json, dynamic }
+    }
+}
@@ -1,74 +1,203 @@
-use std::thread::current;
-
-use chrono::{DateTime, Duration, NaiveDateTime, SecondsFormat, Utc};
-use itoa::Buffer;
-use rand::rngs::StdRng;
-use rand::{RngCore, SeedableRng};
-use sqlx::{Executor, Pool, Postgres, query};
-use tokio::io::AsyncWriteExt;
+use std::collections::HashMap;
+
+use anyhow::{Context, bail};
+use chrono::{Duration, SecondsFormat, Utc};
+use sqlx::{Pool, Postgres, query, query_as, query_scalar};
 use tokio::try_join;
-use tokio::{io::BufWriter, process::Command};
 
-use crate::common::output::send_group;
-use crate::common::{env::Env, output::send_message, snowflake::next_id};
+use crate::common::{
+    config::Config,
+    integrity::{apply, drop_foreign_keys},
+    metadata_policy::MetadataPolicies,
+    output::send_message,
+    partitions::{ensure_range, is_partitioned},
+    schema::{create_indexes, drop_indexes},
+    seed_loader::{EventIds, load_events},
+    seed_profile::{EventGenerator, SeedProfile},
+    snowflake::next_id,
+    tenant::Tenant,
+};
+
+/// What to do when `events` already has rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedMode {
+    /// Refuse to seed over existing events.
+    Fresh,
+    /// Empty events, users and types first.
+    Truncate,
+    /// Keep existing rows; fixed event ids continue after the highest one.
+    Append,
+}
+
+pub async fn seed(
+    pool: Pool<Postgres>,
+    config: Config,
+    profile: SeedProfile,
+    mode: SeedMode,
+    tenant: Tenant,
+) -> Result<(), anyhow::Error> {
+    profile.validate()?;
+
+    // Snowflake ids differ on every run, so a fixed seed numbers rows instead.
+    let fixed_ids = profile.seed.is_some();
+    let profile = profile.resolved();
+
+    send_message(format!(
+        "Seed {} ending {}",
+        profile.seed.unwrap_or_default(),
+        profile
+            .end
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    ));
 
-pub async fn seed(pool: Pool<Postgres>, env: Env) {
     let start = Utc::now();
 
-    let (users_opt, types_opt) =
-        try_join!(create_users(&pool), create_types(&pool)).expect("Slucilos govno");
+    let existing = prepare(&pool, mode).await?;
 
-    let users_id = users_opt.expect("Slucilos strannoe govno (users_id is None)");
-    let types_id = types_opt.expect("Slucilos strannoe govno (types_id is None)");
+    let (users_id, types) = try_join!(
+        create_users(&pool, profile.users, fixed_ids, tenant),
+        create_types(&pool, profile.types, fixed_ids, tenant)
+    )?;
+    let (types_id, type_names): (Vec<i64>, Vec<String>) = types.into_iter().unzip();
 
     send_message("Users and types created".to_owned());
 
-    delete_indexes(&pool).await;
+    // Rows outside every range partition would all land in events_default.
+    if is_partitioned(&pool).await? {
+        let end = profile.end.unwrap_or_default();
+        let start = end - Duration::days(profile.days as i64);
+        let created = ensure_range(&pool, config.partitions.interval, start, end).await?;
+        send_message(format!("Partitions created: {}", created.len()));
+    }
+
+    drop_indexes(&pool).await?;
+    drop_foreign_keys(&pool).await?;
 
-    send_message("Indexes deleted".to_owned());
+    send_message("Indexes and foreign keys deleted".to_owned());
 
-    create_events(&pool, &env, users_id, types_id).await;
+    let ids = match fixed_ids {
+        true => EventIds::After(existing),
+        false => EventIds::Snowflake,
+    };
+    // Seeded metadata goes through the same policies as `POST /event`.
+    let generator = EventGenerator::create(&profile, users_id, types_id)?
+        .with_policies(MetadataPolicies::create(&config.metadata)?, type_names)?;
+    let loaded = load_events(&pool, &config, generator, ids, tenant).await;
 
-    send_message("Events created".to_owned());
+    if loaded.is_ok() {
+        send_message("Events created".to_owned());
 
-    let duration = Utc::now().signed_duration_since(start).num_seconds();
-    send_message(format!("─ Duration of seed {} seconds", duration));
+        let duration = Utc::now().signed_duration_since(start).num_seconds();
+        send_message(format!("─ Duration of seed {} seconds", duration));
+    }
 
-    create_indexes(&pool).await;
+    // Restore the indexes even when loading failed, so the API stays usable.
+    create_indexes(&pool).await?;
 
     send_message("Indexes restored".to_owned());
+
+    loaded?;
+
+    if config.integrity.foreign_keys {
+        apply(&pool, &config.integrity).await?;
+        send_message("Foreign keys validated".to_owned());
+    }
+
+    Ok(())
+}
+
+// Returns the highest event id left in place, 0 when `events` is empty.
+async fn prepare(pool: &Pool<Postgres>, mode: SeedMode) -> Result<i64, anyhow::Error> {
+    if mode == SeedMode::Truncate {
+        query("TRUNCATE events, users, event_types")
+            .execute(pool)
+            .await
+            .context("Cannot truncate events, users and event_types")?;
+        send_message("Tables truncated".to_owned());
+        return Ok(0);
+    }
+
+    let (count, max_id): (i64, Option<i64>) = query_as("SELECT count(*), max(id) FROM events")
+        .fetch_one(pool)
+        .await?;
+
+    if count > 0 && mode == SeedMode::Fresh {
+        bail!(
+            "events already has {} rows; pass --truncate to replace them or --append to add to them",
+            count
+        );
+    }
+
+    Ok(max_id.unwrap_or_default())
 }
 
-pub async fn create_users(pool: &Pool<Postgres>) -> Result<Option<Vec<i64>>, anyhow::Error> {
-    let names_array = vec!["Izya", "Kot", "Nikolayi", "Whiskey", "Michael"];
+pub async fn create_users(
+    pool: &Pool<Postgres>,
+    count: usize,
+    fixed_ids: bool,
+    tenant: Tenant,
+) -> Result<Vec<i64>, anyhow::Error> {
+    let names_array = ["Izya", "Kot", "Nikolayi", "Whiskey", "Michael"];
     let names_len = names_array.len();
 
-    let mut ids: Vec<i64> = Vec::with_capacity(100);
-    let mut names: Vec<String> = Vec::with_capacity(100);
-    (1..=1000).for_each(|i| {
-        let id = next_id();
+    let mut ids: Vec<i64> = Vec::with_capacity(count);
+    let mut names: Vec<String> = Vec::with_capacity(count);
+    (1..=count).for_each(|i| {
+        let id = row_id(i, fixed_ids);
         let name = names_array[i % names_len];
 
-        &ids.push(id);
-        &names.push(name.to_owned());
+        ids.push(id);
+        names.push(name.to_owned());
     });
 
+    ensure_owned(pool, "users", &ids, tenant).await?;
+
     query(
         r#"
-        INSERT INTO users (id, name)
-        SELECT * FROM UNNEST($1::bigint[], $2::text[])
+        INSERT INTO users (id, name, tenant_id)
+        SELECT id, name, $3 FROM UNNEST($1::bigint[], $2::text[]) AS seeded (id, name)
+        ON CONFLICT (id) DO NOTHING
         "#,
     )
-    .bind(ids.clone())
-    .bind(names.clone())
+    .bind(&ids)
+    .bind(&names)
+    .bind(tenant.0)
     .execute(pool)
-    .await?;
+    .await
+    .context("Cannot insert users")?;
+
+    // Live users keep their row and get more events; tombstoned ones, erased
+    // users among them, stay deleted and are left out.
+    let live: Vec<i64> = query_scalar(
+        "SELECT id FROM users WHERE id = ANY($1::bigint[]) AND deleted_at IS NULL ORDER BY id",
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await
+    .context("Cannot read users")?;
+
+    if live.len() < ids.len() {
+        send_message(format!(
+            "Users skipped, their ids belong to deleted users: {}",
+            ids.len() - live.len()
+        ));
+    }
+    if live.is_empty() {
+        bail!("Every seeded user id belongs to a deleted user");
+    }
 
-    Ok(Some(ids))
+    Ok(live)
 }
 
-pub async fn create_types(pool: &Pool<Postgres>) -> Result<Option<Vec<i64>>, anyhow::Error> {
-    let types_array = vec![
+/// Returns the id and name of every seeded type.
+pub async fn create_types(
+    pool: &Pool<Postgres>,
+    count: usize,
+    fixed_ids: bool,
+    tenant: Tenant,
+) -> Result<Vec<(i64, String)>, anyhow::Error> {
+    let types_array = [
         "user.registered",
         "user.login",
         "user.logout",
@@ -171,340 +300,100 @@ pub async fn create_types(pool: &Pool<Postgres>) -> Result<Option<Vec<i64>>, any
         "webhook.failed",
     ];
     let types_len = types_array.len();
+    if count > types_len {
+        bail!(
+            "At most {} event types can be seeded, got {}",
+            types_len,
+            count
+        );
+    }
 
-    let mut ids: Vec<i64> = Vec::with_capacity(types_len);
-    let mut names: Vec<String> = Vec::with_capacity(types_len);
-    (1..=types_len).for_each(|i| {
-        let id = next_id();
-        let name = types_array[i % types_len];
+    let mut ids: Vec<i64> = Vec::with_capacity(count);
+    let mut names: Vec<String> = Vec::with_capacity(count);
+    (1..=count).for_each(|i| {
+        let id = row_id(i, fixed_ids);
+        let name = types_array[i - 1];
 
-        &ids.push(id);
-        &names.push(name.to_owned());
+        ids.push(id);
+        names.push(name.to_owned());
     });
 
+    ensure_owned(pool, "event_types", &ids, tenant).await?;
+
+    // Existing types keep their id, so re-runs reference the same rows. A name
+    // whose id another type of the tenant holds is reported below.
     query(
         r#"
-        INSERT INTO event_types (id, name)
-        SELECT * FROM UNNEST($1::bigint[], $2::text[])
+        INSERT INTO event_types (id, name, tenant_id)
+        SELECT id, name, $3 FROM UNNEST($1::bigint[], $2::text[]) AS seeded (id, name)
+        ON CONFLICT DO NOTHING
         "#,
     )
-    .bind(ids.clone())
-    .bind(names.clone())
+    .bind(&ids)
+    .bind(&names)
+    .bind(tenant.0)
     .execute(pool)
-    .await?;
+    .await
+    .context("Cannot insert event types")?;
 
-    Ok(Some(ids))
+    let rows: Vec<(i64, String)> = query_as(
+        r#"
+        UPDATE event_types SET deleted_at = NULL
+        WHERE tenant_id = $1 AND name = ANY($2::text[])
+        RETURNING id, name
+        "#,
+    )
+    .bind(tenant.0)
+    .bind(&names)
+    .fetch_all(pool)
+    .await
+    .context("Cannot read event types")?;
+
+    let stored: HashMap<String, i64> = rows.into_iter().map(|(id, name)| (name, id)).collect();
+
+    names
+        .into_iter()
+        .map(|name| match stored.get(&name) {
+            Some(id) => Ok((*id, name)),
+            None => Err(anyhow::Error::msg(format!(
+                "Event type {} was not stored, another type of tenant {} has its id",
+                name, tenant.0
+            ))),
+        })
+        .collect()
 }
 
-pub async fn create_events(
+// Fixed ids are the same for every tenant, so one may already be another
+// tenant's row; seeding must not hand it events of this one.
+async fn ensure_owned(
     pool: &Pool<Postgres>,
-    env: &Env,
-    users_id: Vec<i64>,
-    types_id: Vec<i64>,
+    table: &str,
+    ids: &[i64],
+    tenant: Tenant,
 ) -> Result<(), anyhow::Error> {
-    let pages: Vec<&str> = vec![
-        "{\"page\":\"/registration\"}",
-        "{\"page\":\"/login\"}",
-        "{\"page\":\"/logout\"}",
-        "{\"page\":\"/profile/edit\"}",
-        "{\"page\":\"/order/create\"}",
-        "{\"page\":\"/order/confirm\"}",
-        "{\"page\":\"/order/shipped\"}",
-        "{\"page\":\"/order/tracking\"}",
-        "{\"page\":\"/payment/complete\"}",
-        "{\"page\":\"/payment/failed\"}",
-        "{\"page\":\"/payment/refund\"}",
-        "{\"page\":\"/product/view\"}",
-        "{\"page\":\"/cart/add\"}",
-        "{\"page\":\"/cart/remove\"}",
-        "{\"page\":\"/emails/sent\"}",
-        "{\"page\":\"/emails/opened\"}",
-        "{\"page\":\"/emails/click\"}",
-        "{\"page\":\"/notifications/sent\"}",
-        "{\"page\":\"/notifications/read\"}",
-        "{\"page\":\"/api/request\"}",
-        "{\"page\":\"/api/response\"}",
-        "{\"page\":\"/api/error\"}",
-        "{\"page\":\"/password/reset\"}",
-        "{\"page\":\"/password/change\"}",
-        "{\"page\":\"/security/2fa\"}",
-        "{\"page\":\"/security/2fa/disable\"}",
-        "{\"page\":\"/account/delete\"}",
-        "{\"page\":\"/account/suspend\"}",
-        "{\"page\":\"/account/reactivate\"}",
-        "{\"page\":\"/subscription/start\"}",
-        "{\"page\":\"/subscription/cancel\"}",
-        "{\"page\":\"/subscription/renew\"}",
-        "{\"page\":\"/invite/send\"}",
-        "{\"page\":\"/invite/accept\"}",
-        "{\"page\":\"/feedback\"}",
-        "{\"page\":\"/profile/avatar\"}",
-        "{\"page\":\"/profile/preferences\"}",
-        "{\"page\":\"/email/verify\"}",
-        "{\"page\":\"/login/failed\"}",
-        "{\"page\":\"/profile/view\"}",
-        "{\"page\":\"/profile/notifications\"}",
-        "{\"page\":\"/newsletter/subscribe\"}",
-        "{\"page\":\"/order/cancel\"}",
-        "{\"page\":\"/order/return\"}",
-        "{\"page\":\"/order/return/approved\"}",
-        "{\"page\":\"/order/return/rejected\"}",
-        "{\"page\":\"/order/review\"}",
-        "{\"page\":\"/order/invoice\"}",
-        "{\"page\":\"/payment/pending\"}",
-        "{\"page\":\"/payment/dispute\"}",
-        "{\"page\":\"/payment/settled\"}",
-        "{\"page\":\"/cart/view\"}",
-        "{\"page\":\"/cart/update\"}",
-        "{\"page\":\"/cart/clear\"}",
-        "{\"page\":\"/checkout/start\"}",
-        "{\"page\":\"/checkout/complete\"}",
-        "{\"page\":\"/product/review\"}",
-        "{\"page\":\"/wishlist/add\"}",
-        "{\"page\":\"/wishlist/remove\"}",
-        "{\"page\":\"/product/compare\"}",
-        "{\"page\":\"/product/share\"}",
-        "{\"page\":\"/product/restock\"}",
-        "{\"page\":\"/product/stock\"}",
-        "{\"page\":\"/emails/bounced\"}",
-        "{\"page\":\"/emails/unsubscribe\"}",
-        "{\"page\":\"/notifications/dismiss\"}",
-        "{\"page\":\"/notifications/failure\"}",
-        "{\"page\":\"/session/start\"}",
-        "{\"page\":\"/session/expired\"}",
-        "{\"page\":\"/session/end\"}",
-        "{\"page\":\"/admin/login\"}",
-        "{\"page\":\"/admin/logout\"}",
-        "{\"page\":\"/admin/user/edit\"}",
-        "{\"page\":\"/admin/user/delete\"}",
-        "{\"page\":\"/admin/reports\"}",
-        "{\"page\":\"/admin/settings\"}",
-        "{\"page\":\"/files/upload\"}",
-        "{\"page\":\"/files/delete\"}",
-        "{\"page\":\"/files/download\"}",
-        "{\"page\":\"/files/preview\"}",
-        "{\"page\":\"/support/create\"}",
-        "{\"page\":\"/support/close\"}",
-        "{\"page\":\"/support/reopen\"}",
-        "{\"page\":\"/support/message\"}",
-        "{\"page\":\"/support/rating\"}",
-        "{\"page\":\"/search\"}",
-        "{\"page\":\"/search/filter\"}",
-        "{\"page\":\"/search/sort\"}",
-        "{\"page\":\"/settings\"}",
-        "{\"page\":\"/settings/language\"}",
-        "{\"page\":\"/settings/timezone\"}",
-        "{\"page\":\"/api/token\"}",
-        "{\"page\":\"/api/token/revoke\"}",
-        "{\"page\":\"/api/rate-limit\"}",
-        "{\"page\":\"/cron/start\"}",
-        "{\"page\":\"/cron/end\"}",
-        "{\"page\":\"/cron/failure\"}",
-        "{\"page\":\"/webhooks/incoming\"}",
-        "{\"page\":\"/webhooks/verified\"}",
-        "{\"page\":\"/webhooks/failure\"}",
-    ];
-
-    let byte_users_id: Vec<Vec<u8>> = users_id
-        .iter()
-        .map(|id| id.to_string().into_bytes())
-        .collect();
-
-    let byte_types_id: Vec<Vec<u8>> = types_id
-        .iter()
-        .map(|a| a.to_string().into_bytes())
-        .collect();
-
-    let byte_pages: Vec<Vec<u8>> = pages
-        .iter()
-        .map(|raw| {
-            let escaped = raw.replace('"', "\"\"");
-            let field = format!("\"{}\"", escaped);
-            field.into_bytes()
-        })
-        .collect();
-
-    let database = env.postgres_database.clone();
-    let host = env.postgres_host.clone();
-    let port = env.postgres_port.clone().to_string();
-    let user = env.postgres_user.clone();
-    let password = env.postgres_password.clone();
-
-    let mut child = Command::new("pg_bulkload")
-        .arg("-d")
-        .arg(database)
-        .arg("-h")
-        .arg(host)
-        .arg("-p")
-        .arg(port)
-        .arg("-U")
-        .arg(user)
-        .env("PGPASSWORD", password)
-        .arg("events.ctl")
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::inherit())
-        .stderr(std::process::Stdio::inherit())
-        .spawn()
-        .expect("failed to spawn pg_bulkload");
-
-    let mut writer = BufWriter::with_capacity(
-        16 * 1024 * 1024,
-        child.stdin.take().expect("stdin not piped"),
-    );
-
-    let mut chunk = Vec::<u8>::with_capacity(12 * 1024 * 1024);
-    let chunk_mb: usize = 8 * 1024 * 1024;
-
-    let mut key = 0;
-    let users_len = byte_users_id.len();
-    let types_len = byte_types_id.len();
-    let metadata_len = byte_pages.len();
-
-    let now = Utc::now();
-    let one_year_ago = now - Duration::days(365);
-    let start_ts = one_year_ago.timestamp();
-    let end_ts = now.timestamp();
-
-    let mut rng = StdRng::from_os_rng();
-
-    let mut id_buf = Buffer::new();
-
-    for _ in 1..=10_000_000 {
-        let mut line = [0u8; 128];
-        let mut pos = 0;
-
-        let user_idx = key % users_len;
-        let type_idx = key % types_len;
-        let metadata_idx = key % metadata_len;
-        let user_bytes = &byte_users_id[user_idx];
-        let type_bytes = &byte_types_id[type_idx];
-        let metadata_bytes = &byte_pages[metadata_idx];
-
-        let ts_bytes = rand_timestamp(&mut rng, start_ts, end_ts)
-            .to_rfc3339_opts(SecondsFormat::Secs, true)
-            .into_bytes();
-
-        key += 1;
-
-        let id = next_id();
-
-        let bytes_id = id_buf.format(id).as_bytes();
-
-        macro_rules! push_slice {
-            ($src:expr) => {{
-                let s: &[u8] = $src;
-                let len = s.len();
-                line[pos..pos + len].copy_from_slice(s);
-                pos += len;
-            }};
-        }
-
-        push_slice!(bytes_id);
-        line[pos] = b',';
-        pos += 1;
-
-        push_slice!(&user_bytes);
-        line[pos] = b',';
-        pos += 1;
-
-        push_slice!(&type_bytes);
-        line[pos] = b',';
-        pos += 1;
-
-        push_slice!(&ts_bytes);
-        line[pos] = b',';
-        pos += 1;
-
-        push_slice!(&metadata_bytes);
-        line[pos] = b'\n';
-        pos += 1;
-
-        chunk.extend_from_slice(&line[..pos]);
-
-        if chunk.len() >= chunk_mb {
-            writer.write_all(&chunk).await.expect("Failed to Write");
-            chunk.clear();
-        }
-    }
-
-    if !chunk.is_empty() {
-        writer.write_all(&chunk).await.expect("Failed to Write");
-    }
-    writer.flush().await.expect("Failed to flush");
-    drop(writer);
-
-    let status = child
-        .wait()
-        .await
-        .expect("pg_bulkload process failed to run");
-    if !status.success() {
-        send_group(format!("pg_bulkload exit with {:?}", status.code()));
+    let foreign: Option<(i64, i64)> = query_as(&format!(
+        "SELECT id, tenant_id FROM {} WHERE id = ANY($1::bigint[]) AND tenant_id <> $2 ORDER BY id LIMIT 1",
+        table
+    ))
+    .bind(ids)
+    .bind(tenant.0)
+    .fetch_optional(pool)
+    .await
+    .with_context(|| format!("Cannot read {}", table))?;
+
+    if let Some((id, owner)) = foreign {
+        bail!(
+            "{} id {} belongs to tenant {}, not {}; seed that tenant or drop the fixed seed",
+            table,
+            id,
+            owner,
+            tenant.0
+        );
     }
 
     Ok(())
 }
 
-pub async fn create_indexes(pool: &Pool<Postgres>) {
-    match pool
-        .execute(
-            r#"
-    CREATE INDEX IF NOT EXISTS idx_events_count
-        ON events USING btree (id);
-    
-    CREATE INDEX IF NOT EXISTS idx_events_user_timestamp
-        ON events USING btree (user_id, timestamp DESC);
-    
-    CREATE INDEX IF NOT EXISTS idx_events_timestamp_desc
-        ON events USING btree (timestamp DESC);
-    
-    CREATE INDEX IF NOT EXISTS idx_events_type_timestamp
-        ON events USING btree (type_id, timestamp DESC);
-    
-    CREATE INDEX IF NOT EXISTS idx_events_stats
-        ON events USING btree (user_id, (metadata->>'page'), type_id);
-    
-    CREATE INDEX IF NOT EXISTS idx_events_covering
-        ON events USING btree (user_id, type_id, timestamp DESC)
-        INCLUDE (id, metadata);
-        "#,
-        )
-        .await
-    {
-        Ok(_) => {}
-        Err(e) => {
-            panic!("{}", e)
-        }
-    };
-}
-
-pub async fn delete_indexes(pool: &Pool<Postgres>) {
-    match pool
-        .execute(
-            r#"
-DROP INDEX IF EXISTS idx_events_count;
-
-DROP INDEX IF EXISTS idx_events_user_timestamp;
-
-DROP INDEX IF EXISTS idx_events_timestamp_desc;
-
-DROP INDEX IF EXISTS idx_events_type_timestamp;
-
-DROP INDEX IF EXISTS idx_events_stats;
-
-DROP INDEX IF EXISTS idx_events_covering;
-    "#,
-        )
-        .await
-    {
-        Ok(_) => {}
-        Err(e) => {
-            panic!("{}", e)
-        }
-    };
-}
-
-fn rand_timestamp(rng: &mut impl RngCore, start_ts: i64, end_ts: i64) -> DateTime<Utc> {
-    let range = (end_ts - start_ts + 1) as u32;
-    let sec = (rng.next_u32() % range) as i64 + start_ts;
-    DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(sec, 0), Utc)
+fn row_id(index: usize, fixed: bool) -> i64 {
+    if fixed { index as i64 } else { next_id() }
 }
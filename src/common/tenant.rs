@@ -0,0 +1,81 @@
+use std::future::{Ready, ready};
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload, http::header::HeaderValue};
+
+use crate::common::error::AppError;
+
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+/// Tenant of requests that name none and of data written before tenants.
+pub const DEFAULT_TENANT: i64 = 0;
+
+/// The tenant a request acts for, kept in the request extensions by
+/// `authenticate`. Every query and cache key of the events context is scoped
+/// by it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Tenant(pub i64);
+
+impl Default for Tenant {
+    fn default() -> Self {
+        Tenant(DEFAULT_TENANT)
+    }
+}
+
+impl Tenant {
+    /// A key bound to a tenant decides it; the header may only repeat it.
+    /// Otherwise the header decides, and without one the default tenant.
+    pub fn resolve(header: Option<&HeaderValue>, bound: Option<i64>) -> Result<Tenant, AppError> {
+        let requested = header
+            .map(|value| {
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse::<i64>().ok())
+                    .filter(|tenant| *tenant >= 0)
+                    .ok_or(AppError::Validation {
+                        field: "X-Tenant-Id",
+                        message: "`X-Tenant-Id` must be a non-negative integer",
+                    })
+            })
+            .transpose()?;
+
+        match (bound, requested) {
+            (Some(bound), Some(requested)) if bound != requested => Err(AppError::WrongTenant),
+            (Some(tenant), _) | (None, Some(tenant)) => Ok(Tenant(tenant)),
+            (None, None) => Ok(Tenant::default()),
+        }
+    }
+
+    /// `total_events`, `event_types` and `users_id` of this tenant.
+    pub fn key(self, family: &str) -> String {
+        format!("{}_{}", family, self.0)
+    }
+
+    pub fn user_events_key(self, user_id: i64) -> String {
+        format!("user_events_{}_{}", self.0, user_id)
+    }
+
+    /// Tag of the cached stats, filled with from, to and the type id.
+    pub fn stats_pattern(self) -> String {
+        format!("events_stat_{}_{{}}_{{}}_{{}}", self.0)
+    }
+
+    /// Tag of the cached pages, filled with page and limit.
+    pub fn page_pattern(self) -> String {
+        format!("page_{}_{{}}_{{}}", self.0)
+    }
+}
+
+impl FromRequest for Tenant {
+    type Error = AppError;
+    type Future = Ready<Result<Tenant, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Tenant>()
+                .copied()
+                .ok_or_else(|| AppError::Internal(anyhow::Error::msg("Tenant is not resolved"))),
+        )
+    }
+}
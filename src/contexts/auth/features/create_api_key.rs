@@ -0,0 +1,80 @@
+use actix_web::{HttpResponse, post, web};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    common::{
+        auth::Scope,
+        error::{AppError, ErrorBody},
+    },
+    contexts::auth::infrastructure::{api_keys::ApiKeys, api_keys_repo::ApiKeyRow},
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_api_key);
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateKeyRequest {
+    name: String,
+    /// `events:write`, `events:read`, `stats:read` or `admin`
+    #[schema(example = json!(["events:write"]))]
+    scopes: Vec<String>,
+    /// Binds the key to this tenant
+    #[serde(default)]
+    tenant_id: Option<i64>,
+}
+
+/// A key with its secret, answered once by create and rotate.
+#[derive(Serialize, ToSchema)]
+pub struct IssuedKey {
+    #[serde(flatten)]
+    pub row: ApiKeyRow,
+    pub key: String,
+}
+
+#[utoipa::path(
+    tag = "auth",
+    request_body = CreateKeyRequest,
+    responses(
+        (status = 201, description = "The key; its secret is never shown again", body = IssuedKey),
+        (status = 400, description = "Invalid name, scopes or tenant", body = ErrorBody),
+    )
+)]
+#[post("/admin/keys")]
+pub async fn create_api_key(
+    body: web::Json<CreateKeyRequest>,
+    keys: web::Data<ApiKeys>,
+) -> Result<HttpResponse, AppError> {
+    if body.name.trim().is_empty() {
+        return Err(AppError::Validation {
+            field: "name",
+            message: "`name` must not be empty",
+        });
+    }
+
+    let scopes = body
+        .scopes
+        .iter()
+        .map(|scope| scope.parse::<Scope>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|scopes| !scopes.is_empty())
+        .ok_or(AppError::Validation {
+            field: "scopes",
+            message: "`scopes` must list events:write, events:read, stats:read or admin",
+        })?;
+
+    if body.tenant_id.is_some_and(|tenant| tenant < 0) {
+        return Err(AppError::Validation {
+            field: "tenant_id",
+            message: "`tenant_id` must be a non-negative integer",
+        });
+    }
+
+    let (row, key) = keys
+        .issue(body.name.trim(), &scopes, body.tenant_id)
+        .await?;
+
+    Ok(HttpResponse::Created().json(IssuedKey { row, key }))
+}
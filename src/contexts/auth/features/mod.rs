@@ -0,0 +1,13 @@
+use actix_web::web;
+
+pub mod create_api_key;
+pub mod read_api_keys;
+pub mod revoke_api_key;
+pub mod rotate_api_key;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.configure(create_api_key::configure);
+    cfg.configure(read_api_keys::configure);
+    cfg.configure(revoke_api_key::configure);
+    cfg.configure(rotate_api_key::configure);
+}
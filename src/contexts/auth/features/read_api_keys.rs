@@ -0,0 +1,19 @@
+use actix_web::{HttpResponse, get, web};
+
+use crate::{
+    common::error::AppError,
+    contexts::auth::infrastructure::{api_keys::ApiKeys, api_keys_repo::ApiKeyRow},
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(read_api_keys);
+}
+
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, description = "Every key, revoked ones included", body = Vec<ApiKeyRow>))
+)]
+#[get("/admin/keys")]
+pub async fn read_api_keys(keys: web::Data<ApiKeys>) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(keys.list().await?))
+}
@@ -0,0 +1,38 @@
+use actix_web::{HttpResponse, delete, web};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    common::error::{AppError, ErrorBody},
+    contexts::auth::infrastructure::api_keys::ApiKeys,
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(revoke_api_key);
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct KeyPath {
+    key_id: i64,
+}
+
+#[utoipa::path(
+    tag = "auth",
+    params(KeyPath),
+    responses(
+        (status = 204, description = "Revoked"),
+        (status = 404, description = "Unknown key", body = ErrorBody),
+    )
+)]
+#[delete("/admin/keys/{key_id}")]
+pub async fn revoke_api_key(
+    path: web::Path<KeyPath>,
+    keys: web::Data<ApiKeys>,
+) -> Result<HttpResponse, AppError> {
+    if !keys.revoke(path.key_id).await? {
+        return Err(AppError::KeyNotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
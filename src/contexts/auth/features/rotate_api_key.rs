@@ -0,0 +1,39 @@
+use actix_web::{HttpResponse, post, web};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    common::error::{AppError, ErrorBody},
+    contexts::auth::{features::create_api_key::IssuedKey, infrastructure::api_keys::ApiKeys},
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(rotate_api_key);
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct KeyPath {
+    key_id: i64,
+}
+
+#[utoipa::path(
+    tag = "auth",
+    params(KeyPath),
+    responses(
+        (status = 200, description = "The key with its new secret", body = IssuedKey),
+        (status = 404, description = "Unknown or revoked key", body = ErrorBody),
+    )
+)]
+#[post("/admin/keys/{key_id}/rotate")]
+pub async fn rotate_api_key(
+    path: web::Path<KeyPath>,
+    keys: web::Data<ApiKeys>,
+) -> Result<HttpResponse, AppError> {
+    let (row, key) = keys
+        .rotate(path.key_id)
+        .await?
+        .ok_or(AppError::KeyNotFound)?;
+
+    Ok(HttpResponse::Ok().json(IssuedKey { row, key }))
+}
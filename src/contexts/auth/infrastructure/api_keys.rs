@@ -0,0 +1,125 @@
+use simd_json::{from_slice, to_vec};
+
+use crate::{
+    common::{
+        auth::{AuthenticatedKey, Scope, display_prefix, generate_key, hash_key},
+        cache::{CacheDeleteKey, CacheSetKey, LeveledCache},
+        config::AuthConfig,
+        error::AppError,
+    },
+    contexts::auth::infrastructure::api_keys_repo::{ApiKeyRow, ApiKeysRepo},
+};
+
+/// Key lookups through the cache, and the key lifecycle.
+#[derive(Clone)]
+pub struct ApiKeys {
+    repo: ApiKeysRepo,
+    cache: LeveledCache,
+    config: AuthConfig,
+}
+
+impl ApiKeys {
+    pub fn create(repo: ApiKeysRepo, cache: LeveledCache, config: AuthConfig) -> ApiKeys {
+        ApiKeys {
+            repo,
+            cache,
+            config,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The key as cached by an earlier lookup, without touching Postgres.
+    pub async fn cached(&self, key: &str) -> Option<AuthenticatedKey> {
+        let mut bytes = self
+            .cache
+            .try_get(format!("api_key_{}", hash_key(key)))
+            .await?;
+        from_slice::<AuthenticatedKey>(&mut bytes).ok()
+    }
+
+    /// Looks the key up in Postgres and caches what it finds.
+    pub async fn lookup(&self, key: &str) -> Result<Option<AuthenticatedKey>, AppError> {
+        let hash = hash_key(key);
+        let cache = format!("api_key_{}", hash);
+
+        let Some(row) = self.repo.find_by_hash(&hash).await? else {
+            return Ok(None);
+        };
+        let key = AuthenticatedKey {
+            id: row.id,
+            scopes: parse_scopes(&row.scopes)?,
+            tenant_id: row.tenant_id,
+        };
+
+        let _ = self
+            .cache
+            .save(
+                CacheSetKey::Exact(cache),
+                to_vec(&key)?,
+                self.config.cache_ttl,
+            )
+            .await;
+
+        Ok(Some(key))
+    }
+
+    /// Stores a new key and returns it; only its hash is kept. A key given a
+    /// tenant only ever acts for it.
+    pub async fn issue(
+        &self,
+        name: &str,
+        scopes: &[Scope],
+        tenant_id: Option<i64>,
+    ) -> Result<(ApiKeyRow, String), anyhow::Error> {
+        let (key, hash) = generate_key();
+        let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+
+        let row = self
+            .repo
+            .insert(name, &display_prefix(&key), &hash, &scopes, tenant_id)
+            .await?;
+        Ok((row, key))
+    }
+
+    /// Replaces the key; the old one keeps working for
+    /// `auth.rotation_grace_secs`.
+    pub async fn rotate(&self, id: i64) -> Result<Option<(ApiKeyRow, String)>, anyhow::Error> {
+        let (key, hash) = generate_key();
+
+        let row = self
+            .repo
+            .rotate(
+                id,
+                &display_prefix(&key),
+                &hash,
+                self.config.rotation_grace_secs as i64,
+            )
+            .await?;
+        Ok(row.map(|row| (row, key)))
+    }
+
+    pub async fn revoke(&self, id: i64) -> Result<bool, anyhow::Error> {
+        let Some(hashes) = self.repo.revoke(id).await? else {
+            return Ok(false);
+        };
+
+        for hash in std::iter::once(hashes.hash).chain(hashes.previous_hash) {
+            let _ = self
+                .cache
+                .invalidate(CacheDeleteKey::Exact(format!("api_key_{}", hash)))
+                .await;
+        }
+        Ok(true)
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKeyRow>, anyhow::Error> {
+        self.repo.list().await
+    }
+}
+
+pub fn parse_scopes(scopes: &[String]) -> Result<Vec<Scope>, anyhow::Error> {
+    scopes.iter().map(|scope| scope.parse()).collect()
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use sqlx::{Pool, Postgres, query_as};
+use utoipa::ToSchema;
+
+use crate::common::snowflake::next_id;
+
+/// An API key as listed; the key itself is only ever shown once.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ApiKeyRow {
+    #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub tenant_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// The hashes a key answers to, for dropping cached lookups.
+pub struct KeyHashes {
+    pub hash: String,
+    pub previous_hash: Option<String>,
+}
+
+fn i64_to_string<S>(x: &i64, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&x.to_string())
+}
+
+#[derive(Clone)]
+pub struct ApiKeysRepo {
+    postgres: Pool<Postgres>,
+}
+
+impl ApiKeysRepo {
+    pub fn create(postgres: Pool<Postgres>) -> ApiKeysRepo {
+        ApiKeysRepo { postgres }
+    }
+
+    pub async fn insert(
+        &self,
+        name: &str,
+        prefix: &str,
+        hash: &str,
+        scopes: &[String],
+        tenant_id: Option<i64>,
+    ) -> Result<ApiKeyRow, anyhow::Error> {
+        let row = query_as!(
+            ApiKeyRow,
+            r#"INSERT INTO api_keys (id, name, prefix, hash, scopes, tenant_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, prefix, scopes, tenant_id, created_at, rotated_at, revoked_at"#,
+            next_id(),
+            name,
+            prefix,
+            hash,
+            scopes,
+            tenant_id
+        )
+        .fetch_one(&self.postgres)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// The live key with this hash, including one rotated away from within
+    /// its grace period.
+    pub async fn find_by_hash(&self, hash: &str) -> Result<Option<ApiKeyRow>, anyhow::Error> {
+        let row = query_as!(
+            ApiKeyRow,
+            r#"SELECT id, name, prefix, scopes, tenant_id, created_at, rotated_at, revoked_at
+            FROM api_keys
+            WHERE revoked_at IS NULL
+              AND (hash = $1 OR (previous_hash = $1 AND previous_expires_at > now()))"#,
+            hash
+        )
+        .fetch_optional(&self.postgres)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKeyRow>, anyhow::Error> {
+        let rows = query_as!(
+            ApiKeyRow,
+            r#"SELECT id, name, prefix, scopes, tenant_id, created_at, rotated_at, revoked_at
+            FROM api_keys
+            ORDER BY created_at"#
+        )
+        .fetch_all(&self.postgres)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Swaps in a new hash; the old one keeps working for `grace_secs`.
+    pub async fn rotate(
+        &self,
+        id: i64,
+        prefix: &str,
+        hash: &str,
+        grace_secs: i64,
+    ) -> Result<Option<ApiKeyRow>, anyhow::Error> {
+        let row = query_as!(
+            ApiKeyRow,
+            r#"UPDATE api_keys
+            SET previous_hash = hash,
+                previous_expires_at = now() + make_interval(secs => $4),
+                prefix = $2,
+                hash = $3,
+                rotated_at = now()
+            WHERE id = $1 AND revoked_at IS NULL
+            RETURNING id, name, prefix, scopes, tenant_id, created_at, rotated_at, revoked_at"#,
+            id,
+            prefix,
+            hash,
+            grace_secs as f64
+        )
+        .fetch_optional(&self.postgres)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn revoke(&self, id: i64) -> Result<Option<KeyHashes>, anyhow::Error> {
+        let hashes = query_as!(
+            KeyHashes,
+            r#"UPDATE api_keys
+            SET revoked_at = COALESCE(revoked_at, now())
+            WHERE id = $1
+            RETURNING hash, previous_hash"#,
+            id
+        )
+        .fetch_optional(&self.postgres)
+        .await?;
+
+        Ok(hashes)
+    }
+}
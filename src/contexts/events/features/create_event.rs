@@ -1,145 +1,129 @@
-use std::{any::Any, sync::Arc};
+use std::{any::Any, collections::BTreeSet, sync::Arc};
 
-use actix_web::{HttpResponse, Responder, post, web};
+use actix_web::{HttpRequest, HttpResponse, post, web};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use serde_json::json;
 use simd_json::{
     BorrowedValue,
     base::{ValueAsObject, ValueAsScalar},
     derived::ValueObjectAccess,
-    to_borrowed_value, to_vec,
+    from_slice, to_borrowed_value, to_vec,
 };
 use sqlx::Row;
 use sqlx::types::JsonValue;
-use tokio::{sync::RwLock, try_join};
+use tokio::try_join;
+use utoipa::ToSchema;
 
 use crate::{
     common::{
-        cache::LeveledCache,
+        cache::{CacheDeleteKey, CacheSetKey, LeveledCache},
         command_bus::{CommandBus, CommandValue},
+        error::{AppError, ErrorBody},
+        metadata_policy::MetadataPolicies,
+        rate_limit::limit_user,
         snowflake::next_id,
+        tenant::Tenant,
     },
     contexts::events::{
         features::functions_php::{get_type, is_user_exist},
-        infrastructure::cached_projection::EventsProj,
+        infrastructure::{cached_projection::EventsProj, repo::Event},
     },
 };
 
-#[derive(Clone)]
-struct CreateEventRequest {
+#[derive(Clone, ToSchema)]
+pub struct CreateEventRequest {
     user_id: i64,
+    #[schema(example = "user.updated")]
     event_type: String,
     timestamp: DateTime<Utc>,
+    /// `page` is required; the rest passes through the type's metadata policy.
+    #[schema(value_type = Object, example = json!({"page": "/home"}))]
     metadata: JsonValue,
 }
 
-#[derive(Serialize)]
-pub struct HttpError {
-    pub error: String,
-}
-
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(create_event);
 }
 
+#[utoipa::path(
+    tag = "events",
+    request_body = CreateEventRequest,
+    responses(
+        (status = 200, description = "The event, queued for insertion", body = Event),
+        (status = 400, description = "Invalid JSON, a failed validation, or an unknown user or event type", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    )
+)]
 #[post("/event")]
 pub async fn create_event(
+    req: HttpRequest,
+    tenant: Tenant,
     body: Bytes,
     proj: web::Data<EventsProj>,
     bus: web::Data<Arc<CommandBus>>,
-    cache: web::Data<Arc<RwLock<LeveledCache>>>,
-) -> impl Responder {
+    cache: web::Data<LeveledCache>,
+    policies: web::Data<MetadataPolicies>,
+) -> Result<HttpResponse, AppError> {
     let mut buf = body.to_vec();
 
-    let raw_json: BorrowedValue = match to_borrowed_value(&mut buf) {
-        Ok(value) => value,
-        Err(_) => {
-            return HttpResponse::BadRequest()
-                .content_type("application/json")
-                .body("Some");
-        }
-    };
+    let raw_json: BorrowedValue = to_borrowed_value(&mut buf).map_err(|_| AppError::InvalidJson)?;
 
-    let request = match validate_request(&raw_json) {
-        Ok(dto) => dto,
-        Err(resp) => return resp,
-    };
+    let mut request = validate_request(&raw_json)?;
+    tracing::Span::current().record("user_id", request.user_id);
+    limit_user(&req, request.user_id).await?;
 
-    let type_id = match try_join!(
-        is_user_exist(&proj, request.user_id),
-        get_type(&proj, request.event_type.as_str())
-    ) {
-        Ok((user_exist, type_id)) => {
-            if !user_exist {
-                return HttpResponse::BadRequest()
-                    .content_type("application/json")
-                    .body("{\"error\":\"User not exist\"}");
-            }
+    if let JsonValue::Object(fields) = request.metadata {
+        request.metadata = JsonValue::Object(policies.apply(&request.event_type, fields));
+    }
 
-            if type_id.is_none() {
-                return HttpResponse::BadRequest()
-                    .content_type("application/json")
-                    .body("{\"error\":\"Type not exist\"}");
-            }
+    let (user_exist, type_id) = try_join!(
+        is_user_exist(&proj, tenant, request.user_id),
+        get_type(&proj, tenant, request.event_type.as_str())
+    )?;
+
+    if !user_exist {
+        return Err(AppError::UserNotFound);
+    }
 
-            type_id.unwrap()
-        }
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .content_type("application/json")
-                .body(format!("{{\"error\":\"Database error: {}\"}}", e));
-        }
-    };
+    let type_id = type_id.ok_or(AppError::EventTypeNotFound)?;
 
     let id = next_id();
 
-    match insert_to_command_bus(
+    insert_to_command_bus(
         id,
+        tenant,
         type_id,
         request.clone(),
         bus.get_ref(),
         cache.get_ref(),
         proj.get_ref(),
     )
-    .await
-    {
-        Ok(_) => {}
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Failed insert to bus: {}", e));
-        }
-    };
+    .await?;
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(format!(
             "{{\"id\":\"{}\",\"user_id\":\"{}\",\"type_id\":\"{}\",\"timestamp\":\"{}\",\"metadata\":{}}}",
             id, &request.user_id, type_id, &request.timestamp, &request.metadata
-        ))
+        )))
 }
 
-fn validate_request<'a>(
-    raw_json: &'a BorrowedValue<'a>,
-) -> Result<CreateEventRequest, HttpResponse> {
+fn validate_request<'a>(raw_json: &'a BorrowedValue<'a>) -> Result<CreateEventRequest, AppError> {
     let user_id = raw_json
         .get("user_id")
         .and_then(|v| v.as_i64())
-        .ok_or_else(|| {
-            HttpResponse::BadRequest().json(HttpError {
-                error: "`user_id` missing or not a i64".to_owned(),
-            })
+        .ok_or(AppError::Validation {
+            field: "user_id",
+            message: "`user_id` missing or not a i64",
         })?;
 
     let event_type = raw_json
         .get("event_type")
         .and_then(|v| v.as_str())
         .filter(|s| !s.is_empty())
-        .ok_or_else(|| {
-            HttpResponse::BadRequest().json(HttpError {
-                error: "`event_type` missing or empty".to_owned(),
-            })
+        .ok_or(AppError::Validation {
+            field: "event_type",
+            message: "`event_type` missing or empty",
         })?;
 
     let timestamp = raw_json
@@ -147,24 +131,22 @@ fn validate_request<'a>(
         .and_then(|v| v.as_str())
         .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
         .map(|dt| dt.with_timezone(&Utc))
-        .ok_or_else(|| {
-            HttpResponse::BadRequest().json(HttpError {
-                error: "`timestamp` missing or invalid".to_owned(),
-            })
+        .ok_or(AppError::Validation {
+            field: "timestamp",
+            message: "`timestamp` missing or invalid",
         })?;
 
-    let page = raw_json
-        .get("metadata")
-        .and_then(BorrowedValue::as_object)
+    let metadata = raw_json.get("metadata").and_then(BorrowedValue::as_object);
+
+    metadata
         .and_then(|m| m.get("page"))
         .and_then(BorrowedValue::as_str)
-        .ok_or_else(|| {
-            HttpResponse::BadRequest().json(HttpError {
-                error: "`metadata.page` is required and must be a string".to_owned(),
-            })
+        .ok_or(AppError::Validation {
+            field: "metadata.page",
+            message: "`metadata.page` is required and must be a string",
         })?;
 
-    let metadata = json!({"page": page});
+    let metadata = serde_json::to_value(metadata)?;
 
     Ok(CreateEventRequest {
         user_id,
@@ -176,10 +158,11 @@ fn validate_request<'a>(
 
 async fn insert_to_command_bus(
     id: i64,
+    tenant: Tenant,
     type_id: i64,
     request: CreateEventRequest,
     bus: &CommandBus,
-    cache: &Arc<RwLock<LeveledCache>>,
+    cache: &LeveledCache,
     proj: &EventsProj,
 ) -> Result<(), anyhow::Error> {
     let proj_clone = proj.clone();
@@ -188,25 +171,47 @@ async fn insert_to_command_bus(
     bus.push(
         r#"
         WITH inserted AS (
-            INSERT INTO events (id, user_id, type_id, timestamp, metadata)
-            SELECT *
+            INSERT INTO events (id, user_id, type_id, timestamp, metadata, tenant_id)
+            SELECT t.*
             FROM UNNEST(
                 $1::bigint[],
                 $2::bigint[],
                 $3::bigint[],
                 $4::timestamptz[],
-                $5::jsonb[]
+                $5::jsonb[],
+                $6::bigint[]
             ) AS t(
                 id,
                 user_id,
                 type_id,
                 timestamp,
-                metadata
+                metadata,
+                tenant_id
+            )
+            -- The lookups above are cached, so a user or type deleted since
+            -- then is only seen here. Skipping the row keeps the batch alive.
+            WHERE EXISTS (
+                SELECT 1 FROM users u
+                WHERE u.id = t.user_id AND u.tenant_id = t.tenant_id AND u.deleted_at IS NULL
+            )
+              AND EXISTS (
+                SELECT 1 FROM event_types et
+                WHERE et.id = t.type_id AND et.tenant_id = t.tenant_id AND et.deleted_at IS NULL
             )
-            RETURNING user_id
+            RETURNING tenant_id, user_id
+        ),
+        per_tenant AS (
+            SELECT tenant_id, COUNT(*) AS inserted FROM inserted GROUP BY tenant_id
+        ),
+        owners AS (
+            SELECT DISTINCT tenant_id, user_id FROM inserted
         )
-        SELECT COUNT(*) AS total_inserted, array_agg(DISTINCT user_id) AS unique_users
-        FROM inserted;
+        -- One row per flush, so tenants come back as aligned arrays.
+        SELECT
+            ARRAY(SELECT tenant_id FROM per_tenant ORDER BY tenant_id) AS tenants,
+            ARRAY(SELECT inserted FROM per_tenant ORDER BY tenant_id) AS tenant_inserted,
+            ARRAY(SELECT tenant_id FROM owners ORDER BY tenant_id, user_id) AS user_tenants,
+            ARRAY(SELECT user_id FROM owners ORDER BY tenant_id, user_id) AS unique_users;
         "#,
         vec![
             CommandValue::Int(id),
@@ -214,47 +219,63 @@ async fn insert_to_command_bus(
             CommandValue::Int(type_id),
             CommandValue::Timestamp(request.timestamp),
             CommandValue::Json(request.metadata),
+            CommandValue::Int(tenant.0),
         ],
         Some(Box::new(move |row: &dyn Any| {
             if let Some(row) = row.downcast_ref::<sqlx::postgres::PgRow>() {
-                let inserted: i64 = row.try_get("total_inserted").unwrap();
-                let users: Vec<i64> = row.try_get("unique_users").unwrap();
+                let (Ok(tenants), Ok(inserted), Ok(user_tenants), Ok(users)) = (
+                    row.try_get::<Vec<i64>, _>("tenants"),
+                    row.try_get::<Vec<i64>, _>("tenant_inserted"),
+                    row.try_get::<Vec<i64>, _>("user_tenants"),
+                    row.try_get::<Vec<i64>, _>("unique_users"),
+                ) else {
+                    return;
+                };
 
                 let proj_clone = proj_clone.clone();
                 let cache_clone = cache_clone.clone();
 
                 tokio::spawn(async move {
-                    let count = proj_clone.get_events_count().await;
+                    for (tenant, inserted) in tenants.into_iter().map(Tenant).zip(inserted) {
+                        // A cold count would be read after this insert and
+                        // count it twice, so only a cached one is bumped.
+                        let total = tenant.key("total_events");
+                        let bumped = cache_clone
+                            .try_get(total.clone())
+                            .await
+                            .and_then(|mut bytes| from_slice::<i64>(&mut bytes).ok())
+                            .and_then(|count| to_vec(&(count + inserted)).ok());
 
-                    let mut write_cache = cache_clone.write().await;
+                        let _ = match bumped {
+                            Some(payload) => {
+                                cache_clone
+                                    .save(
+                                        CacheSetKey::Exact(total),
+                                        payload,
+                                        proj_clone.ttls().lookup,
+                                    )
+                                    .await
+                            }
+                            None => cache_clone.invalidate(CacheDeleteKey::Exact(total)).await,
+                        };
 
-                    let calculated = count + inserted;
-
-                    let _ = write_cache
-                        .save(
-                            crate::common::cache::CacheSetKey::Exact("total_events".to_string()),
-                            to_vec(&calculated).unwrap(),
-                            100,
-                        )
-                        .await;
-
-                    for user_id in users {
-                        let _ = write_cache
-                            .invalidate(crate::common::cache::CacheDeleteKey::Exact(format!(
-                                "user_{}",
-                                user_id
-                            )))
+                        let _ = cache_clone
+                            .invalidate(CacheDeleteKey::Pattern(tenant.stats_pattern()))
                             .await;
+                    }
 
-                        let _ = write_cache
-                            .invalidate(crate::common::cache::CacheDeleteKey::Pattern(
-                                "events_stat_{}_{}_{}".to_string(),
+                    let owners: BTreeSet<(i64, i64)> =
+                        user_tenants.into_iter().zip(users).collect();
+                    for (tenant, user_id) in owners {
+                        let _ = cache_clone
+                            .invalidate(CacheDeleteKey::Exact(
+                                Tenant(tenant).user_events_key(user_id),
                             ))
                             .await;
                     }
                 });
             } else {
-                println!("Unexpected row type");
+                tracing::warn!("unexpected command bus row type");
             }
         })),
     )
@@ -2,19 +2,27 @@ use std::collections::HashMap;
 
 use serde_json::from_slice;
 
-use crate::contexts::events::infrastructure::cached_projection::EventsProj;
+use crate::{
+    common::{error::AppError, tenant::Tenant},
+    contexts::events::infrastructure::cached_projection::EventsProj,
+};
 
-pub async fn get_type(proj: &EventsProj, event_type: &str) -> Result<Option<i64>, anyhow::Error> {
-    let types: HashMap<String, i64> = proj.get_types_name_id().await;
+pub async fn get_type(
+    proj: &EventsProj,
+    tenant: Tenant,
+    event_type: &str,
+) -> Result<Option<i64>, AppError> {
+    let types: HashMap<String, i64> = proj.get_types_name_id(tenant).await?;
 
-    Ok(match types.get(event_type) {
-        Some(&id) => Some(id),
-        None => None,
-    })
+    Ok(types.get(event_type).copied())
 }
 
-pub async fn is_user_exist(proj: &EventsProj, user_id: i64) -> Result<bool, anyhow::Error> {
-    let users: Vec<i64> = from_slice(proj.get_users_id().await.as_mut())?;
+pub async fn is_user_exist(
+    proj: &EventsProj,
+    tenant: Tenant,
+    user_id: i64,
+) -> Result<bool, AppError> {
+    let users: Vec<i64> = from_slice(proj.get_users_id(tenant).await?.as_mut())?;
 
     let is_exist = users.contains(&user_id);
 
@@ -1,47 +1,57 @@
-use actix_web::{HttpResponse, Responder, get, web};
+use actix_web::{HttpRequest, HttpResponse, get, web};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use utoipa::IntoParams;
 
-use crate::contexts::events::{
-    features::functions_php::get_type, infrastructure::cached_projection::EventsProj,
+use crate::{
+    common::{
+        error::{AppError, ErrorBody},
+        http_cache::cached_response,
+        tenant::Tenant,
+    },
+    contexts::events::{
+        features::functions_php::get_type,
+        infrastructure::cached_projection::{EventsProj, Stat},
+    },
 };
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(read_events_stat);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct StatsQuery {
     from: DateTime<Utc>,
     to: DateTime<Utc>,
+    /// Event type name
     e_type: String,
 }
 
+#[utoipa::path(
+    tag = "events",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Events, unique users and page views of the type", body = Stat),
+        (status = 304, description = "`If-None-Match` matched the `ETag`"),
+        (status = 400, description = "Invalid query or unknown event type", body = ErrorBody),
+    )
+)]
 #[get("/stats")]
 pub async fn read_events_stat(
+    req: HttpRequest,
+    tenant: Tenant,
     query: web::Query<StatsQuery>,
     proj: web::Data<EventsProj>,
-) -> impl Responder {
-    let type_id: i64 = match get_type(&proj, &query.e_type).await {
-        Ok(type_id) => {
-            if let Some(type_id) = type_id {
-                type_id
-            } else {
-                return HttpResponse::BadRequest()
-                    .content_type("application/json")
-                    .body("{\"error\":\"Type not exist\"}");
-            }
-        }
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .content_type("application/json")
-                .body(format!("{{\"error\":\"Database error: {}\"}}", e));
-        }
-    };
+) -> Result<HttpResponse, AppError> {
+    let type_id: i64 = get_type(&proj, tenant, &query.e_type)
+        .await?
+        .ok_or(AppError::EventTypeNotFound)?;
 
-    let stats = proj.get_ref().stats(query.from, query.to, type_id).await;
+    let stats = proj
+        .get_ref()
+        .stats(tenant, query.from, query.to, type_id)
+        .await?;
 
-    HttpResponse::Ok()
-        .content_type("application/json")
-        .body(stats)
+    Ok(cached_response(&req, stats, proj.ttls().stats))
 }
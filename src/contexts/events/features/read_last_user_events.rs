@@ -1,42 +1,55 @@
-use actix_web::{HttpResponse, Responder, get, web};
+use actix_web::{HttpRequest, HttpResponse, get, web};
 use serde::Deserialize;
+use utoipa::IntoParams;
 
-use crate::contexts::events::{
-    features::functions_php::is_user_exist, infrastructure::cached_projection::EventsProj,
+use crate::{
+    common::{
+        error::{AppError, ErrorBody},
+        http_cache::cached_response,
+        rate_limit::limit_user,
+        tenant::Tenant,
+    },
+    contexts::events::{
+        features::functions_php::is_user_exist,
+        infrastructure::{cached_projection::EventsProj, repo::Event},
+    },
 };
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(read_last_user_events);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
 pub struct UserPath {
     user_id: i64,
 }
 
+#[utoipa::path(
+    tag = "events",
+    params(UserPath),
+    responses(
+        (status = 200, description = "The last 1000 events of the user, newest first", body = Vec<Event>),
+        (status = 304, description = "`If-None-Match` matched the `ETag`"),
+        (status = 400, description = "Invalid or unknown user", body = ErrorBody),
+        (status = 429, description = "Rate limited", body = ErrorBody),
+    )
+)]
 #[get("/users/{user_id}/events")]
 pub async fn read_last_user_events(
+    req: HttpRequest,
+    tenant: Tenant,
     path: web::Path<UserPath>,
     proj: web::Data<EventsProj>,
-) -> impl Responder {
-    match is_user_exist(&proj, path.user_id).await {
-        Ok(exists) => {
-            if !exists {
-                return HttpResponse::BadRequest()
-                    .content_type("application/json")
-                    .body("{\"error\":\"User not exist\"}");
-            }
-        }
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .content_type("application/json")
-                .body(format!("{{\"error\":\"Database error: {}\"}}", e));
-        }
-    };
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("user_id", path.user_id);
+    limit_user(&req, path.user_id).await?;
 
-    let events = proj.get_thousand_user_events(path.user_id).await;
+    if !is_user_exist(&proj, tenant, path.user_id).await? {
+        return Err(AppError::UserNotFound);
+    }
 
-    HttpResponse::Ok()
-        .content_type("application/json")
-        .body(events)
+    let events = proj.get_thousand_user_events(tenant, path.user_id).await?;
+
+    Ok(cached_response(&req, events, proj.ttls().user_events))
 }
@@ -1,29 +1,65 @@
-use actix_web::{HttpResponse, Responder, get, web};
+use actix_web::{HttpRequest, HttpResponse, get, web};
 use serde::Deserialize;
+use utoipa::IntoParams;
 
-use crate::contexts::events::infrastructure::cached_projection::EventsProj;
+use crate::{
+    common::{
+        error::{AppError, ErrorBody},
+        http_cache::cached_response,
+        tenant::Tenant,
+    },
+    contexts::events::infrastructure::cached_projection::{EventsProj, PaginatedEvents},
+};
+
+pub const MAX_PAGE_LIMIT: usize = 1000;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(read_paginated_events);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct Pagination {
+    /// Starts at 1, defaults to 1
     page: Option<usize>,
+    /// 1 to 1000, defaults to 100
     limit: Option<usize>,
 }
 
+#[utoipa::path(
+    tag = "events",
+    params(Pagination),
+    responses(
+        (status = 200, description = "A page of events, newest first", body = PaginatedEvents),
+        (status = 304, description = "`If-None-Match` matched the `ETag`"),
+        (status = 400, description = "Invalid query", body = ErrorBody),
+    )
+)]
 #[get("/events")]
 pub async fn read_paginated_events(
+    req: HttpRequest,
+    tenant: Tenant,
     pagination: web::Query<Pagination>,
     proj: web::Data<EventsProj>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let page = pagination.page.unwrap_or(1);
     let limit = pagination.limit.unwrap_or(100);
 
-    let data = proj.paginate_events(page, limit).await;
+    if page == 0 {
+        return Err(AppError::Validation {
+            field: "page",
+            message: "`page` must be at least 1",
+        });
+    }
+
+    if limit == 0 || limit > MAX_PAGE_LIMIT {
+        return Err(AppError::Validation {
+            field: "limit",
+            message: "`limit` must be between 1 and 1000",
+        });
+    }
+
+    let data = proj.paginate_events(tenant, page, limit).await?;
 
-    HttpResponse::Ok()
-        .content_type("application/json")
-        .body(data)
+    Ok(cached_response(&req, data, proj.ttls().page))
 }
@@ -1,37 +1,71 @@
-use std::{collections::HashMap, sync::Arc};
+use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Serializer};
 use simd_json::{from_slice, to_vec};
 use sqlx::types::JsonValue;
-use tokio::sync::RwLock;
+use utoipa::ToSchema;
 
 use crate::{
-    common::cache::{CacheSetKey, LeveledCache},
+    common::{
+        cache::{CacheSetKey, LeveledCache},
+        config::CacheConfig,
+        error::AppError,
+        tenant::Tenant,
+    },
     contexts::events::infrastructure::repo::{EventTypeRow, EventsRepo},
 };
 
+#[derive(Clone, Copy, Debug)]
+pub struct CacheTtls {
+    pub stats: u64,
+    pub user_events: u64,
+    pub page: u64,
+    pub lookup: u64,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        CacheTtls::from(&CacheConfig::default())
+    }
+}
+
+impl From<&CacheConfig> for CacheTtls {
+    fn from(config: &CacheConfig) -> Self {
+        CacheTtls {
+            stats: config.stats_ttl,
+            user_events: config.user_events_ttl,
+            page: config.page_ttl,
+            lookup: config.lookup_ttl,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EventsProj {
-    cache: Arc<RwLock<LeveledCache>>,
+    cache: LeveledCache,
     repo: EventsRepo,
+    ttls: CacheTtls,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Stat {
     total_events: i64,
     unique_users: i64,
     top_pages: HashMap<String, i64>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
 pub struct EventWithType {
     #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
     pub id: i64,
     #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
     pub user_id: i64,
     pub event_type: String,
     pub timestamp: DateTime<Utc>,
+    #[schema(value_type = Object)]
     pub metadata: JsonValue,
 }
 
@@ -42,13 +76,13 @@ where
     s.serialize_str(&x.to_string())
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PaginatedEvents {
     data: Vec<EventWithType>,
     query: Pagination,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Pagination {
     page: usize,
     limit: usize,
@@ -56,21 +90,34 @@ pub struct Pagination {
 }
 
 impl EventsProj {
-    pub fn create(cache: Arc<RwLock<LeveledCache>>, repo: EventsRepo) -> EventsProj {
-        EventsProj { cache, repo }
+    pub fn create(cache: LeveledCache, repo: EventsRepo, ttls: CacheTtls) -> EventsProj {
+        EventsProj { cache, repo, ttls }
     }
 
-    pub async fn stats(&self, from: DateTime<Utc>, to: DateTime<Utc>, type_id: i64) -> Vec<u8> {
+    pub fn ttls(&self) -> CacheTtls {
+        self.ttls
+    }
+
+    pub async fn stats(
+        &self,
+        tenant: Tenant,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        type_id: i64,
+    ) -> Result<Vec<u8>, AppError> {
         let from_rfc = from.to_rfc3339();
         let to_rfc = to.to_rfc3339();
 
-        let cache = format!("events_stat_{}_{}_{}", &from_rfc, &to_rfc, type_id);
+        let cache = format!(
+            "events_stat_{}_{}_{}_{}",
+            tenant.0, &from_rfc, &to_rfc, type_id
+        );
 
-        if let Some(bytes) = &self.cache.read().await.try_get(cache.clone()).await {
-            return bytes.to_owned();
+        if let Some(bytes) = &self.cache.try_get(cache.clone()).await {
+            return Ok(bytes.to_owned());
         }
 
-        let stats = &self.repo.stats(from, to, type_id).await.unwrap();
+        let stats = &self.repo.stats(tenant.0, from, to, type_id).await?;
 
         let mut users: Vec<i64> = vec![];
         let mut total: i64 = 0;
@@ -90,63 +137,74 @@ impl EventsProj {
             top_pages: pages,
         };
 
-        let payload = to_vec(&stat).unwrap();
+        let payload = to_vec(&stat)?;
 
-        let _ = &self
+        let _ = self
             .cache
-            .write()
-            .await
             .save(
                 CacheSetKey::Pattern(
-                    "events_stat_{}_{}_{}".to_string(),
+                    tenant.stats_pattern(),
                     vec![from_rfc, to_rfc, type_id.to_string()],
                 ),
                 payload.clone(),
-                300,
+                self.ttls.stats,
             )
             .await;
 
-        payload
+        Ok(payload)
     }
 
-    pub async fn get_thousand_user_events(&self, user_id: i64) -> Vec<u8> {
-        let cache = format!("user_events_{}", user_id);
+    pub async fn get_thousand_user_events(
+        &self,
+        tenant: Tenant,
+        user_id: i64,
+    ) -> Result<Vec<u8>, AppError> {
+        let cache = tenant.user_events_key(user_id);
 
-        if let Some(bytes) = &self.cache.read().await.try_get(cache.clone()).await {
-            return bytes.to_owned();
+        if let Some(bytes) = &self.cache.try_get(cache.clone()).await {
+            return Ok(bytes.to_owned());
         }
 
-        let events = self.repo.get_thousand_user_events(user_id).await.unwrap();
+        let events = self
+            .repo
+            .get_thousand_user_events(tenant.0, user_id)
+            .await?;
 
-        let payload = to_vec(&events).unwrap();
+        let payload = to_vec(&events)?;
 
-        let _ = &self
+        let _ = self
             .cache
-            .write()
-            .await
-            .save(CacheSetKey::Exact(cache), payload.clone(), 300)
+            .save(
+                CacheSetKey::Exact(cache),
+                payload.clone(),
+                self.ttls.user_events,
+            )
             .await;
 
-        payload
+        Ok(payload)
     }
 
-    pub async fn paginate_events(&self, page: usize, limit: usize) -> Vec<u8> {
-        let cache = format!("page_{}_{}", page, limit);
+    pub async fn paginate_events(
+        &self,
+        tenant: Tenant,
+        page: usize,
+        limit: usize,
+    ) -> Result<Vec<u8>, AppError> {
+        let cache = format!("page_{}_{}_{}", tenant.0, page, limit);
 
-        if let Some(bytes) = &self.cache.read().await.try_get(cache.clone()).await {
-            return bytes.to_owned();
+        if let Some(bytes) = &self.cache.try_get(cache.clone()).await {
+            return Ok(bytes.to_owned());
         }
 
-        let events = &self.repo.paginate_events(page, limit).await.unwrap();
+        let events = &self.repo.paginate_events(tenant.0, page, limit).await?;
 
         let mut typed_rows: Vec<EventWithType> = Vec::with_capacity(events.len());
-        let types_map = self.get_types_id_name().await;
+        let types_map = self.get_types_id_name(tenant).await?;
 
         for ev in events {
-            let name = types_map
-                .get(&ev.type_id)
-                .unwrap_or_else(|| panic!("Event type not found for type_id {}", ev.type_id))
-                .clone();
+            let name = types_map.get(&ev.type_id).cloned().ok_or_else(|| {
+                anyhow::Error::msg(format!("Event type not found for type_id {}", ev.type_id))
+            })?;
 
             typed_rows.push(EventWithType {
                 id: ev.id,
@@ -157,112 +215,123 @@ impl EventsProj {
             });
         }
 
-        let total = self.get_events_count().await;
+        let total = self.get_events_count(tenant).await?;
 
         let result = PaginatedEvents {
             data: typed_rows,
             query: Pagination { page, limit, total },
         };
 
-        let payload = to_vec(&result).unwrap();
+        let payload = to_vec(&result)?;
 
-        let _ = &self
+        let _ = self
             .cache
-            .write()
-            .await
             .save(
                 CacheSetKey::Pattern(
-                    "page_{}_{}".to_string(),
+                    tenant.page_pattern(),
                     vec![page.to_string(), limit.to_string()],
                 ),
                 payload.clone(),
-                300,
+                self.ttls.page,
             )
             .await;
 
-        payload
+        Ok(payload)
     }
 
-    pub async fn get_events_count(&self) -> i64 {
-        let cache = "total_events".to_owned();
+    pub async fn get_events_count(&self, tenant: Tenant) -> Result<i64, AppError> {
+        let cache = tenant.key("total_events");
 
-        if let Some(bytes) = &self.cache.read().await.try_get(cache.clone()).await {
+        if let Some(bytes) = &self.cache.try_get(cache.clone()).await {
             let mut bytes = bytes.to_owned();
-            let count: i64 = from_slice(bytes.as_mut()).unwrap();
-            return count;
+            let count: i64 = from_slice(bytes.as_mut())?;
+            return Ok(count);
         }
 
-        let value = self.repo.count_events().await.unwrap();
+        let value = self.repo.count_events(tenant.0).await?;
 
-        let payload = to_vec(&value).unwrap();
+        let payload = to_vec(&value)?;
 
-        let _ = &self
+        let _ = self
             .cache
-            .write()
-            .await
-            .save(CacheSetKey::Exact(cache.clone()), payload.clone(), 300)
+            .save(
+                CacheSetKey::Exact(cache.clone()),
+                payload.clone(),
+                self.ttls.lookup,
+            )
             .await;
 
-        value
+        Ok(value)
     }
 
-    pub async fn get_types(&self) -> Vec<u8> {
-        let cache = "event_types".to_owned();
+    pub async fn get_types(&self, tenant: Tenant) -> Result<Vec<u8>, AppError> {
+        let cache = tenant.key("event_types");
 
-        if let Some(bytes) = &self.cache.read().await.try_get(cache.clone()).await {
-            return bytes.to_owned();
+        if let Some(bytes) = &self.cache.try_get(cache.clone()).await {
+            return Ok(bytes.to_owned());
         }
 
-        let types = &self.repo.get_types().await.unwrap();
+        let types = &self.repo.get_types(tenant.0).await?;
 
-        let payload = to_vec(types).unwrap();
+        let payload = to_vec(types)?;
 
-        let _ = &self
+        let _ = self
             .cache
-            .write()
-            .await
-            .save(CacheSetKey::Exact(cache.clone()), payload.clone(), 300)
+            .save(
+                CacheSetKey::Exact(cache.clone()),
+                payload.clone(),
+                self.ttls.lookup,
+            )
             .await;
 
-        payload
+        Ok(payload)
     }
 
-    pub async fn get_types_name_id(&self) -> HashMap<String, i64> {
-        let types: Vec<EventTypeRow> = from_slice(self.get_types().await.as_mut()).unwrap();
+    pub async fn get_types_name_id(
+        &self,
+        tenant: Tenant,
+    ) -> Result<HashMap<String, i64>, AppError> {
+        let types: Vec<EventTypeRow> = from_slice(self.get_types(tenant).await?.as_mut())?;
 
-        types
+        Ok(types
             .into_iter()
+            .filter(|type_row| !type_row.deleted)
             .map(|type_row| (type_row.name, type_row.id))
-            .collect::<HashMap<String, i64>>()
+            .collect::<HashMap<String, i64>>())
     }
 
-    pub async fn get_types_id_name(&self) -> HashMap<i64, String> {
-        let types: Vec<EventTypeRow> = from_slice(self.get_types().await.as_mut()).unwrap();
+    pub async fn get_types_id_name(
+        &self,
+        tenant: Tenant,
+    ) -> Result<HashMap<i64, String>, AppError> {
+        let types: Vec<EventTypeRow> = from_slice(self.get_types(tenant).await?.as_mut())?;
 
-        types
+        Ok(types
             .into_iter()
             .map(|type_row| (type_row.id, type_row.name))
-            .collect::<HashMap<i64, String>>()
+            .collect::<HashMap<i64, String>>())
     }
 
-    pub async fn get_users_id(&self) -> Vec<u8> {
-        let cache = "users_id".to_owned();
+    pub async fn get_users_id(&self, tenant: Tenant) -> Result<Vec<u8>, AppError> {
+        let cache = tenant.key("users_id");
 
-        if let Some(bytes) = &self.cache.read().await.try_get(cache.clone()).await {
-            return bytes.to_owned();
+        if let Some(bytes) = &self.cache.try_get(cache.clone()).await {
+            return Ok(bytes.to_owned());
         }
 
-        let ids = &self.repo.get_users_id().await.unwrap();
+        let ids = &self.repo.get_users_id(tenant.0).await?;
 
-        let payload = to_vec(ids).unwrap();
+        let payload = to_vec(ids)?;
 
-        let _ = &self
+        let _ = self
             .cache
-            .write()
-            .await
-            .save(CacheSetKey::Exact(cache.clone()), payload.clone(), 300)
+            .save(
+                CacheSetKey::Exact(cache.clone()),
+                payload.clone(),
+                self.ttls.lookup,
+            )
             .await;
 
-        payload
+        Ok(payload)
     }
 }
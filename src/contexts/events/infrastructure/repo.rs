@@ -1,8 +1,10 @@
 use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize, Serializer};
 use sqlx::prelude::FromRow;
 use sqlx::types::JsonValue;
-use sqlx::{Pool, Postgres, query_as, query_scalar};
+use sqlx::{Pool, Postgres, query, query_as, query_scalar};
+use utoipa::ToSchema;
 
 #[derive(Clone)]
 pub struct EventsRepo {
@@ -13,6 +15,7 @@ pub struct EventsRepo {
 pub struct EventTypeRow {
     pub id: i64,
     pub name: String,
+    pub deleted: bool,
 }
 
 #[derive(Serialize)]
@@ -22,15 +25,19 @@ pub struct EventStat {
     pub page: String,
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, FromRow, Serialize, ToSchema)]
 pub struct Event {
     #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
     pub id: i64,
     #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
     pub user_id: i64,
     #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
     pub type_id: i64,
     pub timestamp: DateTime<Utc>,
+    #[schema(value_type = Object)]
     pub metadata: JsonValue,
 }
 fn i64_to_string<S>(x: &i64, s: S) -> Result<S::Ok, S::Error>
@@ -45,32 +52,45 @@ impl EventsRepo {
         EventsRepo { postgres }
     }
 
-    pub async fn get_types(&self) -> Result<Vec<EventTypeRow>, anyhow::Error> {
-        let rows = query_as!(EventTypeRow, r#"SELECT id, name FROM event_types"#)
-            .fetch_all(&self.postgres.to_owned())
-            .await?;
+    pub async fn get_types(&self, tenant_id: i64) -> Result<Vec<EventTypeRow>, anyhow::Error> {
+        let rows = query_as!(
+            EventTypeRow,
+            r#"SELECT id, name, deleted_at IS NOT NULL AS "deleted!"
+            FROM event_types
+            WHERE tenant_id = $1"#,
+            tenant_id
+        )
+        .fetch_all(&self.postgres.to_owned())
+        .await?;
 
         Ok(rows)
     }
 
-    pub async fn get_users_id(&self) -> Result<Vec<i64>, anyhow::Error> {
-        let ids: Vec<i64> = query_scalar!(r#"SELECT id FROM users"#)
-            .fetch_all(&self.postgres)
-            .await?;
+    pub async fn get_users_id(&self, tenant_id: i64) -> Result<Vec<i64>, anyhow::Error> {
+        let ids: Vec<i64> = query_scalar!(
+            r#"SELECT id FROM users WHERE tenant_id = $1 AND deleted_at IS NULL"#,
+            tenant_id
+        )
+        .fetch_all(&self.postgres)
+        .await?;
 
         Ok(ids)
     }
 
-    pub async fn count_events(&self) -> Result<i64, anyhow::Error> {
-        let count: Option<i64> = query_scalar!(r#"SELECT COUNT(id) FROM events"#)
-            .fetch_one(&self.postgres)
-            .await?;
+    pub async fn count_events(&self, tenant_id: i64) -> Result<i64, anyhow::Error> {
+        let count: Option<i64> = query_scalar!(
+            r#"SELECT COUNT(id) FROM events WHERE tenant_id = $1"#,
+            tenant_id
+        )
+        .fetch_one(&self.postgres)
+        .await?;
 
         Ok(count.unwrap_or(0))
     }
 
     pub async fn paginate_events(
         &self,
+        tenant_id: i64,
         page: usize,
         limit: usize,
     ) -> Result<Vec<Event>, anyhow::Error> {
@@ -84,9 +104,11 @@ impl EventsRepo {
                 type_id,
                 timestamp,
                 metadata
-            FROM events 
+            FROM events
+            WHERE tenant_id = $1
             ORDER BY timestamp DESC
-            OFFSET $1 LIMIT $2"#,
+            OFFSET $2 LIMIT $3"#,
+            tenant_id,
             offset as i64,
             limit as i64
         )
@@ -98,6 +120,7 @@ impl EventsRepo {
 
     pub async fn get_thousand_user_events(
         &self,
+        tenant_id: i64,
         user_id: i64,
     ) -> Result<Vec<Event>, anyhow::Error> {
         let events = sqlx::query_as!(
@@ -109,9 +132,10 @@ impl EventsRepo {
                 timestamp,
                 metadata
                FROM events
-               WHERE user_id = $1
+               WHERE tenant_id = $1 AND user_id = $2
                ORDER BY timestamp DESC
                LIMIT 1000"#,
+            tenant_id,
             user_id
         )
         .fetch_all(&self.postgres)
@@ -122,6 +146,7 @@ impl EventsRepo {
 
     pub async fn stats(
         &self,
+        tenant_id: i64,
         from: DateTime<Utc>,
         to: DateTime<Utc>,
         type_id: i64,
@@ -133,10 +158,12 @@ impl EventsRepo {
                 user_id as "user_id!",
                 metadata->>'page' as "page!"
             FROM events
-            WHERE timestamp >= $1
-            AND timestamp <= $2
-            AND type_id = $3
+            WHERE tenant_id = $1
+            AND timestamp >= $2
+            AND timestamp <= $3
+            AND type_id = $4
             GROUP BY user_id, metadata->>'page'"#,
+            tenant_id,
             from,
             to,
             type_id
@@ -146,4 +173,135 @@ impl EventsRepo {
 
         Ok(stats)
     }
+
+    pub fn stream_events(
+        &self,
+        tenant_id: i64,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> BoxStream<'_, Result<Event, sqlx::Error>> {
+        query_as!(
+            Event,
+            r#"SELECT
+                id,
+                user_id,
+                type_id,
+                timestamp,
+                metadata
+            FROM events
+            WHERE tenant_id = $1
+            AND timestamp >= COALESCE($2, '-infinity'::timestamptz)
+            AND timestamp < COALESCE($3, 'infinity'::timestamptz)
+            ORDER BY id"#,
+            tenant_id,
+            from,
+            to
+        )
+        .fetch(&self.postgres)
+    }
+
+    /// Live and retention-archived events of one user, oldest first.
+    pub fn stream_user_events(
+        &self,
+        tenant_id: i64,
+        user_id: i64,
+    ) -> BoxStream<'_, Result<Event, sqlx::Error>> {
+        query_as!(
+            Event,
+            r#"SELECT
+                id AS "id!",
+                user_id AS "user_id!",
+                type_id AS "type_id!",
+                timestamp AS "timestamp!",
+                metadata AS "metadata!"
+            FROM (
+                SELECT id, user_id, type_id, timestamp, metadata FROM events
+                WHERE tenant_id = $1 AND user_id = $2
+                UNION ALL
+                SELECT id, user_id, type_id, timestamp, metadata FROM events_archive
+                WHERE tenant_id = $1 AND user_id = $2
+            ) AS stored
+            ORDER BY timestamp, id"#,
+            tenant_id,
+            user_id
+        )
+        .fetch(&self.postgres)
+    }
+
+    /// Deletes up to `limit` events of the user, archived ones included.
+    /// Returns how many went; call until it returns 0.
+    pub async fn delete_user_events(
+        &self,
+        tenant_id: i64,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<u64, anyhow::Error> {
+        let deleted = query!(
+            r#"WITH doomed AS (
+                SELECT id, timestamp FROM events WHERE tenant_id = $1 AND user_id = $2 LIMIT $3
+            )
+            DELETE FROM events e
+            USING doomed d
+            WHERE e.id = d.id AND e.timestamp = d.timestamp"#,
+            tenant_id,
+            user_id,
+            limit
+        )
+        .execute(&self.postgres)
+        .await?
+        .rows_affected();
+
+        let archived = query!(
+            r#"DELETE FROM events_archive WHERE tenant_id = $1 AND user_id = $2"#,
+            tenant_id,
+            user_id
+        )
+        .execute(&self.postgres)
+        .await?
+        .rows_affected();
+
+        Ok(deleted + archived)
+    }
+
+    /// Moves up to `limit` events of the user to `anonymous_id` and drops
+    /// their metadata but `page`, which the stats group by. Returns how many
+    /// moved; call until it returns 0.
+    pub async fn anonymize_user_events(
+        &self,
+        tenant_id: i64,
+        user_id: i64,
+        anonymous_id: i64,
+        limit: i64,
+    ) -> Result<u64, anyhow::Error> {
+        let moved = query!(
+            r#"WITH doomed AS (
+                SELECT id, timestamp FROM events WHERE tenant_id = $1 AND user_id = $2 LIMIT $4
+            )
+            UPDATE events e
+            SET user_id = $3, metadata = jsonb_build_object('page', e.metadata->'page')
+            FROM doomed d
+            WHERE e.id = d.id AND e.timestamp = d.timestamp"#,
+            tenant_id,
+            user_id,
+            anonymous_id,
+            limit
+        )
+        .execute(&self.postgres)
+        .await?
+        .rows_affected();
+
+        let archived = query!(
+            r#"UPDATE events_archive
+            SET user_id = $3, metadata = jsonb_build_object('page', metadata->'page')
+            WHERE tenant_id = $1 AND user_id = $2"#,
+            tenant_id,
+            user_id,
+            anonymous_id
+        )
+        .execute(&self.postgres)
+        .await?
+        .rows_affected();
+
+        Ok(moved + archived)
+    }
 }
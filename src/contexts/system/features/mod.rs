@@ -0,0 +1,13 @@
+use actix_web::web;
+
+pub mod read_liveness;
+pub mod read_metrics;
+pub mod read_openapi;
+pub mod read_readiness;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.configure(read_liveness::configure);
+    cfg.configure(read_metrics::configure);
+    cfg.configure(read_openapi::configure);
+    cfg.configure(read_readiness::configure);
+}
@@ -0,0 +1,29 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use serde::Serialize;
+
+use crate::common::health::Health;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(read_liveness);
+}
+
+#[derive(Serialize)]
+struct Liveness {
+    status: &'static str,
+    uptime_seconds: u64,
+}
+
+// Liveness only proves the worker answers; dependencies belong to /readyz so a
+// Postgres outage does not get every pod restarted.
+#[utoipa::path(
+    tag = "system",
+    security(()),
+    responses((status = 200, description = "The worker answers"))
+)]
+#[get("/healthz")]
+pub async fn read_liveness(health: Option<web::Data<Health>>) -> impl Responder {
+    HttpResponse::Ok().json(Liveness {
+        status: "up",
+        uptime_seconds: health.map(|h| h.uptime().as_secs()).unwrap_or(0),
+    })
+}
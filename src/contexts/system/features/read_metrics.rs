@@ -0,0 +1,25 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use sqlx::{Pool, Postgres};
+
+use crate::common::metrics::METRICS;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(read_metrics);
+}
+
+#[utoipa::path(
+    tag = "system",
+    security(()),
+    responses((status = 200, description = "Prometheus text format", body = String,
+        content_type = "text/plain"))
+)]
+#[get("/metrics")]
+pub async fn read_metrics(pool: Option<web::Data<Pool<Postgres>>>) -> impl Responder {
+    if let Some(pool) = pool {
+        METRICS.observe_pool(pool.get_ref());
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render())
+}
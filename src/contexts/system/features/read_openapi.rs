@@ -0,0 +1,30 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use once_cell::sync::Lazy;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::common::openapi::ApiDoc;
+
+static SPEC: Lazy<String> = Lazy::new(|| {
+    ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("OpenAPI document serializes")
+});
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(read_openapi);
+    // The viewer's assets are compiled in, so it works without internet access.
+    cfg.service(SwaggerUi::new("/docs/{_:.*}").config(Config::from("/openapi.json")));
+}
+
+#[utoipa::path(
+    tag = "system",
+    security(()),
+    responses((status = 200, description = "This document", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+pub async fn read_openapi() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(SPEC.as_str())
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use actix_web::{HttpResponse, Responder, get, web};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+
+use crate::common::{cache::redis_store::RedisStore, command_bus::CommandBus, health::Health};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(read_readiness);
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    status: &'static str,
+    draining: bool,
+    checks: BTreeMap<&'static str, Check>,
+}
+
+#[derive(Serialize)]
+struct Check {
+    status: &'static str,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lag_ms: Option<f64>,
+}
+
+impl Check {
+    fn disabled() -> Check {
+        Check {
+            status: "disabled",
+            latency_ms: 0.0,
+            error: None,
+            lag_ms: None,
+        }
+    }
+
+    fn is_down(&self) -> bool {
+        self.status == "down"
+    }
+}
+
+// Redis is reported but does not gate readiness: the cache degrades to L1 and
+// Postgres when it is gone (see the tiered store).
+#[utoipa::path(
+    tag = "system",
+    security(()),
+    responses(
+        (status = 200, description = "Postgres and the command bus are up"),
+        (status = 503, description = "Draining, or a dependency is down"),
+    )
+)]
+#[get("/readyz")]
+pub async fn read_readiness(
+    health: Option<web::Data<Health>>,
+    pool: Option<web::Data<Pool<Postgres>>>,
+    redis: Option<web::Data<RedisStore>>,
+    bus: Option<web::Data<Arc<CommandBus>>>,
+) -> impl Responder {
+    let draining = health.as_ref().is_some_and(|h| h.is_draining());
+
+    let (postgres, redis) = tokio::join!(
+        async {
+            match &pool {
+                Some(pool) => {
+                    probe("postgres", async {
+                        sqlx::query("SELECT 1").execute(pool.get_ref()).await
+                    })
+                    .await
+                }
+                None => Check::disabled(),
+            }
+        },
+        async {
+            match &redis {
+                Some(redis) => probe("redis", redis.ping()).await,
+                None => Check::disabled(),
+            }
+        },
+    );
+
+    let command_bus = match &bus {
+        Some(bus) => {
+            let lag = bus.lag();
+            let threshold = health
+                .as_ref()
+                .map(|h| h.bus_lag_threshold())
+                .unwrap_or(Duration::MAX);
+
+            Check {
+                status: if lag <= threshold { "up" } else { "down" },
+                latency_ms: 0.0,
+                error: (lag > threshold).then(|| "Command bus lag above threshold".to_owned()),
+                lag_ms: Some(millis(lag)),
+            }
+        }
+        None => Check::disabled(),
+    };
+
+    let ready = !draining && !postgres.is_down() && !command_bus.is_down();
+
+    let mut checks = BTreeMap::new();
+    checks.insert("postgres", postgres);
+    checks.insert("redis", redis);
+    checks.insert("command_bus", command_bus);
+
+    let body = Readiness {
+        status: if ready { "ready" } else { "not_ready" },
+        draining,
+        checks,
+    };
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+async fn probe<T, E, F>(name: &'static str, check: F) -> Check
+where
+    E: std::fmt::Display,
+    F: Future<Output = Result<T, E>>,
+{
+    let started = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check).await;
+    let latency_ms = millis(started.elapsed());
+
+    let error = match result {
+        Ok(Ok(_)) => None,
+        Ok(Err(error)) => {
+            tracing::warn!(check = name, error = %error, "readiness check failed");
+            Some("Unavailable".to_owned())
+        }
+        Err(_) => {
+            tracing::warn!(check = name, "readiness check timed out");
+            Some("Timed out".to_owned())
+        }
+    };
+
+    Check {
+        status: if error.is_none() { "up" } else { "down" },
+        latency_ms,
+        error,
+        lag_ms: None,
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,45 @@
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    common::{
+        error::{AppError, ErrorBody},
+        tenant::Tenant,
+    },
+    contexts::users::{
+        features::gdpr_response::accept,
+        infrastructure::gdpr_repo::{GdprJob, GdprKind, GdprRepo},
+    },
+};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(export_user_data);
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct UserPath {
+    user_id: i64,
+}
+
+#[utoipa::path(
+    tag = "users",
+    params(UserPath),
+    responses(
+        (status = 202, description = "The queued export, or the one still open for the user", body = GdprJob,
+            headers(("Location" = String, description = "`/gdpr/jobs/{job_id}`"))),
+        (status = 400, description = "Invalid or unknown user", body = ErrorBody),
+    )
+)]
+#[get("/users/{user_id}/data-export")]
+pub async fn export_user_data(
+    req: HttpRequest,
+    path: web::Path<UserPath>,
+    tenant: Tenant,
+    repo: web::Data<GdprRepo>,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("user_id", path.user_id);
+
+    accept(&req, &repo, GdprKind::Export, tenant, path.user_id).await
+}
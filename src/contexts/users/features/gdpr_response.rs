@@ -0,0 +1,29 @@
+use actix_web::{HttpRequest, HttpResponse, http::header::LOCATION};
+
+use crate::{
+    common::{error::AppError, request_id::current_request_id, tenant::Tenant},
+    contexts::users::infrastructure::gdpr_repo::{GdprKind, GdprRepo},
+};
+
+/// Queues the request with its audit details and answers 202 pointing at the
+/// job status.
+pub async fn accept(
+    req: &HttpRequest,
+    repo: &GdprRepo,
+    kind: GdprKind,
+    tenant: Tenant,
+    user_id: i64,
+) -> Result<HttpResponse, AppError> {
+    if !repo.user_exists(tenant.0, user_id).await? {
+        return Err(AppError::UserNotFound);
+    }
+
+    let client_addr = req.peer_addr().map(|addr| addr.ip().to_string());
+    let job = repo
+        .request(kind, tenant.0, user_id, current_request_id(), client_addr)
+        .await?;
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((LOCATION, format!("/gdpr/jobs/{}", job.id)))
+        .json(job))
+}
@@ -0,0 +1,12 @@
+use actix_web::web;
+
+pub mod erase_user_data;
+pub mod export_user_data;
+pub mod gdpr_response;
+pub mod read_gdpr_job;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.configure(erase_user_data::configure);
+    cfg.configure(export_user_data::configure);
+    cfg.configure(read_gdpr_job::configure);
+}
@@ -0,0 +1,110 @@
+use actix_web::{
+    HttpResponse, get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web,
+};
+use bytes::BytesMut;
+use serde::Deserialize;
+use tokio::{fs::File, io::AsyncReadExt};
+use utoipa::IntoParams;
+
+use crate::{
+    common::{
+        config::GdprConfig,
+        error::{AppError, ErrorBody},
+        tenant::Tenant,
+    },
+    contexts::users::infrastructure::{
+        gdpr::export_path,
+        gdpr_repo::{GdprJob, GdprRepo},
+    },
+};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(read_gdpr_job);
+    cfg.service(download_export);
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct JobPath {
+    job_id: i64,
+}
+
+#[utoipa::path(
+    tag = "users",
+    params(JobPath),
+    responses(
+        (status = 200, description = "The job", body = GdprJob),
+        (status = 404, description = "Unknown job", body = ErrorBody),
+    )
+)]
+#[get("/gdpr/jobs/{job_id}")]
+pub async fn read_gdpr_job(
+    path: web::Path<JobPath>,
+    tenant: Tenant,
+    repo: web::Data<GdprRepo>,
+) -> Result<HttpResponse, AppError> {
+    let job = repo
+        .get(tenant.0, path.job_id)
+        .await?
+        .ok_or(AppError::JobNotFound)?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[utoipa::path(
+    tag = "users",
+    params(JobPath),
+    responses(
+        (status = 200, description = "The export as JSON lines", body = String,
+            content_type = "application/x-ndjson"),
+        (status = 404, description = "Unknown export job", body = ErrorBody),
+        (status = 409, description = "The export is not finished", body = ErrorBody),
+        (status = 410, description = "The export is past `gdpr.export_ttl_hours`", body = ErrorBody),
+    )
+)]
+#[get("/gdpr/jobs/{job_id}/download")]
+pub async fn download_export(
+    path: web::Path<JobPath>,
+    tenant: Tenant,
+    repo: web::Data<GdprRepo>,
+    config: web::Data<GdprConfig>,
+) -> Result<HttpResponse, AppError> {
+    let job = repo
+        .get(tenant.0, path.job_id)
+        .await?
+        .filter(|job| job.kind == "export")
+        .ok_or(AppError::JobNotFound)?;
+
+    if !job.is_done() {
+        return Err(AppError::ExportNotReady);
+    }
+
+    let file = File::open(export_path(&config.export_dir, job.id))
+        .await
+        .map_err(|_| AppError::ExportExpired)?;
+
+    let body = futures::stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut chunk = BytesMut::with_capacity(CHUNK_SIZE);
+        match file.read_buf(&mut chunk).await {
+            Ok(0) => None,
+            Ok(_) => Some((Ok(chunk.freeze()), Some(file))),
+            Err(error) => Some((Err(error), None)),
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "user-{}.jsonl",
+                job.user_id
+            ))],
+        })
+        .streaming(body))
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Error};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    commands::export::{ExportFormat, ExportedEvent, write_event},
+    common::{
+        archive::{ArchivedEvent, Manifest, read_range, rewrite_range},
+        cache::{CacheDeleteKey, LeveledCache},
+        config::{ArchiveConfig, GdprConfig},
+        tenant::Tenant,
+    },
+    contexts::{
+        events::infrastructure::repo::EventsRepo,
+        users::infrastructure::gdpr_repo::{GdprJob, GdprRepo},
+    },
+};
+
+/// What an erasure request does to the user's events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureMode {
+    Delete,
+    /// Keep the events for the aggregates, moved to a tombstoned stand-in
+    /// user and with no metadata but `page`.
+    Anonymize,
+}
+
+// How often the worker looks for exports past `gdpr.export_ttl_hours`.
+const EXPORT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn export_path(dir: &Path, job_id: i64) -> PathBuf {
+    dir.join(format!("{}.jsonl", job_id))
+}
+
+/// Deletes the exports, finished or left half-written, last modified more
+/// than `ttl` ago. Returns how many were deleted.
+pub fn expire_exports(dir: &Path, ttl: Duration) -> Result<u64, Error> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut expired = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.ends_with(".jsonl") && !name.ends_with(".jsonl.tmp") {
+            continue;
+        }
+
+        let age = SystemTime::now()
+            .duration_since(entry.metadata()?.modified()?)
+            .unwrap_or_default();
+        if age > ttl {
+            fs::remove_file(entry.path())?;
+            expired += 1;
+        }
+    }
+
+    Ok(expired)
+}
+
+fn is_owned_by(event: &ArchivedEvent, tenant: Tenant, user_id: i64) -> bool {
+    event.tenant_id == tenant.0 && event.event.user_id == user_id
+}
+
+/// Removes or anonymises every event of the user, in Postgres and in the
+/// Parquet archives, then the user row, and drops the cache entries that may
+/// still show them. Safe to run again.
+pub async fn erase(
+    events: &EventsRepo,
+    gdpr: &GdprRepo,
+    cache: &LeveledCache,
+    config: &GdprConfig,
+    archive: &ArchiveConfig,
+    tenant: Tenant,
+    user_id: i64,
+) -> Result<u64, Error> {
+    let anonymous_id = match config.erasure {
+        ErasureMode::Delete => None,
+        ErasureMode::Anonymize => Some(gdpr.create_anonymous_user(tenant.0).await?),
+    };
+    // Parquet reads and writes block, so they stay off the runtime threads.
+    let archive = archive.clone();
+    let mut erased = tokio::task::spawn_blocking(move || {
+        erase_archived(&archive, tenant, user_id, anonymous_id)
+    })
+    .await??;
+
+    loop {
+        let count = match anonymous_id {
+            None => {
+                events
+                    .delete_user_events(tenant.0, user_id, config.batch_size as i64)
+                    .await?
+            }
+            Some(anonymous_id) => {
+                events
+                    .anonymize_user_events(
+                        tenant.0,
+                        user_id,
+                        anonymous_id,
+                        config.batch_size as i64,
+                    )
+                    .await?
+            }
+        };
+        if count == 0 {
+            break;
+        }
+        erased += count;
+    }
+
+    gdpr.delete_user(tenant.0, user_id).await?;
+
+    let _ = cache
+        .invalidate(CacheDeleteKey::Exact(tenant.user_events_key(user_id)))
+        .await;
+    for pattern in [tenant.stats_pattern(), tenant.page_pattern()] {
+        let _ = cache.invalidate(CacheDeleteKey::Pattern(pattern)).await;
+    }
+    for key in ["total_events", "users_id"] {
+        let _ = cache
+            .invalidate(CacheDeleteKey::Exact(tenant.key(key)))
+            .await;
+    }
+
+    Ok(erased)
+}
+
+// Parquet files cannot be edited in place: each file holding one of the
+// user's events is written again without them, or with them anonymised, and
+// its manifest entry updated. A file that cannot be read or rewritten fails
+// the job instead of leaving the user's events on disk.
+fn erase_archived(
+    archive: &ArchiveConfig,
+    tenant: Tenant,
+    user_id: i64,
+    anonymous_id: Option<i64>,
+) -> Result<u64, Error> {
+    let mut manifest = Manifest::load(&archive.dir)?;
+    let mut erased = 0u64;
+
+    for range in manifest.ranges.clone() {
+        let mut holds_user = false;
+        for events in read_range(&archive.dir, &range)? {
+            if events?
+                .iter()
+                .any(|event| is_owned_by(event, tenant, user_id))
+            {
+                holds_user = true;
+                break;
+            }
+        }
+        if !holds_user {
+            continue;
+        }
+
+        rewrite_range(
+            &archive.dir,
+            &mut manifest,
+            &range,
+            archive.batch_size,
+            |mut event| {
+                if !is_owned_by(&event, tenant, user_id) {
+                    return Some(event);
+                }
+                erased += 1;
+
+                event.event.user_id = anonymous_id?;
+                let page = event.event.metadata.get("page").cloned();
+                event.event.metadata = json!({ "page": page });
+                Some(event)
+            },
+        )
+        .with_context(|| format!("Cannot erase user {} from archive {}", user_id, range.file))?;
+    }
+
+    Ok(erased)
+}
+
+/// Writes everything stored for the user to `path` as JSON lines: the user
+/// row with its GDPR requests first, then one line per event, archived ones
+/// included.
+pub async fn export(
+    events: &EventsRepo,
+    gdpr: &GdprRepo,
+    archive: &ArchiveConfig,
+    tenant: Tenant,
+    user_id: i64,
+    path: &Path,
+) -> Result<u64, Error> {
+    let types: HashMap<i64, String> = events
+        .get_types(tenant.0)
+        .await?
+        .into_iter()
+        .map(|row| (row.id, row.name))
+        .collect();
+
+    let tmp = path.with_extension("jsonl.tmp");
+    let mut writer = BufWriter::new(
+        File::create(&tmp).with_context(|| format!("Cannot create {}", tmp.display()))?,
+    );
+
+    let header = json!({
+        "user": gdpr.get_user(tenant.0, user_id).await?,
+        "requests": gdpr.list_for_user(tenant.0, user_id).await?,
+    });
+    serde_json::to_writer(&mut writer, &header)?;
+    writeln!(writer)?;
+
+    // Archived periods are older than anything left in Postgres. Parquet
+    // reads block, so that pass runs off the runtime threads.
+    let archive = archive.clone();
+    let (mut writer, types, mut exported) = tokio::task::spawn_blocking(move || {
+        let exported = export_archived(&archive, tenant, user_id, &types, &mut writer)?;
+        Ok::<_, Error>((writer, types, exported))
+    })
+    .await??;
+
+    let mut stream = events.stream_user_events(tenant.0, user_id);
+
+    while let Some(event) = stream.try_next().await? {
+        let record = ExportedEvent {
+            id: event.id,
+            user_id: event.user_id,
+            event_type: types.get(&event.type_id).map_or("", String::as_str),
+            timestamp: event.timestamp,
+            metadata: &event.metadata,
+        };
+
+        write_event(&mut writer, ExportFormat::Jsonl, &record)?;
+        exported += 1;
+    }
+
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&tmp, path)?;
+
+    Ok(exported)
+}
+
+fn export_archived(
+    archive: &ArchiveConfig,
+    tenant: Tenant,
+    user_id: i64,
+    types: &HashMap<i64, String>,
+    writer: &mut impl Write,
+) -> Result<u64, Error> {
+    let mut exported = 0u64;
+
+    for range in Manifest::load(&archive.dir)?.ranges {
+        for archived in read_range(&archive.dir, &range)? {
+            for ArchivedEvent { event, .. } in archived?
+                .into_iter()
+                .filter(|event| is_owned_by(event, tenant, user_id))
+            {
+                let record = ExportedEvent {
+                    id: event.id,
+                    user_id: event.user_id,
+                    event_type: types.get(&event.type_id).map_or("", String::as_str),
+                    timestamp: event.timestamp,
+                    metadata: &event.metadata,
+                };
+
+                write_event(writer, ExportFormat::Jsonl, &record)?;
+                exported += 1;
+            }
+        }
+    }
+
+    Ok(exported)
+}
+
+async fn run_job(
+    events: &EventsRepo,
+    gdpr: &GdprRepo,
+    cache: &LeveledCache,
+    config: &GdprConfig,
+    archive: &ArchiveConfig,
+    job: &GdprJob,
+) -> Result<u64, Error> {
+    let tenant = Tenant(job.tenant_id);
+
+    match job.kind.as_str() {
+        "erasure" => erase(events, gdpr, cache, config, archive, tenant, job.user_id).await,
+        _ => {
+            fs::create_dir_all(&config.export_dir)
+                .with_context(|| format!("Cannot create {}", config.export_dir.display()))?;
+            export(
+                events,
+                gdpr,
+                archive,
+                tenant,
+                job.user_id,
+                &export_path(&config.export_dir, job.id),
+            )
+            .await
+        }
+    }
+}
+
+/// Works through the queued requests one at a time, polling every
+/// `gdpr.poll_interval_ms` while idle, and deletes expired exports.
+pub fn spawn_worker(
+    events: EventsRepo,
+    gdpr: GdprRepo,
+    cache: LeveledCache,
+    config: GdprConfig,
+    archive: ArchiveConfig,
+) {
+    tokio::spawn(async move {
+        let period = Duration::from_millis(config.poll_interval_ms);
+        let export_ttl = Duration::from_secs(config.export_ttl_hours * 3600);
+        let mut swept: Option<Instant> = None;
+
+        loop {
+            if swept.is_none_or(|swept| swept.elapsed() >= EXPORT_SWEEP_INTERVAL) {
+                match expire_exports(&config.export_dir, export_ttl) {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!(count, "expired gdpr exports deleted"),
+                    Err(error) => {
+                        tracing::error!(error = %format!("{:#}", error), "cannot delete expired gdpr exports")
+                    }
+                }
+                swept = Some(Instant::now());
+            }
+
+            let job = match gdpr.claim().await {
+                Ok(Some(job)) => job,
+                Ok(None) => {
+                    tokio::time::sleep(period).await;
+                    continue;
+                }
+                Err(error) => {
+                    tracing::error!(error = %format!("{:#}", error), "gdpr queue unavailable");
+                    tokio::time::sleep(period).await;
+                    continue;
+                }
+            };
+
+            let outcome = match run_job(&events, &gdpr, &cache, &config, &archive, &job).await {
+                Ok(count) => {
+                    tracing::info!(job_id = job.id, kind = %job.kind, events = count, "gdpr request done");
+                    gdpr.finish(job.id, count as i64).await
+                }
+                Err(error) => {
+                    let error = format!("{:#}", error);
+                    tracing::error!(job_id = job.id, kind = %job.kind, %error, "gdpr request failed");
+                    gdpr.fail(job.id, &error).await
+                }
+            };
+
+            if let Err(error) = outcome {
+                tracing::error!(job_id = job.id, error = %format!("{:#}", error), "cannot record gdpr outcome");
+                tokio::time::sleep(period).await;
+            }
+        }
+    });
+}
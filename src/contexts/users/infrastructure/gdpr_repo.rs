@@ -0,0 +1,288 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use sqlx::{Pool, Postgres, query, query_as, query_scalar};
+use utoipa::ToSchema;
+
+use crate::common::snowflake::next_id;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GdprKind {
+    Erasure,
+    Export,
+}
+
+impl GdprKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GdprKind::Erasure => "erasure",
+            GdprKind::Export => "export",
+        }
+    }
+}
+
+/// One erasure or export request; the row is its audit record.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct GdprJob {
+    #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
+    pub id: i64,
+    #[schema(example = "erasure")]
+    pub kind: String,
+    #[serde(skip_serializing)]
+    pub tenant_id: i64,
+    #[serde(serialize_with = "i64_to_string")]
+    #[schema(value_type = String)]
+    pub user_id: i64,
+    #[schema(example = "pending")]
+    pub status: String,
+    pub request_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub client_addr: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Events erased or exported, once done.
+    pub events: Option<i64>,
+    /// Kept for operators only; it may carry database text.
+    #[serde(skip_serializing)]
+    pub error: Option<String>,
+}
+
+impl GdprJob {
+    pub fn is_done(&self) -> bool {
+        self.status == "done"
+    }
+}
+
+#[derive(Serialize)]
+pub struct StoredUser {
+    #[serde(serialize_with = "i64_to_string")]
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+fn i64_to_string<S>(x: &i64, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&x.to_string())
+}
+
+#[derive(Clone)]
+pub struct GdprRepo {
+    postgres: Pool<Postgres>,
+}
+
+impl GdprRepo {
+    pub fn create(postgres: Pool<Postgres>) -> GdprRepo {
+        GdprRepo { postgres }
+    }
+
+    /// Tombstoned users count too: their data is still stored.
+    pub async fn user_exists(&self, tenant_id: i64, user_id: i64) -> Result<bool, anyhow::Error> {
+        let exists = query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM users WHERE tenant_id = $1 AND id = $2) AS "exists!""#,
+            tenant_id,
+            user_id
+        )
+        .fetch_one(&self.postgres)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn get_user(
+        &self,
+        tenant_id: i64,
+        user_id: i64,
+    ) -> Result<Option<StoredUser>, anyhow::Error> {
+        let user = query_as!(
+            StoredUser,
+            r#"SELECT id, name, created_at, deleted_at FROM users
+            WHERE tenant_id = $1 AND id = $2"#,
+            tenant_id,
+            user_id
+        )
+        .fetch_optional(&self.postgres)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Queues a request, or returns the one of the same kind still open for
+    /// the user.
+    pub async fn request(
+        &self,
+        kind: GdprKind,
+        tenant_id: i64,
+        user_id: i64,
+        request_id: Option<String>,
+        client_addr: Option<String>,
+    ) -> Result<GdprJob, anyhow::Error> {
+        if let Some(open) = query_as!(
+            GdprJob,
+            r#"SELECT id, kind, tenant_id, user_id, status, request_id, client_addr,
+                requested_at, started_at, finished_at, events, error
+            FROM gdpr_requests
+            WHERE tenant_id = $1 AND user_id = $2 AND kind = $3
+              AND status IN ('pending', 'running')
+            ORDER BY requested_at
+            LIMIT 1"#,
+            tenant_id,
+            user_id,
+            kind.as_str()
+        )
+        .fetch_optional(&self.postgres)
+        .await?
+        {
+            return Ok(open);
+        }
+
+        let job = query_as!(
+            GdprJob,
+            r#"INSERT INTO gdpr_requests (id, kind, tenant_id, user_id, request_id, client_addr)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, kind, tenant_id, user_id, status, request_id, client_addr,
+                requested_at, started_at, finished_at, events, error"#,
+            next_id(),
+            kind.as_str(),
+            tenant_id,
+            user_id,
+            request_id,
+            client_addr
+        )
+        .fetch_one(&self.postgres)
+        .await?;
+
+        Ok(job)
+    }
+
+    pub async fn get(&self, tenant_id: i64, id: i64) -> Result<Option<GdprJob>, anyhow::Error> {
+        let job = query_as!(
+            GdprJob,
+            r#"SELECT id, kind, tenant_id, user_id, status, request_id, client_addr,
+                requested_at, started_at, finished_at, events, error
+            FROM gdpr_requests
+            WHERE tenant_id = $1 AND id = $2"#,
+            tenant_id,
+            id
+        )
+        .fetch_optional(&self.postgres)
+        .await?;
+
+        Ok(job)
+    }
+
+    pub async fn list_for_user(
+        &self,
+        tenant_id: i64,
+        user_id: i64,
+    ) -> Result<Vec<GdprJob>, anyhow::Error> {
+        let jobs = query_as!(
+            GdprJob,
+            r#"SELECT id, kind, tenant_id, user_id, status, request_id, client_addr,
+                requested_at, started_at, finished_at, events, error
+            FROM gdpr_requests
+            WHERE tenant_id = $1 AND user_id = $2
+            ORDER BY requested_at"#,
+            tenant_id,
+            user_id
+        )
+        .fetch_all(&self.postgres)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    /// Takes the oldest pending job, or a running one whose worker died more
+    /// than an hour ago, and marks it running.
+    pub async fn claim(&self) -> Result<Option<GdprJob>, anyhow::Error> {
+        let job = query_as!(
+            GdprJob,
+            r#"UPDATE gdpr_requests
+            SET status = 'running', started_at = now()
+            WHERE id = (
+                SELECT id FROM gdpr_requests
+                WHERE status = 'pending'
+                   OR (status = 'running' AND started_at < now() - INTERVAL '1 hour')
+                ORDER BY requested_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, tenant_id, user_id, status, request_id, client_addr,
+                requested_at, started_at, finished_at, events, error"#
+        )
+        .fetch_optional(&self.postgres)
+        .await?;
+
+        Ok(job)
+    }
+
+    pub async fn finish(&self, id: i64, events: i64) -> Result<(), anyhow::Error> {
+        query!(
+            r#"UPDATE gdpr_requests
+            SET status = 'done', finished_at = now(), events = $2, error = NULL
+            WHERE id = $1"#,
+            id,
+            events
+        )
+        .execute(&self.postgres)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fail(&self, id: i64, error: &str) -> Result<(), anyhow::Error> {
+        query!(
+            r#"UPDATE gdpr_requests
+            SET status = 'failed', finished_at = now(), error = $2
+            WHERE id = $1"#,
+            id,
+            error
+        )
+        .execute(&self.postgres)
+        .await?;
+
+        Ok(())
+    }
+
+    /// A tombstoned stand-in that keeps anonymised events countable.
+    pub async fn create_anonymous_user(&self, tenant_id: i64) -> Result<i64, anyhow::Error> {
+        let id = query_scalar!(
+            r#"INSERT INTO users (id, name, deleted_at, tenant_id) VALUES ($1, 'anonymous', now(), $2)
+            RETURNING id"#,
+            next_id(),
+            tenant_id
+        )
+        .fetch_one(&self.postgres)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Blanks the name first, so a tombstone delete policy keeps nothing
+    /// personal either.
+    pub async fn delete_user(&self, tenant_id: i64, user_id: i64) -> Result<(), anyhow::Error> {
+        let mut tx = self.postgres.begin().await?;
+
+        query!(
+            r#"UPDATE users SET name = '' WHERE tenant_id = $1 AND id = $2"#,
+            tenant_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        query!(
+            r#"DELETE FROM users WHERE tenant_id = $1 AND id = $2"#,
+            tenant_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -1,8 +1,25 @@
-use actix_web::web;
+use actix_web::{middleware::from_fn, web};
 
+use crate::common::{auth::authenticate, error::AppError, rate_limit::rate_limit};
+
+pub mod commands;
 pub mod common;
 pub mod contexts;
 
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
-    cfg.configure(contexts::events::features::configure);
+    cfg.app_data(web::QueryConfig::default().error_handler(|err, _| AppError::from(err).into()));
+    cfg.app_data(web::PathConfig::default().error_handler(|err, _| AppError::from(err).into()));
+    cfg.app_data(web::JsonConfig::default().error_handler(|err, _| AppError::from(err).into()));
+
+    // Probes and metrics stay open for the orchestrator and Prometheus.
+    cfg.configure(contexts::system::features::configure);
+
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(rate_limit))
+            .wrap(from_fn(authenticate))
+            .configure(contexts::auth::features::configure)
+            .configure(contexts::events::features::configure)
+            .configure(contexts::users::features::configure),
+    );
 }
@@ -0,0 +1,189 @@
+use std::{fs, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use w_collider::{
+    common::archive::{
+        ArchiveWriter, ArchivedEvent, ArchivedRange, Manifest, MetadataKind, file_sha256,
+        read_archive, rewrite_range, stray_files,
+    },
+    contexts::events::infrastructure::repo::Event,
+};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("w_collider_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn at(text: &str) -> DateTime<Utc> {
+    text.parse().unwrap()
+}
+
+#[test]
+fn flattened_metadata_reads_back_unchanged() {
+    let dir = scratch_dir("roundtrip");
+    let path = dir.join("events.parquet");
+
+    let events = vec![
+        Event {
+            id: 1,
+            user_id: 10,
+            type_id: 3,
+            timestamp: at("2025-01-05T10:00:00.123456Z"),
+            metadata: json!({"page": "/home", "n": 3, "tags": ["a", "b"]}),
+        },
+        Event {
+            id: 2,
+            user_id: 11,
+            type_id: 4,
+            timestamp: at("2025-01-06T00:00:00Z"),
+            metadata: json!({"page": "/cart", "n": "three"}),
+        },
+        Event {
+            id: 3,
+            user_id: 12,
+            type_id: 4,
+            timestamp: at("2025-01-07T00:00:00Z"),
+            metadata: json!({}),
+        },
+    ];
+
+    let keys = vec![
+        ("n".to_owned(), MetadataKind::Json),
+        ("page".to_owned(), MetadataKind::Text),
+        ("tags".to_owned(), MetadataKind::Json),
+    ];
+    // A batch size below the row count exercises several row groups.
+    let mut writer = ArchiveWriter::create(&path, keys, 2).unwrap();
+    for (tenant_id, event) in events.iter().enumerate() {
+        writer
+            .push(ArchivedEvent {
+                tenant_id: tenant_id as i64,
+                event: Event {
+                    metadata: event.metadata.clone(),
+                    ..*event
+                },
+            })
+            .unwrap();
+    }
+    assert_eq!(writer.finish().unwrap(), 3);
+
+    let read: Vec<ArchivedEvent> = read_archive(&path)
+        .unwrap()
+        .flat_map(Result::unwrap)
+        .collect();
+
+    assert_eq!(read.len(), events.len());
+    for (tenant_id, (read, written)) in read.iter().zip(&events).enumerate() {
+        assert_eq!(read.tenant_id, tenant_id as i64);
+        let read = &read.event;
+        assert_eq!(read.id, written.id);
+        assert_eq!(read.user_id, written.user_id);
+        assert_eq!(read.type_id, written.type_id);
+        assert_eq!(read.timestamp, written.timestamp);
+        assert_eq!(read.metadata, written.metadata);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manifest_survives_a_save_and_reports_overlaps_and_strays() {
+    let dir = scratch_dir("manifest");
+    assert!(Manifest::load(&dir).unwrap().ranges.is_empty());
+
+    let manifest = Manifest {
+        ranges: vec![ArchivedRange {
+            file: "events_p2025_01.parquet".to_owned(),
+            from: at("2025-01-01T00:00:00Z"),
+            to: at("2025-02-01T00:00:00Z"),
+            rows: 42,
+            sha256: "00".to_owned(),
+            archived_at: at("2025-06-01T00:00:00Z"),
+        }],
+    };
+    manifest.save(&dir).unwrap();
+    fs::write(dir.join("events_p2025_01.parquet"), b"").unwrap();
+    fs::write(dir.join("events_p2025_02.parquet.tmp"), b"").unwrap();
+
+    let loaded = Manifest::load(&dir).unwrap();
+    assert_eq!(loaded.ranges, manifest.ranges);
+    assert!(loaded.covers(at("2025-01-15T00:00:00Z"), at("2025-03-01T00:00:00Z")));
+    assert!(!loaded.covers(at("2025-02-01T00:00:00Z"), at("2025-03-01T00:00:00Z")));
+
+    let stray = stray_files(&dir, &loaded).unwrap();
+    assert_eq!(stray, vec![dir.join("events_p2025_02.parquet.tmp")]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rewritten_ranges_keep_their_columns_and_update_the_manifest() {
+    let dir = scratch_dir("rewrite");
+    let file = "events_p2025_01.parquet";
+
+    let keys = vec![("page".to_owned(), MetadataKind::Text)];
+    let mut writer = ArchiveWriter::create(&dir.join(file), keys, 2).unwrap();
+    for (id, user_id) in [(1, 10), (2, 11), (3, 10)] {
+        writer
+            .push(ArchivedEvent {
+                tenant_id: 0,
+                event: Event {
+                    id,
+                    user_id,
+                    type_id: 1,
+                    timestamp: at("2025-01-05T10:00:00Z"),
+                    metadata: json!({"page": format!("/{}", id)}),
+                },
+            })
+            .unwrap();
+    }
+    let rows = writer.finish().unwrap();
+
+    let range = ArchivedRange {
+        file: file.to_owned(),
+        from: at("2025-01-01T00:00:00Z"),
+        to: at("2025-02-01T00:00:00Z"),
+        rows,
+        sha256: file_sha256(&dir.join(file)).unwrap(),
+        archived_at: at("2025-06-01T00:00:00Z"),
+    };
+    let mut manifest = Manifest {
+        ranges: vec![range.clone()],
+    };
+    manifest.save(&dir).unwrap();
+
+    let rewritten = rewrite_range(&dir, &mut manifest, &range, 2, |event| {
+        (event.event.user_id != 10).then_some(event)
+    })
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(rewritten.rows, 1);
+    assert_eq!(rewritten.sha256, file_sha256(&dir.join(file)).unwrap());
+    assert_eq!(
+        Manifest::load(&dir).unwrap().ranges,
+        vec![rewritten.clone()]
+    );
+    let read: Vec<ArchivedEvent> = read_archive(&dir.join(file))
+        .unwrap()
+        .flat_map(Result::unwrap)
+        .collect();
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].event.metadata, json!({"page": "/2"}));
+
+    // The old checksum no longer matches, so a stale range is refused.
+    assert!(rewrite_range(&dir, &mut manifest, &range, 2, Some).is_err());
+
+    assert!(
+        rewrite_range(&dir, &mut manifest, &rewritten, 2, |_| None)
+            .unwrap()
+            .is_none()
+    );
+    assert!(Manifest::load(&dir).unwrap().ranges.is_empty());
+    assert!(stray_files(&dir, &manifest).unwrap().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
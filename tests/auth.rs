@@ -0,0 +1,149 @@
+mod support;
+
+use actix_web::{
+    App, ResponseError,
+    http::{Method, StatusCode},
+    test::{self, TestRequest},
+};
+use serde_json::Value;
+use w_collider::{
+    common::{
+        auth::{
+            AuthenticatedKey, KEY_PREFIX, Scope, display_prefix, generate_key, hash_key,
+            required_scope,
+        },
+        error::AppError,
+    },
+    init_routes,
+};
+
+#[actix_web::test]
+async fn requests_without_a_key_are_refused_but_probes_stay_open() {
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(true))
+            .configure(init_routes),
+    )
+    .await;
+
+    for request in [
+        TestRequest::get().uri("/events"),
+        TestRequest::get().uri("/stats"),
+        TestRequest::post().uri("/event"),
+        TestRequest::get().uri("/admin/keys"),
+        TestRequest::get()
+            .uri("/events")
+            .insert_header(("Authorization", "Basic dXNlcjpwYXNz")),
+        TestRequest::get()
+            .uri("/events")
+            .insert_header(("X-Api-Key", " ")),
+    ] {
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get("www-authenticate").unwrap(),
+            "Bearer"
+        );
+
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["code"], "unauthorized");
+    }
+
+    let response = test::call_service(&app, TestRequest::get().uri("/healthz").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn requests_are_refused_when_keys_are_not_configured() {
+    let app = test::init_service(App::new().configure(init_routes)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/events").to_request()).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn routes_map_to_the_narrowest_scope() {
+    assert_eq!(required_scope(&Method::POST, "/event"), Scope::EventsWrite);
+    assert_eq!(required_scope(&Method::GET, "/events"), Scope::EventsRead);
+    assert_eq!(
+        required_scope(&Method::GET, "/users/{user_id}/events"),
+        Scope::EventsRead
+    );
+    assert_eq!(required_scope(&Method::GET, "/stats"), Scope::StatsRead);
+
+    assert_eq!(required_scope(&Method::GET, "/admin/keys"), Scope::Admin);
+    assert_eq!(
+        required_scope(&Method::DELETE, "/users/{user_id}/data"),
+        Scope::Admin
+    );
+    assert_eq!(required_scope(&Method::DELETE, "/events"), Scope::Admin);
+}
+
+#[test]
+fn admin_grants_every_scope() {
+    let reader = AuthenticatedKey {
+        id: 1,
+        scopes: vec![Scope::EventsRead],
+        tenant_id: None,
+    };
+    assert!(reader.allows(Scope::EventsRead));
+    assert!(!reader.allows(Scope::StatsRead));
+    assert!(!reader.allows(Scope::Admin));
+
+    let admin = AuthenticatedKey {
+        id: 2,
+        scopes: vec![Scope::Admin],
+        tenant_id: Some(3),
+    };
+    for scope in [
+        Scope::EventsWrite,
+        Scope::EventsRead,
+        Scope::StatsRead,
+        Scope::Admin,
+    ] {
+        assert!(admin.allows(scope));
+    }
+}
+
+#[test]
+fn scopes_parse_from_their_names() {
+    for scope in [
+        Scope::EventsWrite,
+        Scope::EventsRead,
+        Scope::StatsRead,
+        Scope::Admin,
+    ] {
+        assert_eq!(scope.as_str().parse::<Scope>().unwrap(), scope);
+    }
+    assert!("events:delete".parse::<Scope>().is_err());
+}
+
+#[test]
+fn keys_are_random_and_only_their_hash_is_kept() {
+    let (key, hash) = generate_key();
+    let (other, _) = generate_key();
+
+    assert!(key.starts_with(KEY_PREFIX));
+    assert_eq!(key.len(), KEY_PREFIX.len() + 64);
+    assert_ne!(key, other);
+
+    assert_eq!(hash, hash_key(&key));
+    assert_eq!(hash.len(), 64);
+    assert!(!hash.contains(&key[KEY_PREFIX.len()..]));
+    assert_eq!(display_prefix(&key), key[..KEY_PREFIX.len() + 8]);
+}
+
+#[test]
+fn auth_errors_have_their_own_status() {
+    assert_eq!(
+        AppError::Unauthorized.status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        AppError::Forbidden(Scope::StatsRead).status_code(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(AppError::Forbidden(Scope::StatsRead).code(), "forbidden");
+    assert_eq!(AppError::KeyNotFound.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(AppError::KeyNotFound.code(), "key_not_found");
+}
@@ -0,0 +1,200 @@
+use std::{sync::Arc, time::Duration};
+
+use w_collider::common::cache::{
+    CacheDeleteKey, CacheSetKey, CacheTopology, LeveledCache,
+    envelope::{Codec, Envelope},
+    memory_store::MemoryStore,
+    noop_store::NoopStore,
+    store::CacheStore,
+    tiered_store::TieredStore,
+};
+
+fn envelope() -> Envelope {
+    Envelope::create(Codec::Lz4, 64)
+}
+
+#[tokio::test]
+async fn memory_store_saves_and_reads_exact_keys() {
+    let cache = LeveledCache::create(Arc::new(MemoryStore::create(1)), envelope());
+
+    cache
+        .save(
+            CacheSetKey::Exact("total_events".into()),
+            b"42".to_vec(),
+            60,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        cache.try_get("total_events".into()).await,
+        Some(b"42".to_vec())
+    );
+
+    cache
+        .invalidate(CacheDeleteKey::Exact("total_events".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(cache.try_get("total_events".into()).await, None);
+}
+
+#[tokio::test]
+async fn pattern_invalidation_drops_every_tagged_key() {
+    let cache = LeveledCache::create(Arc::new(MemoryStore::create(1)), envelope());
+
+    for page in ["1", "2"] {
+        cache
+            .save(
+                CacheSetKey::Pattern("page_{}_{}".into(), vec![page.into(), "100".into()]),
+                page.as_bytes().to_vec(),
+                60,
+            )
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        cache.try_get("page_2_100".into()).await,
+        Some(b"2".to_vec())
+    );
+
+    cache
+        .invalidate(CacheDeleteKey::Pattern("page_{}_{}".into()))
+        .await
+        .unwrap();
+
+    assert_eq!(cache.try_get("page_1_100".into()).await, None);
+    assert_eq!(cache.try_get("page_2_100".into()).await, None);
+}
+
+#[tokio::test]
+async fn noop_store_never_hits() {
+    let cache = LeveledCache::create(Arc::new(NoopStore), envelope());
+
+    cache
+        .save(CacheSetKey::Exact("users_id".into()), b"[]".to_vec(), 60)
+        .await
+        .unwrap();
+
+    assert_eq!(cache.try_get("users_id".into()).await, None);
+}
+
+#[tokio::test]
+async fn tiered_store_backfills_l1_from_l2() {
+    let l1: Arc<dyn CacheStore> = Arc::new(MemoryStore::create(1));
+    let l2: Arc<dyn CacheStore> = Arc::new(MemoryStore::create(1));
+    let tiered = TieredStore::create(l1.clone(), l2.clone(), 60);
+
+    l2.set_ex("event_types", b"[]".to_vec(), 60).await.unwrap();
+    assert_eq!(l1.get("event_types").await.unwrap(), None);
+
+    assert_eq!(
+        tiered.get("event_types").await.unwrap(),
+        Some(b"[]".to_vec())
+    );
+    assert_eq!(l1.get("event_types").await.unwrap(), Some(b"[]".to_vec()));
+}
+
+#[tokio::test]
+async fn tiered_store_merges_tags_from_both_levels() {
+    let l1: Arc<dyn CacheStore> = Arc::new(MemoryStore::create(1));
+    let l2: Arc<dyn CacheStore> = Arc::new(MemoryStore::create(1));
+    let tiered = TieredStore::create(l1.clone(), l2.clone(), 60);
+
+    l1.tag("page_{}_{}", "page_1_100", 60).await.unwrap();
+    l2.tag("page_{}_{}", "page_2_100", 60).await.unwrap();
+
+    let mut keys = tiered.tagged("page_{}_{}").await.unwrap();
+    keys.sort();
+
+    assert_eq!(keys, vec!["page_1_100", "page_2_100"]);
+}
+
+#[tokio::test]
+async fn memory_store_drops_tags_of_keys_that_left_the_cache() {
+    let store = MemoryStore::create(1);
+    for (key, seconds) in [("page_1_100", 1), ("page_2_100", 60), ("page_3_100", 60)] {
+        store.set_ex(key, b"[]".to_vec(), seconds).await.unwrap();
+        store.tag("page_{}_{}", key, seconds).await.unwrap();
+    }
+
+    store.del("page_3_100").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    assert_eq!(
+        store.tagged("page_{}_{}").await.unwrap(),
+        vec!["page_2_100"]
+    );
+
+    store.del("page_2_100").await.unwrap();
+    assert!(store.tagged("page_{}_{}").await.unwrap().is_empty());
+}
+
+#[test]
+fn topology_parses_from_env_values() {
+    assert_eq!(
+        "tiered".parse::<CacheTopology>().unwrap(),
+        CacheTopology::Tiered
+    );
+    assert_eq!(
+        "NONE".parse::<CacheTopology>().unwrap(),
+        CacheTopology::None
+    );
+    assert!("memcached".parse::<CacheTopology>().is_err());
+    assert!(!CacheTopology::Memory.needs_redis());
+}
+
+#[test]
+fn envelope_round_trips_every_codec() {
+    let payload = br#"{"page":"/login"}"#.repeat(100);
+
+    for codec in [Codec::None, Codec::Lz4, Codec::Zstd] {
+        let envelope = Envelope::create(codec, 64);
+        let encoded = envelope.encode(&payload);
+
+        assert_eq!(envelope.decode(&encoded), Some(payload.clone()));
+        if codec != Codec::None {
+            assert!(encoded.len() < payload.len());
+        }
+    }
+}
+
+#[test]
+fn envelope_skips_compression_below_threshold() {
+    let envelope = Envelope::create(Codec::Zstd, 1024);
+    let encoded = envelope.encode(b"42");
+
+    assert_eq!(&encoded[encoded.len() - 2..], b"42");
+    assert_eq!(envelope.decode(&encoded), Some(b"42".to_vec()));
+}
+
+#[test]
+fn envelope_rejects_other_versions_and_corrupted_bytes() {
+    let current = envelope();
+    let mut previous = envelope();
+    previous.version -= 1;
+
+    let encoded = previous.encode(b"[]");
+    assert_eq!(current.decode(&encoded), None);
+
+    let mut corrupted = current.encode(b"[1,2,3]");
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0xff;
+    assert_eq!(current.decode(&corrupted), None);
+
+    assert_eq!(current.decode(b"[1,2,3]"), None);
+}
+
+#[tokio::test]
+async fn leveled_cache_treats_foreign_bytes_as_a_miss() {
+    let store: Arc<dyn CacheStore> = Arc::new(MemoryStore::create(1));
+    let cache = LeveledCache::create(store.clone(), envelope());
+
+    store
+        .set_ex("user_events_1", b"[]".to_vec(), 60)
+        .await
+        .unwrap();
+
+    assert_eq!(cache.try_get("user_events_1".into()).await, None);
+}
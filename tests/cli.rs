@@ -0,0 +1,134 @@
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use serde_json::json;
+use w_collider::{
+    commands::{
+        Cli, Command,
+        export::{ExportFormat, ExportedEvent, write_event},
+        migrate::MigrateAction,
+    },
+    common::{config::Config, seeder::SeedMode},
+};
+
+#[test]
+fn no_subcommand_means_serve() {
+    let cli = Cli::try_parse_from(["w_collider"]).unwrap();
+    assert!(cli.command.is_none());
+}
+
+#[test]
+fn serve_flags_override_config() {
+    let cli = Cli::try_parse_from([
+        "w_collider",
+        "serve",
+        "--bind",
+        "127.0.0.1:8080",
+        "--workers",
+        "4",
+    ])
+    .unwrap();
+
+    let Some(Command::Serve(args)) = cli.command else {
+        panic!("expected serve");
+    };
+
+    let mut config = Config::default();
+    args.apply(&mut config).unwrap();
+
+    assert_eq!(config.server.host, "127.0.0.1");
+    assert_eq!(config.server.port, 8080);
+    assert_eq!(config.server.workers, 4);
+}
+
+#[test]
+fn seed_counts_override_the_profile_and_must_be_positive() {
+    let cli = Cli::try_parse_from(["w_collider", "seed", "--events", "500"]).unwrap();
+    let Some(Command::Seed(args)) = cli.command else {
+        panic!("expected seed");
+    };
+    let profile = args.profile().unwrap();
+    assert_eq!(profile.users, 1000);
+    assert_eq!(profile.events, 500);
+    assert_eq!(profile.seed, None);
+
+    let error = Cli::try_parse_from(["w_collider", "seed", "--users", "0"])
+        .err()
+        .unwrap();
+    assert_eq!(error.exit_code(), 2);
+}
+
+#[test]
+fn seed_mode_flags_are_exclusive() {
+    let cli = Cli::try_parse_from(["w_collider", "seed", "--append", "--verify"]).unwrap();
+    let Some(Command::Seed(args)) = cli.command else {
+        panic!("expected seed");
+    };
+    assert_eq!(args.mode(), SeedMode::Append);
+    assert!(args.verify);
+
+    let cli = Cli::try_parse_from(["w_collider", "seed"]).unwrap();
+    let Some(Command::Seed(args)) = cli.command else {
+        panic!("expected seed");
+    };
+    assert_eq!(args.mode(), SeedMode::Fresh);
+    assert_eq!(args.tenant, 0);
+
+    let cli = Cli::try_parse_from(["w_collider", "seed", "--tenant", "7"]).unwrap();
+    let Some(Command::Seed(args)) = cli.command else {
+        panic!("expected seed");
+    };
+    assert_eq!(args.tenant, 7);
+
+    let error = Cli::try_parse_from(["w_collider", "seed", "--truncate", "--append"])
+        .err()
+        .unwrap();
+    assert_eq!(error.exit_code(), 2);
+}
+
+#[test]
+fn migrate_down_takes_a_target_and_global_options() {
+    let cli = Cli::try_parse_from([
+        "w_collider",
+        "migrate",
+        "down",
+        "--target",
+        "0",
+        "--set",
+        "log.level=warn",
+    ])
+    .unwrap();
+
+    assert_eq!(cli.overrides, vec!["log.level=warn"]);
+    let Some(Command::Migrate(args)) = cli.command else {
+        panic!("expected migrate");
+    };
+    assert!(matches!(
+        args.action,
+        MigrateAction::Down { target: Some(0) }
+    ));
+}
+
+#[test]
+fn export_escapes_csv_and_writes_json_lines() {
+    let metadata = json!({"page": "/a,b"});
+    let event = ExportedEvent {
+        id: 1,
+        user_id: 2,
+        event_type: "order.created",
+        timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+        metadata: &metadata,
+    };
+
+    let mut csv = vec![];
+    write_event(&mut csv, ExportFormat::Csv, &event).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "1,2,order.created,2025-01-01T00:00:00.000000Z,\"{\"\"page\"\":\"\"/a,b\"\"}\"\n"
+    );
+
+    let mut jsonl = vec![];
+    write_event(&mut jsonl, ExportFormat::Jsonl, &event).unwrap();
+    let line: serde_json::Value = serde_json::from_slice(&jsonl).unwrap();
+    assert_eq!(line["event_type"], "order.created");
+    assert_eq!(line["metadata"]["page"], "/a,b");
+}
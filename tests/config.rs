@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use w_collider::common::{
+    cache::{CacheTopology, envelope::Codec},
+    config::Config,
+    durability::{DurabilityProfile, OnUnsafe},
+    integrity::OnDelete,
+};
+
+const FILE: &str = r#"
+[server]
+port = 8080
+
+[bus]
+interval_ms = 250
+chunk_size = 500
+
+[cache]
+store = "memory"
+page_ttl = 30
+"#;
+
+fn env(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let pairs: HashMap<String, String> = pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    move |name| pairs.get(name).cloned()
+}
+
+#[test]
+fn file_then_env_then_overrides() {
+    let mut config = Config::from_toml(FILE).unwrap();
+    assert_eq!(config.server.port, 8080);
+    assert_eq!(config.bus.chunk_size, 500);
+    assert_eq!(config.cache.store, CacheTopology::Memory);
+    assert_eq!(config.cache.stats_ttl, 300);
+
+    config
+        .apply_env(env(&[("APP_PORT", "9090"), ("CACHE_CODEC", "zstd")]))
+        .unwrap();
+    assert_eq!(config.server.port, 9090);
+    assert_eq!(config.cache.codec, Codec::Zstd);
+
+    config
+        .apply_overrides(&[
+            "server.port=7070".to_owned(),
+            "bus.chunk_size = 10".to_owned(),
+        ])
+        .unwrap();
+    assert_eq!(config.server.port, 7070);
+    assert_eq!(config.bus.chunk_size, 10);
+    assert_eq!(config.bus.interval_ms, 250);
+
+    config.validate().unwrap();
+}
+
+#[test]
+fn bad_values_are_rejected_instead_of_defaulted() {
+    let mut config = Config::default();
+
+    let error = config
+        .apply_env(env(&[("BUS_CHUNK_SIZE", "lots")]))
+        .unwrap_err();
+    assert!(format!("{:#}", error).contains("BUS_CHUNK_SIZE"));
+
+    let error = config.set("cache.codec", "gzip").unwrap_err();
+    assert!(error.to_string().contains("unknown variant"));
+
+    assert!(config.set("cache.nope", "1").is_err());
+    assert!(Config::from_toml("[server]\nprot = 80\n").is_err());
+}
+
+#[test]
+fn validation_lists_every_problem() {
+    let mut config = Config::default();
+    config.postgres.min_connections = 10;
+    config.postgres.max_connections = 5;
+    config.bus.interval_ms = 0;
+    config.postgres.url = "not a url".to_owned();
+
+    let message = config.validate().unwrap_err().to_string();
+
+    assert!(message.contains("postgres.min_connections"));
+    assert!(message.contains("bus.interval_ms"));
+    assert!(message.contains("postgres.url"));
+}
+
+#[test]
+fn printed_config_redacts_the_database_password() {
+    let mut config = Config::default();
+    config.postgres.url = "postgres://app:hunter2@db:5432/app".to_owned();
+
+    let printed = config.redacted().to_toml().unwrap();
+
+    assert!(!printed.contains("hunter2"));
+    assert!(printed.contains("postgres://app:<redacted>@db:5432/app"));
+}
+
+#[test]
+fn integrity_policy_comes_from_file_or_env() {
+    let mut config =
+        Config::from_toml("[integrity]\nforeign_keys = true\non_delete = \"tombstone\"").unwrap();
+    assert!(config.integrity.foreign_keys);
+    assert_eq!(config.integrity.on_delete, OnDelete::Tombstone);
+
+    config
+        .apply_env(env(&[("INTEGRITY_ON_DELETE", "cascade")]))
+        .unwrap();
+    assert_eq!(config.integrity.on_delete, OnDelete::Cascade);
+
+    assert!(Config::from_toml("[integrity]\non_delete = \"ignore\"").is_err());
+    assert_eq!(Config::default().integrity.on_delete, OnDelete::Reject);
+}
+
+#[test]
+fn durability_defaults_to_benchmark_and_switches_by_env() {
+    let config = Config::default();
+    assert_eq!(config.durability.profile, DurabilityProfile::Benchmark);
+    assert!(!config.durability.profile.synchronous_commit());
+
+    let mut config = Config::default();
+    config
+        .apply_env(env(&[
+            ("DURABILITY_PROFILE", "production"),
+            ("DURABILITY_ON_UNSAFE", "warn"),
+        ]))
+        .unwrap();
+    assert_eq!(config.durability.profile, DurabilityProfile::Production);
+    assert_eq!(config.durability.on_unsafe, OnUnsafe::Warn);
+    assert!(config.durability.profile.synchronous_commit());
+
+    assert!(
+        Config::from_toml(
+            "[durability]
+profile = \"safe\""
+        )
+        .is_err()
+    );
+}
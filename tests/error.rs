@@ -0,0 +1,106 @@
+mod support;
+
+use actix_web::{
+    App, HttpResponse,
+    http::StatusCode,
+    middleware::from_fn,
+    test::{self, TestRequest},
+    web,
+};
+use serde_json::Value;
+use w_collider::{
+    common::{
+        error::AppError,
+        request_id::{REQUEST_ID_HEADER, request_id},
+    },
+    init_routes,
+};
+
+async fn failing_validation() -> Result<HttpResponse, AppError> {
+    Err(AppError::Validation {
+        field: "metadata.page",
+        message: "`metadata.page` is required and must be a string",
+    })
+}
+
+async fn failing_database() -> Result<HttpResponse, AppError> {
+    Err(AppError::Internal(anyhow::Error::msg(
+        "relation \"events\" does not exist",
+    )))
+}
+
+#[actix_web::test]
+async fn validation_errors_carry_code_field_and_request_id() {
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(request_id))
+            .route("/", web::post().to(failing_validation)),
+    )
+    .await;
+
+    let response = test::call_service(
+        &app,
+        TestRequest::post()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "req-42"))
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers().get(REQUEST_ID_HEADER).unwrap(), "req-42");
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["field"], "metadata.page");
+    assert_eq!(body["request_id"], "req-42");
+}
+
+#[actix_web::test]
+async fn internal_errors_do_not_leak_database_text() {
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(request_id))
+            .route("/", web::get().to(failing_database)),
+    )
+    .await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/").to_request()).await;
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let generated = response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "internal_error");
+    assert_eq!(body["request_id"], generated.as_str());
+    assert!(!body.to_string().contains("relation"));
+}
+
+#[actix_web::test]
+async fn malformed_query_strings_use_the_json_error_model() {
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(false))
+            .configure(init_routes),
+    )
+    .await;
+
+    let response = test::call_service(
+        &app,
+        TestRequest::get()
+            .uri("/stats?from=yesterday&to=today&e_type=user.login")
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "invalid_query");
+}
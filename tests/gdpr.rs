@@ -0,0 +1,201 @@
+mod support;
+
+use std::{
+    fs::{self, File},
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    App,
+    http::StatusCode,
+    test::{self, TestRequest},
+};
+use serde_json::{Value, json};
+use w_collider::{
+    common::{
+        cache::{
+            LeveledCache,
+            envelope::{Codec, Envelope},
+            noop_store::NoopStore,
+        },
+        config::{ArchiveConfig, Config, GdprConfig},
+        error::AppError,
+        snowflake::next_id,
+        tenant::Tenant,
+    },
+    contexts::{
+        events::infrastructure::repo::EventsRepo,
+        users::infrastructure::{
+            gdpr::{ErasureMode, erase, expire_exports, export_path},
+            gdpr_repo::GdprRepo,
+        },
+    },
+    init_routes,
+};
+
+#[actix_web::test]
+async fn gdpr_routes_reject_malformed_ids_before_touching_the_database() {
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(false))
+            .configure(init_routes),
+    )
+    .await;
+
+    for request in [
+        TestRequest::delete().uri("/users/abc/data"),
+        TestRequest::get().uri("/users/abc/data-export"),
+        TestRequest::get().uri("/gdpr/jobs/abc"),
+        TestRequest::get().uri("/gdpr/jobs/abc/download"),
+    ] {
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["code"], "invalid_query");
+    }
+}
+
+#[test]
+fn job_errors_have_their_own_status() {
+    use actix_web::ResponseError;
+
+    assert_eq!(AppError::JobNotFound.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(AppError::JobNotFound.code(), "job_not_found");
+    assert_eq!(AppError::ExportNotReady.status_code(), StatusCode::CONFLICT);
+    assert_eq!(AppError::ExportNotReady.code(), "export_not_ready");
+    assert_eq!(AppError::ExportExpired.status_code(), StatusCode::GONE);
+    assert_eq!(AppError::ExportExpired.code(), "export_expired");
+}
+
+#[test]
+fn erasure_deletes_by_default_and_exports_are_named_by_job() {
+    let mut config = Config::default();
+    assert_eq!(config.gdpr.erasure, ErasureMode::Delete);
+
+    config.set("gdpr.erasure", "anonymize").unwrap();
+    assert_eq!(config.gdpr.erasure, ErasureMode::Anonymize);
+
+    assert_eq!(
+        export_path(Path::new("exports"), 42),
+        Path::new("exports/42.jsonl")
+    );
+}
+
+#[test]
+fn only_exports_past_their_ttl_are_deleted() {
+    let dir = std::env::temp_dir().join(format!("w_collider_exports_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 3600);
+    for (name, modified) in [
+        ("1.jsonl", two_hours_ago),
+        ("2.jsonl.tmp", two_hours_ago),
+        ("3.jsonl", SystemTime::now()),
+        ("notes.txt", two_hours_ago),
+    ] {
+        File::create(dir.join(name))
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    assert_eq!(expire_exports(&dir, Duration::from_secs(3600)).unwrap(), 2);
+    let mut left: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    left.sort();
+    assert_eq!(left, vec!["3.jsonl", "notes.txt"]);
+
+    assert_eq!(
+        expire_exports(&dir.join("missing"), Duration::ZERO).unwrap(),
+        0
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn stats_still_group_anonymized_events_by_page() {
+    let Some(pool) = support::database().await else {
+        return;
+    };
+    // A tenant of its own keeps the rows apart from anything else there.
+    let tenant = Tenant(next_id());
+    let (user_id, type_id) = (next_id(), next_id());
+    let at: chrono::DateTime<chrono::Utc> = "2025-01-05T10:00:00Z".parse().unwrap();
+
+    sqlx::query("INSERT INTO users (id, name, tenant_id) VALUES ($1, 'alice', $2)")
+        .bind(user_id)
+        .bind(tenant.0)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO event_types (id, name, tenant_id) VALUES ($1, 'user.login', $2)")
+        .bind(type_id)
+        .bind(tenant.0)
+        .execute(&pool)
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        sqlx::query(
+            "INSERT INTO events (id, user_id, type_id, timestamp, metadata, tenant_id)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(next_id())
+        .bind(user_id)
+        .bind(type_id)
+        .bind(at)
+        .bind(json!({"page": "/login", "email": "alice@example.com"}))
+        .bind(tenant.0)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let events = EventsRepo::create(pool.clone());
+    let archive = ArchiveConfig {
+        dir: std::env::temp_dir().join(format!("w_collider_no_archive_{}", tenant.0)),
+        ..ArchiveConfig::default()
+    };
+    let erased = erase(
+        &events,
+        &GdprRepo::create(pool.clone()),
+        &LeveledCache::create(Arc::new(NoopStore), Envelope::create(Codec::None, 0)),
+        &GdprConfig {
+            erasure: ErasureMode::Anonymize,
+            ..GdprConfig::default()
+        },
+        &archive,
+        tenant,
+        user_id,
+    )
+    .await
+    .unwrap();
+    assert_eq!(erased, 2);
+
+    let stats = events
+        .stats(
+            tenant.0,
+            at - chrono::Duration::hours(1),
+            at + chrono::Duration::hours(1),
+            type_id,
+        )
+        .await
+        .unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].page, "/login");
+    assert_eq!(stats[0].page_count, 2);
+    assert_ne!(stats[0].user_id, user_id);
+
+    for table in ["events", "users", "event_types"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE tenant_id = $1", table))
+            .bind(tenant.0)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
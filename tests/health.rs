@@ -0,0 +1,188 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{
+    App,
+    test::{self, TestRequest},
+    web,
+};
+use redis::Client;
+use serde_json::Value;
+use sqlx::postgres::PgPoolOptions;
+use support::fake_redis::FakeRedis;
+use w_collider::{
+    common::{
+        cache::redis_store::{RedisSettings, RedisStore},
+        command_bus::{CommandBus, CommandValue},
+        health::Health,
+        snowflake::next_id,
+    },
+    init_routes,
+};
+
+fn settings() -> RedisSettings {
+    RedisSettings {
+        timeout: Duration::from_millis(200),
+        failure_threshold: 2,
+        cooldown: Duration::from_millis(100),
+    }
+}
+
+async fn readyz(
+    health: web::Data<Health>,
+    extra: impl Fn(&mut web::ServiceConfig),
+) -> (u16, Value) {
+    let app = test::init_service(
+        App::new()
+            .app_data(health)
+            .configure(extra)
+            .configure(init_routes),
+    )
+    .await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/readyz").to_request()).await;
+    let status = response.status().as_u16();
+    let body: Value = test::read_body_json(response).await;
+
+    (status, body)
+}
+
+#[actix_web::test]
+async fn liveness_is_cheap_and_always_up() {
+    let app = test::init_service(App::new().configure(init_routes)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/healthz").to_request()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["status"], "up");
+}
+
+#[actix_web::test]
+async fn readiness_reports_redis_ping_latency() {
+    let fake = FakeRedis::start().await;
+    let redis = RedisStore::connect(Client::open(fake.url()).unwrap(), settings()).await;
+    let health = web::Data::new(Health::create(Duration::from_secs(5)));
+
+    let (status, body) = readyz(health, move |cfg| {
+        cfg.app_data(web::Data::new(redis.clone()));
+    })
+    .await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["checks"]["redis"]["status"], "up");
+    assert!(body["checks"]["redis"]["latency_ms"].is_number());
+    assert_eq!(body["checks"]["postgres"]["status"], "disabled");
+}
+
+#[actix_web::test]
+async fn readiness_keeps_serving_when_only_redis_is_down() {
+    let fake = FakeRedis::start().await;
+    let redis = RedisStore::connect(Client::open(fake.url()).unwrap(), settings()).await;
+    fake.go_down();
+    let health = web::Data::new(Health::create(Duration::from_secs(5)));
+
+    let (status, body) = readyz(health, move |cfg| {
+        cfg.app_data(web::Data::new(redis.clone()));
+    })
+    .await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["checks"]["redis"]["status"], "down");
+}
+
+#[actix_web::test]
+async fn readiness_fails_when_postgres_is_unreachable() {
+    let pool = PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(200))
+        .connect_lazy("postgres://nobody@127.0.0.1:1/none")
+        .unwrap();
+    let health = web::Data::new(Health::create(Duration::from_secs(5)));
+
+    let (status, body) = readyz(health, move |cfg| {
+        cfg.app_data(web::Data::new(pool.clone()));
+    })
+    .await;
+
+    assert_eq!(status, 503);
+    assert_eq!(body["checks"]["postgres"]["status"], "down");
+    assert_eq!(body["checks"]["postgres"]["error"], "Unavailable");
+}
+
+#[actix_web::test]
+async fn readiness_fails_when_command_bus_lags() {
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://nobody@127.0.0.1:1/none")
+        .unwrap();
+    let bus = Arc::new(CommandBus::init(
+        Duration::from_secs(3600),
+        2000,
+        false,
+        pool,
+    ));
+    bus.push("SELECT 1", vec![CommandValue::Int(1)], None).await;
+    tokio::time::sleep(Duration::from_millis(30)).await;
+
+    let health = web::Data::new(Health::create(Duration::from_millis(10)));
+
+    let (status, body) = readyz(health, move |cfg| {
+        cfg.app_data(web::Data::new(bus.clone()));
+    })
+    .await;
+
+    assert_eq!(status, 503);
+    assert_eq!(body["checks"]["command_bus"]["status"], "down");
+    assert!(body["checks"]["command_bus"]["lag_ms"].as_f64().unwrap() >= 10.0);
+}
+
+#[actix_web::test]
+async fn readiness_flips_while_draining() {
+    let health = web::Data::new(Health::create(Duration::from_secs(5)));
+    health.start_draining();
+
+    let (status, body) = readyz(health, |_| {}).await;
+
+    assert_eq!(status, 503);
+    assert_eq!(body["draining"], true);
+    assert_eq!(body["status"], "not_ready");
+}
+
+#[actix_web::test]
+async fn shutdown_flushes_what_the_bus_still_queues() {
+    let Some(pool) = support::database().await else {
+        return;
+    };
+    let tenant = next_id();
+
+    // The interval never ticks again within the test, only shutdown flushes.
+    let bus = CommandBus::init(Duration::from_secs(3600), 2000, false, pool.clone());
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    bus.push(
+        "INSERT INTO users (id, name, tenant_id) SELECT * FROM UNNEST($1::bigint[], $2::text[], $3::bigint[]) RETURNING id",
+        vec![
+            CommandValue::Int(next_id()),
+            CommandValue::Str("draining".to_owned()),
+            CommandValue::Int(tenant),
+        ],
+        None,
+    )
+    .await;
+
+    bus.shutdown().await;
+
+    let stored: i64 = sqlx::query_scalar("SELECT count(*) FROM users WHERE tenant_id = $1")
+        .bind(tenant)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 1);
+    assert_eq!(bus.lag(), Duration::ZERO);
+
+    sqlx::query("DELETE FROM users WHERE tenant_id = $1")
+        .bind(tenant)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
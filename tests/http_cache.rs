@@ -0,0 +1,66 @@
+use actix_web::{
+    http::{
+        StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    },
+    test::TestRequest,
+};
+use w_collider::common::http_cache::{cached_response, etag};
+
+#[test]
+fn etag_is_strong_and_stable_for_identical_bytes() {
+    let tag = etag(br#"{"data":[]}"#);
+
+    assert!(tag.starts_with('"') && tag.ends_with('"'));
+    assert_eq!(tag, etag(br#"{"data":[]}"#));
+    assert_ne!(tag, etag(br#"{"data":[1]}"#));
+}
+
+#[test]
+fn full_body_carries_etag_and_max_age() {
+    let req = TestRequest::default().to_http_request();
+    let response = cached_response(&req, b"[]".to_vec(), 300);
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(ETAG).unwrap(), etag(b"[]").as_str());
+    assert_eq!(
+        response.headers().get(CACHE_CONTROL).unwrap(),
+        "max-age=300"
+    );
+}
+
+#[test]
+fn matching_if_none_match_answers_not_modified() {
+    let tag = etag(b"[]");
+
+    for header in [
+        tag.clone(),
+        format!("W/{}", tag),
+        format!("\"other\", {}", tag),
+        "*".into(),
+    ] {
+        let req = TestRequest::default()
+            .insert_header((IF_NONE_MATCH, header))
+            .to_http_request();
+        let response = cached_response(&req, b"[]".to_vec(), 300);
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(ETAG).unwrap(), tag.as_str());
+        assert_eq!(
+            response.headers().get(CACHE_CONTROL).unwrap(),
+            "max-age=300"
+        );
+    }
+}
+
+#[test]
+fn stale_if_none_match_returns_the_body() {
+    let req = TestRequest::default()
+        .insert_header((IF_NONE_MATCH, etag(b"[1]")))
+        .to_http_request();
+
+    assert_eq!(
+        cached_response(&req, b"[]".to_vec(), 300).status(),
+        StatusCode::OK
+    );
+}
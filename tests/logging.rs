@@ -0,0 +1,50 @@
+use actix_web::{
+    App,
+    middleware::from_fn,
+    test::{self, TestRequest},
+};
+use w_collider::{
+    common::{
+        logging::{LogFormat, trace_request},
+        output::{send_group, send_message},
+        request_id::request_id,
+    },
+    init_routes,
+};
+
+#[test]
+fn log_format_parses_case_insensitively() {
+    assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    assert_eq!("pretty".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+    assert!("xml".parse::<LogFormat>().is_err());
+}
+
+#[test]
+fn console_tree_tolerates_messages_without_group() {
+    send_message("orphan".to_owned());
+    send_group("Group".to_owned());
+    send_message("├─ child".to_owned());
+}
+
+#[actix_web::test]
+async fn request_span_keeps_response_intact() {
+    let app = test::init_service(
+        App::new()
+            .wrap(from_fn(trace_request))
+            .wrap(from_fn(request_id))
+            .configure(init_routes),
+    )
+    .await;
+
+    let response = test::call_service(
+        &app,
+        TestRequest::get()
+            .uri("/metrics")
+            .insert_header(("x-request-id", "trace-me"))
+            .to_request(),
+    )
+    .await;
+
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("x-request-id").unwrap(), "trace-me");
+}
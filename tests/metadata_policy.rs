@@ -0,0 +1,356 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{
+    App,
+    http::StatusCode,
+    test::{self, TestRequest},
+    web,
+};
+use serde_json::{Value, json};
+use sqlx::postgres::PgPoolOptions;
+use w_collider::{
+    common::{
+        cache::{
+            CacheSetKey, LeveledCache,
+            envelope::{Codec, Envelope},
+            memory_store::MemoryStore,
+        },
+        command_bus::CommandBus,
+        config::{Config, MetadataConfig},
+        metadata_policy::{FieldAction, MetadataPolicies, Policy},
+        metrics::METRICS,
+        seed_profile::{EventGenerator, SeedProfile},
+        tenant::Tenant,
+    },
+    contexts::events::infrastructure::{
+        cached_projection::{CacheTtls, EventsProj},
+        repo::EventsRepo,
+    },
+    init_routes,
+};
+
+fn object(value: Value) -> serde_json::Map<String, Value> {
+    match value {
+        Value::Object(object) => object,
+        _ => unreachable!(),
+    }
+}
+
+fn policies() -> MetadataPolicies {
+    let mut config = MetadataConfig {
+        hmac_key: "secret".to_owned(),
+        ..MetadataConfig::default()
+    };
+    config.policies.insert(
+        "user.*".to_owned(),
+        Policy::from([
+            ("page".to_owned(), FieldAction::Allow),
+            ("email".to_owned(), FieldAction::Hmac),
+            ("ip".to_owned(), FieldAction::Truncate),
+            ("password".to_owned(), FieldAction::Drop),
+        ]),
+    );
+    config.policies.insert(
+        "order.*".to_owned(),
+        Policy {
+            keep_unlisted: true,
+            ..Policy::from([
+                ("card".to_owned(), FieldAction::Drop),
+                ("password".to_owned(), FieldAction::Drop),
+            ])
+        },
+    );
+
+    MetadataPolicies::create(&config).unwrap()
+}
+
+#[test]
+fn types_no_policy_matches_keep_only_the_page() {
+    let mut config = MetadataConfig::default();
+    config.policies.clear();
+    let policies = MetadataPolicies::create(&config).unwrap();
+    let unlisted_before = METRICS
+        .metadata_violations
+        .with_label_values(&["", "unlisted"])
+        .get();
+
+    let metadata = json!({"page": "/cart", "email": "a@example.com", "n": 3});
+    let kept = policies.apply("order.created", object(metadata));
+
+    assert_eq!(Value::Object(kept), json!({"page": "/cart"}));
+    assert_eq!(
+        METRICS
+            .metadata_violations
+            .with_label_values(&["", "unlisted"])
+            .get(),
+        unlisted_before + 2
+    );
+}
+
+#[test]
+fn unlisted_fields_are_dropped_unless_the_policy_keeps_them() {
+    let policies = policies();
+
+    let kept = policies.apply(
+        "order.created",
+        object(json!({"page": "/cart", "card": "4242", "extra": true})),
+    );
+    assert_eq!(Value::Object(kept), json!({"page": "/cart", "extra": true}));
+
+    let kept = policies.apply(
+        "user.login",
+        object(json!({"page": "/login", "extra": true})),
+    );
+    assert_eq!(Value::Object(kept), json!({"page": "/login"}));
+}
+
+#[test]
+fn keep_unlisted_reads_from_the_policy_table() {
+    let config: MetadataConfig = toml::from_str(
+        r#"
+        [policies."user.*"]
+        page = "allow"
+
+        [policies."order.*"]
+        keep_unlisted = true
+        card = "drop"
+        "#,
+    )
+    .unwrap();
+
+    assert!(!config.policies["user.*"].keep_unlisted);
+    assert_eq!(config.policies["user.*"].fields["page"], FieldAction::Allow);
+    assert!(config.policies["order.*"].keep_unlisted);
+}
+
+#[test]
+fn policies_must_keep_the_page() {
+    for (policy, problem) in [
+        (
+            Policy::from([("page".to_owned(), FieldAction::Drop)]),
+            "must not drop or truncate `page`",
+        ),
+        (
+            Policy {
+                keep_unlisted: true,
+                ..Policy::from([("page".to_owned(), FieldAction::Truncate)])
+            },
+            "must not drop or truncate `page`",
+        ),
+        (
+            Policy::from([("email".to_owned(), FieldAction::Drop)]),
+            "must list `page`",
+        ),
+    ] {
+        let mut config = Config::default();
+        config.metadata.policies.insert("user.*".to_owned(), policy);
+
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("metadata.policies.\"user.*\""), "{}", error);
+        assert!(error.contains(problem), "{}", error);
+    }
+
+    let mut config = Config::default();
+    config.metadata.policies.insert(
+        "order.*".to_owned(),
+        Policy {
+            keep_unlisted: true,
+            ..Policy::from([("card".to_owned(), FieldAction::Drop)])
+        },
+    );
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn the_most_specific_policy_hashes_truncates_and_drops() {
+    let policies = policies();
+    let hashed_before = METRICS
+        .metadata_violations
+        .with_label_values(&["user.*", "hashed"])
+        .get();
+
+    let kept = policies.apply(
+        "user.login",
+        object(json!({
+            "page": "/login",
+            "email": "a@example.com",
+            "ip": "203.0.113.77",
+            "password": "hunter2",
+            "extra": true
+        })),
+    );
+
+    assert_eq!(kept["page"], "/login");
+    assert_eq!(kept["ip"], "203.0.113.0");
+    assert!(!kept.contains_key("password"));
+    assert!(!kept.contains_key("extra"));
+
+    let email = kept["email"].as_str().unwrap();
+    assert_eq!(email.len(), 64);
+    assert_ne!(email, "a@example.com");
+    assert_eq!(
+        policies.apply("user.logout", object(json!({"email": "a@example.com"})))["email"],
+        email
+    );
+
+    assert_eq!(
+        METRICS
+            .metadata_violations
+            .with_label_values(&["user.*", "hashed"])
+            .get(),
+        hashed_before + 2
+    );
+}
+
+#[test]
+fn truncation_keeps_network_prefixes_and_drops_anything_else() {
+    let policies = policies();
+
+    let kept = policies.apply("user.login", object(json!({"ip": "2001:db8:abcd:12::1"})));
+    assert_eq!(kept["ip"], "2001:db8:abcd::");
+
+    let kept = policies.apply("user.login", object(json!({"ip": "not an address"})));
+    assert!(kept.is_empty());
+}
+
+#[test]
+fn hmac_fields_need_a_key() {
+    let mut config = Config::default();
+    config.metadata.policies.insert(
+        "*".to_owned(),
+        Policy::from([
+            ("page".to_owned(), FieldAction::Allow),
+            ("email".to_owned(), FieldAction::Hmac),
+        ]),
+    );
+
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("metadata.hmac_key"), "{}", error);
+
+    config.set("metadata.hmac_key", "secret").unwrap();
+    assert!(config.validate().is_ok());
+    assert!(!format!("{:?}", config.redacted()).contains("secret"));
+}
+
+#[test]
+fn seeded_metadata_goes_through_the_policies() {
+    let profile = SeedProfile {
+        seed: Some(7),
+        end: chrono::DateTime::from_timestamp(1_735_689_600, 0),
+        users: 2,
+        types: 2,
+        events: 20,
+        templates: vec![
+            json!({"page": "/home", "email": "{user_id}@example.com"}),
+            json!({"page": "/login", "password": "hunter2"}),
+        ],
+        ..SeedProfile::default()
+    };
+
+    let mut generator = EventGenerator::create(&profile, vec![1, 2], vec![10, 20])
+        .unwrap()
+        .with_policies(
+            policies(),
+            vec!["user.login".to_owned(), "order.created".to_owned()],
+        )
+        .unwrap();
+
+    while let Some(event) = generator.next_event() {
+        let metadata: Value = serde_json::from_str(&event.metadata).unwrap();
+        assert!(metadata.get("password").is_none());
+
+        // `order.created` has no `email` action, so it keeps the address.
+        match event.type_id {
+            10 => assert!(
+                metadata
+                    .get("email")
+                    .is_none_or(|email| !email.as_str().unwrap().contains('@'))
+            ),
+            _ => assert!(
+                metadata
+                    .get("email")
+                    .is_none_or(|email| email.as_str().unwrap().contains('@'))
+            ),
+        }
+        assert!(metadata["page"].is_string());
+    }
+}
+
+#[actix_web::test]
+async fn the_default_config_stores_only_the_page() {
+    let cache = LeveledCache::create(
+        Arc::new(MemoryStore::create(1)),
+        Envelope::create(Codec::Lz4, 64),
+    );
+    for (key, value) in [
+        ("users_id", json!([42])),
+        (
+            "event_types",
+            json!([{"id": 1, "name": "order.created", "deleted": false}]),
+        ),
+    ] {
+        cache
+            .save(
+                CacheSetKey::Exact(Tenant(0).key(key)),
+                serde_json::to_vec(&value).unwrap(),
+                60,
+            )
+            .await
+            .unwrap();
+    }
+
+    // Neither the repo nor the bus ever reach this pool in the test.
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .expect("lazy pool");
+    let proj = EventsProj::create(
+        cache.clone(),
+        EventsRepo::create(pool.clone()),
+        CacheTtls::default(),
+    );
+    let bus = Arc::new(CommandBus::init(
+        Duration::from_secs(3600),
+        2000,
+        false,
+        pool,
+    ));
+    let policies = MetadataPolicies::create(&Config::default().metadata).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(false))
+            .app_data(web::Data::new(proj))
+            .app_data(web::Data::new(bus))
+            .app_data(web::Data::new(cache))
+            .app_data(web::Data::new(policies))
+            .configure(init_routes),
+    )
+    .await;
+
+    let metadata = json!({
+        "page": "/cart",
+        "email": "a@example.com",
+        "items": [{"sku": "A-1", "qty": 2}],
+        "coupon": null,
+        "total": 19.5
+    });
+    let response = test::call_service(
+        &app,
+        TestRequest::post()
+            .uri("/event")
+            .set_json(json!({
+                "user_id": 42,
+                "event_type": "order.created",
+                "timestamp": "2025-01-05T10:00:00Z",
+                "metadata": metadata
+            }))
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["metadata"], json!({"page": "/cart"}));
+}
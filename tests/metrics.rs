@@ -0,0 +1,71 @@
+use actix_web::{
+    App,
+    middleware::from_fn,
+    test::{self, TestRequest},
+};
+use w_collider::{
+    common::{
+        cache::{memory_store::MemoryStore, store::CacheStore},
+        metrics::{METRICS, Metrics, key_family, track_http},
+    },
+    init_routes,
+};
+
+#[test]
+fn cache_keys_collapse_into_bounded_families() {
+    assert_eq!(key_family("page_0_3_100"), "page");
+    assert_eq!(key_family("user_events_0_42"), "user_events");
+    assert_eq!(key_family("users_id_0"), "users_id");
+    assert_eq!(
+        key_family("events_stat_0_2025-01-01_2025-01-02_7"),
+        "events_stat"
+    );
+    assert_eq!(key_family("something_else"), "other");
+}
+
+#[test]
+fn registry_renders_prometheus_text() {
+    let metrics = Metrics::create();
+
+    metrics.observe_cache("l1", "page_1_100", true);
+    metrics.observe_cache("l2", "page_1_100", false);
+    metrics.bus_queue_depth.set(3);
+    metrics.bus_rows_per_flush.observe(2000.0);
+
+    let text = metrics.render();
+
+    assert!(text.contains(r#"cache_lookups_total{family="page",level="l1",result="hit"} 1"#));
+    assert!(text.contains(r#"cache_lookups_total{family="page",level="l2",result="miss"} 1"#));
+    assert!(text.contains("command_bus_queue_depth 3"));
+    assert!(text.contains("command_bus_rows_per_flush_count 1"));
+}
+
+#[tokio::test]
+async fn memory_store_reports_l1_lookups() {
+    let store = MemoryStore::create(1);
+    store
+        .set_ex("event_types", b"[]".to_vec(), 60)
+        .await
+        .unwrap();
+    store.get("event_types").await.unwrap();
+
+    let hits = METRICS
+        .cache_lookups
+        .with_label_values(&["l1", "event_types", "hit"])
+        .get();
+    assert!(hits >= 1);
+}
+
+#[actix_web::test]
+async fn metrics_endpoint_reports_route_latency_and_status() {
+    let app = test::init_service(App::new().wrap(from_fn(track_http)).configure(init_routes)).await;
+
+    test::call_service(&app, TestRequest::get().uri("/metrics").to_request()).await;
+    let response = test::call_service(&app, TestRequest::get().uri("/metrics").to_request()).await;
+
+    assert!(response.status().is_success());
+
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    assert!(body.contains(r#"http_requests_total{method="GET",route="/metrics",status="200"}"#));
+    assert!(body.contains(r#"http_request_duration_seconds_bucket{method="GET",route="/metrics""#));
+}
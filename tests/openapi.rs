@@ -0,0 +1,145 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use actix_web::{
+    App,
+    http::StatusCode,
+    test::{self, TestRequest},
+};
+use serde_json::Value;
+use utoipa::OpenApi;
+use w_collider::{common::openapi::ApiDoc, init_routes};
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+// Every `#[get("/path")]` style route attribute under `dir`.
+fn declared_routes(dir: &Path, routes: &mut BTreeSet<(String, String)>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            declared_routes(&path, routes);
+            continue;
+        }
+        if path.extension().is_none_or(|extension| extension != "rs") {
+            continue;
+        }
+
+        for line in fs::read_to_string(&path).unwrap().lines() {
+            for method in METHODS {
+                let Some(rest) = line.trim().strip_prefix(&format!("#[{}(\"", method)) else {
+                    continue;
+                };
+                let route = rest.split('"').next().unwrap();
+                routes.insert((method.to_uppercase(), route.to_owned()));
+            }
+        }
+    }
+}
+
+fn documented_routes() -> BTreeSet<(String, String)> {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+    let mut routes = BTreeSet::new();
+    for (route, item) in spec["paths"].as_object().unwrap() {
+        for method in METHODS {
+            if item.get(method).is_some() {
+                routes.insert((method.to_uppercase(), route.clone()));
+            }
+        }
+    }
+    routes
+}
+
+#[test]
+fn every_route_is_in_the_spec_and_nothing_else() {
+    let mut declared = BTreeSet::new();
+    declared_routes(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+        &mut declared,
+    );
+    let documented = documented_routes();
+
+    let missing: Vec<_> = declared.difference(&documented).collect();
+    let stale: Vec<_> = documented.difference(&declared).collect();
+    assert!(
+        missing.is_empty() && stale.is_empty(),
+        "routes missing from the spec: {:?}, spec entries without a route: {:?}",
+        missing,
+        stale
+    );
+}
+
+#[test]
+fn api_docs_only_name_routes_in_the_spec() {
+    let documented = documented_routes();
+    let text =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("docs/api.md")).unwrap();
+
+    let mut named = 0;
+    for heading in text.lines().filter_map(|line| line.strip_prefix("#### `")) {
+        let Some((method, route)) = heading.trim_end_matches('`').split_once(' ') else {
+            continue;
+        };
+        let route = route.split('?').next().unwrap();
+
+        assert!(
+            documented.contains(&(method.to_owned(), route.to_owned())),
+            "docs/api.md documents `{} {}`, which is not a route",
+            method,
+            route
+        );
+        named += 1;
+    }
+    assert!(named > 0);
+}
+
+#[test]
+fn spec_names_the_request_and_response_bodies() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let schemas = &spec["components"]["schemas"];
+
+    for name in [
+        "CreateEventRequest",
+        "PaginatedEvents",
+        "Stat",
+        "EventWithType",
+        "ErrorBody",
+    ] {
+        assert!(schemas.get(name).is_some(), "no `{}` schema", name);
+    }
+    assert_eq!(
+        spec["paths"]["/event"]["post"]["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/CreateEventRequest"
+    );
+    assert!(
+        spec["paths"]["/stats"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|parameter| parameter["name"] == "x-tenant-id")
+    );
+    assert!(spec["paths"]["/healthz"]["get"]["parameters"].is_null());
+}
+
+#[actix_web::test]
+async fn spec_and_viewer_are_served_without_a_key() {
+    let app = test::init_service(App::new().configure(init_routes)).await;
+
+    let response =
+        test::call_service(&app, TestRequest::get().uri("/openapi.json").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let spec: Value = test::read_body_json(response).await;
+    assert!(spec["openapi"].as_str().unwrap().starts_with('3'));
+    assert!(spec["paths"]["/event"]["post"].is_object());
+
+    let response = test::call_service(&app, TestRequest::get().uri("/docs/").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+}
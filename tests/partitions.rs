@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use w_collider::common::partitions::{PartitionInterval, parse_bound};
+
+fn at(text: &str) -> DateTime<Utc> {
+    text.parse().unwrap()
+}
+
+#[test]
+fn monthly_periods_start_on_the_first_and_roll_over_the_year() {
+    let interval = PartitionInterval::Monthly;
+    let start = interval.start_of(at("2024-12-17T13:45:00Z"));
+
+    assert_eq!(start, at("2024-12-01T00:00:00Z"));
+    assert_eq!(interval.next(start), at("2025-01-01T00:00:00Z"));
+    assert_eq!(interval.previous(start), at("2024-11-01T00:00:00Z"));
+    assert_eq!(interval.name(start), "events_p2024_12");
+}
+
+#[test]
+fn daily_periods_start_at_midnight_utc() {
+    let interval = PartitionInterval::Daily;
+    let start = interval.start_of(at("2024-02-28T23:59:59Z"));
+
+    assert_eq!(start, at("2024-02-28T00:00:00Z"));
+    assert_eq!(interval.next(start), at("2024-02-29T00:00:00Z"));
+    assert_eq!(interval.name(start), "events_p2024_02_28");
+}
+
+#[test]
+fn bounds_are_read_from_the_catalog_expression() {
+    assert_eq!(
+        parse_bound("FOR VALUES FROM ('2025-01-01 00:00:00+00') TO ('2025-02-01 00:00:00+00')"),
+        Some((
+            Some(at("2025-01-01T00:00:00Z")),
+            Some(at("2025-02-01T00:00:00Z"))
+        ))
+    );
+    assert_eq!(
+        parse_bound("FOR VALUES FROM (MINVALUE) TO ('2025-02-01 00:00:00+00')"),
+        Some((None, Some(at("2025-02-01T00:00:00Z"))))
+    );
+    assert_eq!(parse_bound("DEFAULT"), None);
+}
@@ -0,0 +1,269 @@
+mod support;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use actix_web::{
+    App,
+    http::StatusCode,
+    test::{self, TestRequest},
+    web,
+};
+use redis::Client;
+use serde_json::Value;
+use support::fake_redis::FakeRedis;
+use w_collider::{
+    common::{
+        cache::redis_store::{RedisSettings, RedisStore},
+        config::{Config, RateLimitConfig},
+        rate_limit::{Limit, RateLimiter, RouteLimits},
+    },
+    init_routes,
+};
+
+async fn connect(fake: &FakeRedis) -> RedisStore {
+    let settings = RedisSettings {
+        timeout: Duration::from_millis(200),
+        failure_threshold: 2,
+        cooldown: Duration::from_secs(60),
+    };
+    RedisStore::connect(Client::open(fake.url()).unwrap(), settings).await
+}
+
+fn limit(burst: u64) -> Limit {
+    Limit {
+        burst,
+        per_second: 0.01,
+    }
+}
+
+fn config(route: &str, limits: RouteLimits) -> RateLimitConfig {
+    RateLimitConfig {
+        enabled: true,
+        routes: BTreeMap::from([(route.to_owned(), limits)]),
+        keys: BTreeMap::new(),
+        key_lookups: None,
+    }
+}
+
+#[test]
+fn buckets_refill_with_time_up_to_the_burst() {
+    let limit = Limit {
+        burst: 3,
+        per_second: 2.0,
+    };
+
+    let mut bucket = None;
+    for _ in 0..3 {
+        let (allowed, next) = limit.take(bucket, 0);
+        assert!(allowed);
+        bucket = Some(next);
+    }
+    let (allowed, next) = limit.take(bucket, 0);
+    assert!(!allowed);
+
+    let (allowed, next) = limit.take(Some(next), 500);
+    assert!(allowed);
+    assert_eq!(next.tokens, 0.0);
+
+    let (_, next) = limit.take(Some(next), 60_000);
+    assert_eq!(next.tokens, 2.0);
+}
+
+#[test]
+fn decisions_say_when_to_come_back() {
+    let limit = Limit {
+        burst: 10,
+        per_second: 2.0,
+    };
+
+    let passed = limit.decision(true, 4.5);
+    assert_eq!(
+        (passed.remaining, passed.reset, passed.retry_after),
+        (4, 3, 0)
+    );
+
+    let refused = limit.decision(false, 0.2);
+    assert_eq!(refused.remaining, 0);
+    assert_eq!(refused.retry_after, 1);
+    assert_eq!(refused.reset, 5);
+}
+
+#[tokio::test]
+async fn servers_share_buckets_through_the_redis_script() {
+    let fake = FakeRedis::start().await;
+    let redis = connect(&fake).await;
+
+    let limits = RouteLimits {
+        per_key: Some(limit(3)),
+        ..RouteLimits::default()
+    };
+    let first = RateLimiter::create(config("POST /event", limits.clone()), Some(redis.clone()));
+    let second = RateLimiter::create(config("POST /event", limits), Some(redis));
+
+    let mut passed = 0;
+    for limiter in [&first, &second, &first, &second] {
+        let decisions = limiter.check_request("POST /event", Some(7)).await;
+        passed += decisions.iter().filter(|d| d.allowed).count();
+    }
+    assert_eq!(passed, 3);
+    assert_eq!(fake.evals(), 4);
+
+    let other_key = first.check_request("POST /event", Some(8)).await;
+    assert!(other_key[0].allowed);
+    assert_eq!(other_key[0].remaining, 2);
+}
+
+#[tokio::test]
+async fn memory_takes_over_while_redis_is_down() {
+    let fake = FakeRedis::start().await;
+    let redis = connect(&fake).await;
+    fake.go_down();
+
+    let limits = RouteLimits {
+        per_user: Some(limit(2)),
+        ..RouteLimits::default()
+    };
+    let limiter = RateLimiter::create(config("POST /event", limits), Some(redis));
+
+    let mut allowed = vec![];
+    for _ in 0..3 {
+        allowed.push(limiter.check_user("POST /event", 1).await.unwrap().allowed);
+    }
+    assert_eq!(allowed, [true, true, false]);
+
+    assert!(limiter.check_user("POST /event", 2).await.unwrap().allowed);
+    assert!(limiter.check_user("GET /stats", 1).await.is_none());
+}
+
+#[tokio::test]
+async fn listed_keys_get_their_own_limit() {
+    let mut config = config(
+        "*",
+        RouteLimits {
+            per_key: Some(limit(1)),
+            ..RouteLimits::default()
+        },
+    );
+    config.keys.insert("42".to_owned(), limit(5));
+    let limiter = RateLimiter::create(config, None);
+
+    let listed = limiter.check_request("GET /events", Some(42)).await;
+    assert_eq!((listed[0].limit, listed[0].remaining), (5, 4));
+
+    let other = limiter.check_request("GET /events", Some(1)).await;
+    assert_eq!((other[0].limit, other[0].remaining), (1, 0));
+    assert!(!limiter.check_request("GET /events", Some(1)).await[0].allowed);
+}
+
+#[actix_web::test]
+async fn exhausted_routes_answer_429_with_rate_limit_headers() {
+    let limiter = RateLimiter::create(
+        config(
+            "GET /gdpr/jobs/{job_id}",
+            RouteLimits {
+                total: Some(limit(2)),
+                ..RouteLimits::default()
+            },
+        ),
+        None,
+    );
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(false))
+            .app_data(web::Data::new(limiter))
+            .configure(init_routes),
+    )
+    .await;
+
+    // Malformed ids still spend a token: the route limit runs first.
+    for remaining in ["1", "0"] {
+        let request = TestRequest::get().uri("/gdpr/jobs/abc");
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers().get("ratelimit-limit").unwrap(), "2");
+        assert_eq!(
+            response.headers().get("ratelimit-remaining").unwrap(),
+            remaining
+        );
+    }
+
+    let request = TestRequest::get().uri("/gdpr/jobs/abc");
+    let response = test::call_service(&app, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers().get("ratelimit-remaining").unwrap(), "0");
+    assert_eq!(response.headers().get("retry-after").unwrap(), "100");
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "rate_limited");
+}
+
+#[actix_web::test]
+async fn unknown_keys_spend_the_lookup_budget_of_their_address() {
+    let limiter = RateLimiter::create(
+        RateLimitConfig {
+            key_lookups: Some(limit(2)),
+            ..config("*", RouteLimits::default())
+        },
+        None,
+    );
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(true))
+            .app_data(web::Data::new(limiter))
+            .configure(init_routes),
+    )
+    .await;
+    let request = |key: &str, address: &str| {
+        TestRequest::get()
+            .uri("/events")
+            .insert_header(("X-Api-Key", key.to_owned()))
+            .peer_addr(address.parse().unwrap())
+            .to_request()
+    };
+
+    // The lookups themselves fail, the pool points nowhere; only the
+    // budget matters here.
+    for key in ["wck_a", "wck_b"] {
+        let response = test::call_service(&app, request(key, "203.0.113.7:4000")).await;
+        assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let response = test::call_service(&app, request("wck_c", "203.0.113.7:4001")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "rate_limited");
+
+    let response = test::call_service(&app, request("wck_d", "198.51.100.1:4000")).await;
+    assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn limits_must_be_positive_and_routes_well_formed() {
+    let config = Config {
+        rate_limit: RateLimitConfig {
+            enabled: true,
+            routes: BTreeMap::from([(
+                "post event".to_owned(),
+                RouteLimits {
+                    total: Some(Limit {
+                        burst: 0,
+                        per_second: 1.0,
+                    }),
+                    ..RouteLimits::default()
+                },
+            )]),
+            keys: BTreeMap::from([("ingest".to_owned(), limit(1))]),
+            key_lookups: Some(Limit {
+                burst: 1,
+                per_second: 0.0,
+            }),
+        },
+        ..Config::default()
+    };
+
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("rate_limit.routes `post event`"));
+    assert!(error.contains("rate_limit.routes.\"post event\".total"));
+    assert!(error.contains("rate_limit.keys `ingest`"));
+    assert!(error.contains("rate_limit.key_lookups"));
+}
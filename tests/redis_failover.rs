@@ -0,0 +1,179 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use redis::Client;
+use support::fake_redis::FakeRedis;
+use w_collider::common::{
+    cache::{
+        CacheDeleteKey, CacheSetKey, LeveledCache,
+        envelope::{Codec, Envelope},
+        memory_store::MemoryStore,
+        redis_store::{RedisSettings, RedisStore},
+        store::CacheStore,
+        tiered_store::TieredStore,
+    },
+    metrics::METRICS,
+};
+
+fn settings() -> RedisSettings {
+    RedisSettings {
+        timeout: Duration::from_millis(200),
+        failure_threshold: 2,
+        cooldown: Duration::from_millis(100),
+    }
+}
+
+async fn connect(fake: &FakeRedis) -> RedisStore {
+    RedisStore::connect(Client::open(fake.url()).unwrap(), settings()).await
+}
+
+fn envelope() -> Envelope {
+    Envelope::create(Codec::None, usize::MAX)
+}
+
+fn tiered(redis: &RedisStore) -> (Arc<dyn CacheStore>, LeveledCache) {
+    let l1: Arc<dyn CacheStore> = Arc::new(MemoryStore::create(1));
+    let store = TieredStore::create(l1.clone(), Arc::new(redis.clone()), 60);
+    (l1, LeveledCache::create(Arc::new(store), envelope()))
+}
+
+async fn wait_until_available(redis: &RedisStore) {
+    for _ in 0..50 {
+        if redis.is_available() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("redis circuit never closed");
+}
+
+#[tokio::test]
+async fn unreachable_redis_at_startup_degrades_to_memory() {
+    let fake = FakeRedis::start().await;
+    fake.go_down();
+
+    let redis = connect(&fake).await;
+    assert!(!redis.is_available());
+
+    let (_, cache) = tiered(&redis);
+    cache
+        .save(CacheSetKey::Exact("users_id".into()), b"[1]".to_vec(), 60)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        cache.try_get("users_id".into()).await,
+        Some(b"[1]".to_vec())
+    );
+    assert_eq!(cache.try_get("event_types".into()).await, None);
+}
+
+#[tokio::test]
+async fn dropped_connections_fall_through_to_l1_and_open_the_circuit() {
+    let fake = FakeRedis::start().await;
+    let redis = connect(&fake).await;
+    let (l1, cache) = tiered(&redis);
+
+    cache
+        .save(CacheSetKey::Exact("total_events".into()), b"7".to_vec(), 60)
+        .await
+        .unwrap();
+    assert!(redis.get("total_events").await.unwrap().is_some());
+
+    fake.go_down();
+
+    assert!(redis.get("total_events").await.is_err());
+    assert!(redis.get("total_events").await.is_err());
+    assert!(!redis.is_available());
+
+    let commands = fake.commands();
+    assert!(redis.get("total_events").await.is_err());
+    assert_eq!(fake.commands(), commands);
+
+    assert_eq!(
+        cache.try_get("total_events".into()).await,
+        Some(b"7".to_vec())
+    );
+
+    l1.del("total_events").await.unwrap();
+    assert_eq!(cache.try_get("total_events".into()).await, None);
+}
+
+#[tokio::test]
+async fn circuit_closes_in_the_background_once_redis_is_back() {
+    let fake = FakeRedis::start().await;
+    fake.go_down();
+
+    let redis = connect(&fake).await;
+    assert!(!redis.is_available());
+
+    fake.come_back();
+    wait_until_available(&redis).await;
+
+    redis
+        .set_ex("page_1_100", b"[]".to_vec(), 60)
+        .await
+        .unwrap();
+    assert_eq!(redis.get("page_1_100").await.unwrap(), Some(b"[]".to_vec()));
+}
+
+#[tokio::test]
+async fn tag_sets_expire_with_their_longest_lived_member() {
+    let fake = FakeRedis::start().await;
+    let redis = connect(&fake).await;
+    let cache = LeveledCache::create(Arc::new(redis.clone()), envelope());
+
+    for (page, seconds) in [("1", 60), ("2", 300), ("3", 30)] {
+        cache
+            .save(
+                CacheSetKey::Pattern("page_{}_{}".into(), vec![page.into(), "100".into()]),
+                b"[]".to_vec(),
+                seconds,
+            )
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(fake.ttl("tag:page_{}_{}"), Some(300));
+    assert_eq!(redis.tagged("page_{}_{}").await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn failed_l2_deletes_are_counted_and_retried() {
+    let fake = FakeRedis::start().await;
+    let redis = connect(&fake).await;
+    let (_, cache) = tiered(&redis);
+    let failures = || {
+        METRICS
+            .cache_invalidation_failures
+            .with_label_values(&["del", "users_id", "retrying"])
+            .get()
+    };
+
+    cache
+        .save(CacheSetKey::Exact("users_id".into()), b"[1]".to_vec(), 60)
+        .await
+        .unwrap();
+    let before = failures();
+
+    fake.go_down();
+    cache
+        .invalidate(CacheDeleteKey::Exact("users_id".into()))
+        .await
+        .unwrap();
+    assert!(failures() > before);
+    fake.come_back();
+
+    for _ in 0..80 {
+        if redis
+            .get("users_id")
+            .await
+            .is_ok_and(|value| value.is_none())
+        {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the l2 delete was never retried");
+}
@@ -0,0 +1,62 @@
+use w_collider::common::retention::{RetentionRule, glob_matches, resolve};
+
+fn rule(pattern: &str, ttl_days: i32) -> RetentionRule {
+    RetentionRule {
+        pattern: pattern.to_owned(),
+        ttl_days,
+        archive: false,
+    }
+}
+
+#[test]
+fn globs_match_any_run_of_characters() {
+    assert!(glob_matches("order.*", "order.created"));
+    assert!(glob_matches("order.*", "order."));
+    assert!(!glob_matches("order.*", "orders.created"));
+    assert!(glob_matches("*.login", "user.login"));
+    assert!(glob_matches("user.*.failed", "user.login.failed"));
+    assert!(!glob_matches("ab*b", "ab"));
+    assert!(glob_matches("*", "anything"));
+    assert!(glob_matches("user.login", "user.login"));
+    assert!(!glob_matches("user.login", "user.logout"));
+}
+
+#[test]
+fn the_most_specific_rule_wins() {
+    let rules = vec![
+        rule("*", 3650),
+        rule("order.*", 730),
+        rule("user.*", 200),
+        rule("user.login", 30),
+    ];
+    let types = vec![
+        (1, "user.login".to_owned()),
+        (2, "user.logout".to_owned()),
+        (3, "order.created".to_owned()),
+        (4, "page.view".to_owned()),
+        (5, "order.refunded".to_owned()),
+    ];
+
+    let resolved: Vec<(&str, Vec<i64>)> = resolve(&rules, &types)
+        .into_iter()
+        .map(|(rule, ids)| (rule.pattern.as_str(), ids))
+        .collect();
+
+    assert_eq!(
+        resolved,
+        vec![
+            ("*", vec![4]),
+            ("order.*", vec![3, 5]),
+            ("user.*", vec![2]),
+            ("user.login", vec![1]),
+        ]
+    );
+}
+
+#[test]
+fn types_without_a_rule_are_kept() {
+    let rules = vec![rule("order.*", 730)];
+    let types = vec![(1, "user.login".to_owned())];
+
+    assert!(resolve(&rules, &types).is_empty());
+}
@@ -0,0 +1,63 @@
+use w_collider::common::schema::{INDEXES_DOWN, INDEXES_UP, MIGRATOR};
+
+#[test]
+fn embedded_migrations_are_reversible() {
+    let versions: Vec<(i64, bool)> = MIGRATOR
+        .iter()
+        .map(|m| (m.version, m.migration_type.is_down_migration()))
+        .collect();
+
+    for version in [0, 1, 2, 3, 4, 5, 6, 7] {
+        assert!(versions.contains(&(version, false)));
+        assert!(versions.contains(&(version, true)));
+    }
+}
+
+#[test]
+fn seeder_index_sql_matches_the_index_migration() {
+    let up = MIGRATOR
+        .iter()
+        .find(|m| m.version == 1 && !m.migration_type.is_down_migration())
+        .unwrap();
+
+    assert_eq!(up.sql, INDEXES_UP);
+
+    let created = INDEXES_UP.matches("CREATE INDEX IF NOT EXISTS").count();
+    let dropped = INDEXES_DOWN.matches("DROP INDEX IF EXISTS").count();
+    assert_eq!(created, dropped);
+}
+
+#[test]
+fn integrity_migration_installs_the_delete_policy_function() {
+    let sql = |down: bool| {
+        MIGRATOR
+            .iter()
+            .find(|m| m.version == 2 && m.migration_type.is_down_migration() == down)
+            .unwrap()
+            .sql
+            .to_string()
+    };
+
+    let up = sql(false);
+    for policy in ["'tombstone'", "'cascade'", "foreign_key_violation"] {
+        assert!(up.contains(policy), "{}", policy);
+    }
+    assert!(sql(true).contains("DROP FUNCTION IF EXISTS integrity_on_delete()"));
+}
+
+#[test]
+fn partition_migration_recreates_every_index_on_the_parent() {
+    let up = MIGRATOR
+        .iter()
+        .find(|m| m.version == 3 && !m.migration_type.is_down_migration())
+        .unwrap();
+
+    assert!(up.sql.contains("PARTITION BY RANGE (timestamp)"));
+    for statement in INDEXES_UP
+        .split(';')
+        .map(|s| s.trim().trim_start_matches("-- Indexes").trim())
+        .filter(|s| !s.is_empty())
+    {
+        assert!(up.sql.contains(statement), "{}", statement);
+    }
+}
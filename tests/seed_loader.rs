@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+
+use itoa::Buffer;
+use w_collider::common::{
+    config::Config,
+    seed_loader::{CopyFormat, SeedLoader, encode_row},
+    seed_profile::GeneratedEvent,
+    tenant::Tenant,
+};
+
+fn event() -> GeneratedEvent<'static> {
+    GeneratedEvent {
+        user_id: 7,
+        type_id: 3,
+        // 2000-01-01T00:00:01Z
+        timestamp: 946_684_801,
+        metadata: Cow::Borrowed(r#"{"page":"/a"}"#),
+    }
+}
+
+#[test]
+fn csv_rows_quote_the_metadata() {
+    let mut chunk = vec![];
+    encode_row(
+        &mut chunk,
+        CopyFormat::Csv,
+        42,
+        Tenant(9),
+        &event(),
+        &mut Buffer::new(),
+    );
+
+    assert_eq!(
+        String::from_utf8(chunk).unwrap(),
+        "42,7,3,2000-01-01T00:00:01Z,\"{\"\"page\"\":\"\"/a\"\"}\",9\n"
+    );
+}
+
+#[test]
+fn binary_rows_use_postgres_wire_types() {
+    let mut chunk = vec![];
+    encode_row(
+        &mut chunk,
+        CopyFormat::Binary,
+        42,
+        Tenant(9),
+        &event(),
+        &mut Buffer::new(),
+    );
+
+    let mut expected = vec![0, 6];
+    for value in [42i64, 7, 3, 1_000_000] {
+        expected.extend_from_slice(&8i32.to_be_bytes());
+        expected.extend_from_slice(&value.to_be_bytes());
+    }
+    expected.extend_from_slice(&14i32.to_be_bytes());
+    expected.push(1);
+    expected.extend_from_slice(br#"{"page":"/a"}"#);
+    expected.extend_from_slice(&8i32.to_be_bytes());
+    expected.extend_from_slice(&9i64.to_be_bytes());
+
+    assert_eq!(chunk, expected);
+}
+
+#[test]
+fn loader_is_chosen_by_config() {
+    let config = Config::default();
+    assert_eq!(config.seed.loader, SeedLoader::Copy);
+
+    let config = Config::from_toml("[seed]\ncopy_format = \"csv\"").unwrap();
+    assert_eq!(config.seed.copy_format, CopyFormat::Csv);
+
+    let config = Config::from_toml("[seed]\nloader = \"pg_bulkload\"").unwrap();
+    let error = config.validate().err().unwrap().to_string();
+    assert!(error.contains("seed.loader"), "{}", error);
+
+    let mut config = Config::default();
+    config.seed.parallelism = 500;
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+use w_collider::common::seed_profile::{
+    Diurnal, EventGenerator, Pick, SeedProfile, SeedProfiles, Sessions,
+};
+
+const FILE: &str = r#"
+[bench]
+seed = 7
+end = "2025-06-01T00:00:00Z"
+users = 50
+types = 5
+events = 2000
+days = 30
+pick_users = { zipf = 1.2 }
+pick_templates = "uniform"
+templates = [{ page = "/checkout", cart = "{user_id}-{n}" }, { page = "/home" }]
+
+[bench.sessions]
+probability = 0.5
+"#;
+
+fn generate(profile: &SeedProfile) -> Vec<(i64, i64, i64, String)> {
+    let mut generator = EventGenerator::create(
+        profile,
+        (1..=profile.users as i64).collect(),
+        (1..=profile.types as i64).collect(),
+    )
+    .unwrap();
+
+    let mut events = vec![];
+    while let Some(event) = generator.next_event() {
+        events.push((
+            event.user_id,
+            event.type_id,
+            event.timestamp,
+            event.metadata.into_owned(),
+        ));
+    }
+    events
+}
+
+fn bench() -> SeedProfile {
+    SeedProfiles::from_toml(FILE).unwrap().get("bench").unwrap()
+}
+
+#[test]
+fn profiles_file_extends_the_builtins() {
+    let profiles = SeedProfiles::from_toml(FILE).unwrap();
+    let bench = profiles.get("bench").unwrap();
+
+    assert_eq!(bench.pick_users, Pick::Zipf(1.2));
+    assert_eq!(bench.pick_types, Pick::RoundRobin);
+    assert_eq!(bench.sessions.as_ref().unwrap().max_events, 12);
+    assert_eq!(profiles.get("default").unwrap(), SeedProfile::default());
+    assert!(profiles.get("realistic").unwrap().seed.is_some());
+
+    let error = profiles.get("missing").err().unwrap().to_string();
+    assert!(error.contains("bench, default, realistic"), "{}", error);
+
+    assert!(SeedProfiles::from_toml("[bench]\nevent = 1").is_err());
+
+    let example = SeedProfiles::from_toml(include_str!("../seed.example.toml")).unwrap();
+    example.get("bench").unwrap().validate().unwrap();
+}
+
+#[test]
+fn same_seed_gives_the_same_dataset() {
+    let profile = bench();
+    let first = generate(&profile);
+
+    assert_eq!(first.len(), 2000);
+    assert_eq!(first, generate(&profile));
+    let realistic = SeedProfile {
+        events: 500,
+        ..SeedProfile::realistic()
+    };
+    assert_eq!(generate(&realistic), generate(&realistic));
+
+    let other = SeedProfile {
+        seed: Some(8),
+        ..profile
+    };
+    assert_ne!(first, generate(&other));
+}
+
+#[test]
+fn events_stay_in_the_time_span_and_render_templates() {
+    let profile = bench();
+    let end = profile.end.unwrap().timestamp();
+    let start = end - 30 * 86_400;
+
+    for (user_id, _, timestamp, metadata) in generate(&profile) {
+        assert!((start..=end).contains(&timestamp));
+        assert!(!metadata.contains("{n}") && !metadata.contains("{user_id}"));
+        if metadata.contains("/checkout") {
+            assert!(
+                metadata.contains(&format!("\"{}-", user_id)),
+                "{}",
+                metadata
+            );
+        }
+    }
+}
+
+#[test]
+fn zipf_users_are_skewed() {
+    let profile = SeedProfile {
+        sessions: None,
+        events: 10_000,
+        ..bench()
+    };
+
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for (user_id, ..) in generate(&profile) {
+        *counts.entry(user_id).or_default() += 1;
+    }
+
+    // Uniform would give each of the 50 users about 200 events.
+    assert!(counts[&1] > 1000, "{:?}", counts.get(&1));
+    assert!(counts[&1] > counts.get(&50).copied().unwrap_or_default() * 10);
+}
+
+#[test]
+fn diurnal_traffic_peaks_at_the_peak_hour() {
+    let profile = SeedProfile {
+        seed: Some(1),
+        end: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+        events: 20_000,
+        diurnal: Some(Diurnal {
+            amplitude: 0.9,
+            peak_hour: 14.0,
+        }),
+        ..SeedProfile::default()
+    };
+
+    let mut hours = [0usize; 24];
+    for (_, _, timestamp, _) in generate(&profile) {
+        hours[(timestamp.rem_euclid(86_400) / 3600) as usize] += 1;
+    }
+
+    assert!(hours[14] > hours[2] * 5, "{:?}", hours);
+}
+
+#[test]
+fn sessions_repeat_the_user_with_short_gaps() {
+    let profile = SeedProfile {
+        seed: Some(3),
+        end: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+        events: 100,
+        pick_users: Pick::Uniform,
+        sessions: Some(Sessions {
+            probability: 1.0,
+            min_events: 4,
+            max_events: 4,
+            max_gap_seconds: 10,
+        }),
+        ..SeedProfile::default()
+    };
+
+    let events = generate(&profile);
+    for session in events.chunks(4) {
+        for pair in session.windows(2) {
+            assert_eq!(pair[0].0, pair[1].0);
+            let gap = pair[1].2 - pair[0].2;
+            assert!((0..=10).contains(&gap), "{}", gap);
+        }
+    }
+}
+
+#[test]
+fn seed_without_end_is_rejected() {
+    let profile = SeedProfile {
+        seed: Some(1),
+        ..SeedProfile::default()
+    };
+    let error = profile.validate().err().unwrap().to_string();
+    assert!(error.contains("end is required"), "{}", error);
+
+    let resolved = SeedProfile::default().resolved();
+    assert!(resolved.seed.is_some() && resolved.end.is_some());
+}
+
+#[test]
+fn templates_need_a_string_page() {
+    let profile = SeedProfile {
+        templates: vec![
+            json!({"page": "/home"}),
+            json!({"cart": "{n}"}),
+            json!({"page": 3}),
+            json!("/home"),
+        ],
+        ..SeedProfile::default()
+    };
+
+    let error = profile.validate().err().unwrap().to_string();
+    assert!(!error.contains("templates[0]"), "{}", error);
+    assert!(
+        error.contains("templates[1].page must be a string"),
+        "{}",
+        error
+    );
+    assert!(
+        error.contains("templates[2].page must be a string"),
+        "{}",
+        error
+    );
+    assert!(error.contains("templates[3] must be a table"), "{}", error);
+}
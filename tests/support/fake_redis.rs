@@ -0,0 +1,276 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use redis::Script;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::AbortHandle,
+};
+use w_collider::common::rate_limit::{Bucket, Limit, TOKEN_BUCKET};
+
+// A tiny RESP2 server that understands the handful of commands the cache
+// stores issue, and can drop every open connection on demand. Lua is not
+// interpreted: the rate limit script is answered by the same arithmetic the
+// in-memory limiter uses. EXPIRE is recorded but never fires.
+#[derive(Clone)]
+pub struct FakeRedis {
+    pub addr: SocketAddr,
+    state: Arc<State>,
+}
+
+#[derive(Default)]
+struct State {
+    down: AtomicBool,
+    commands: AtomicUsize,
+    strings: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+    sets: Mutex<HashMap<Vec<u8>, HashSet<Vec<u8>>>>,
+    expiries: Mutex<HashMap<Vec<u8>, u64>>,
+    scripts: Mutex<HashMap<String, Vec<u8>>>,
+    buckets: Mutex<HashMap<Vec<u8>, Bucket>>,
+    evals: AtomicUsize,
+    connections: Mutex<Vec<AbortHandle>>,
+}
+
+impl FakeRedis {
+    pub async fn start() -> FakeRedis {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(State::default());
+
+        let accept_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((socket, _)) = listener.accept().await else {
+                    return;
+                };
+
+                if accept_state.down.load(Ordering::SeqCst) {
+                    drop(socket);
+                    continue;
+                }
+
+                let connection_state = accept_state.clone();
+                let handle = tokio::spawn(serve(socket, connection_state));
+                accept_state
+                    .connections
+                    .lock()
+                    .unwrap()
+                    .push(handle.abort_handle());
+            }
+        });
+
+        FakeRedis { addr, state }
+    }
+
+    pub fn url(&self) -> String {
+        format!("redis://{}/", self.addr)
+    }
+
+    pub fn commands(&self) -> usize {
+        self.state.commands.load(Ordering::SeqCst)
+    }
+
+    /// Scripts run through EVAL or EVALSHA.
+    pub fn evals(&self) -> usize {
+        self.state.evals.load(Ordering::SeqCst)
+    }
+
+    /// Seconds set by the last EXPIRE that applied to `key`, if any.
+    pub fn ttl(&self, key: &str) -> Option<u64> {
+        self.state
+            .expiries
+            .lock()
+            .unwrap()
+            .get(key.as_bytes())
+            .copied()
+    }
+
+    pub fn go_down(&self) {
+        self.state.down.store(true, Ordering::SeqCst);
+        for handle in self.state.connections.lock().unwrap().drain(..) {
+            handle.abort();
+        }
+    }
+
+    pub fn come_back(&self) {
+        self.state.down.store(false, Ordering::SeqCst);
+    }
+}
+
+async fn serve(socket: TcpStream, state: Arc<State>) {
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+
+    while let Some(args) = read_command(&mut reader).await {
+        state.commands.fetch_add(1, Ordering::SeqCst);
+
+        let reply = execute(&state, &args);
+        if writer.write_all(&reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn read_command<R>(reader: &mut BufReader<R>) -> Option<Vec<Vec<u8>>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let header = read_line(reader).await?;
+    let count: usize = header.strip_prefix('*')?.parse().ok()?;
+
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len: usize = read_line(reader).await?.strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0u8; len + 2];
+        reader.read_exact(&mut arg).await.ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+
+    Some(args)
+}
+
+async fn read_line<R>(reader: &mut BufReader<R>) -> Option<String>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await.ok()? == 0 {
+        return None;
+    }
+    Some(line.trim_end().to_owned())
+}
+
+fn execute(state: &State, args: &[Vec<u8>]) -> Vec<u8> {
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let mut strings = state.strings.lock().unwrap();
+    let mut sets = state.sets.lock().unwrap();
+    let mut expiries = state.expiries.lock().unwrap();
+
+    match (name.as_str(), args.len()) {
+        ("PING", _) => b"+PONG\r\n".to_vec(),
+        ("GET", 2) => match strings.get(&args[1]) {
+            Some(value) => bulk(value),
+            None => b"$-1\r\n".to_vec(),
+        },
+        ("SET", n) if n >= 3 => {
+            strings.insert(args[1].clone(), args[2].clone());
+            b"+OK\r\n".to_vec()
+        }
+        ("SETEX", 4) => {
+            let seconds = String::from_utf8_lossy(&args[2]).parse().unwrap();
+            expiries.insert(args[1].clone(), seconds);
+            strings.insert(args[1].clone(), args[3].clone());
+            b"+OK\r\n".to_vec()
+        }
+        ("DEL", _) => {
+            let removed = args[1..]
+                .iter()
+                .filter(|key| {
+                    expiries.remove(*key);
+                    strings.remove(*key).is_some() || sets.remove(*key).is_some()
+                })
+                .count();
+            format!(":{}\r\n", removed).into_bytes()
+        }
+        ("SADD", n) if n >= 3 => {
+            let set = sets.entry(args[1].clone()).or_default();
+            let added = args[2..]
+                .iter()
+                .filter(|member| set.insert((*member).clone()))
+                .count();
+            format!(":{}\r\n", added).into_bytes()
+        }
+        ("EXPIRE", 3 | 4) => {
+            let exists = strings.contains_key(&args[1]) || sets.contains_key(&args[1]);
+            let seconds: u64 = String::from_utf8_lossy(&args[2]).parse().unwrap();
+            let current = expiries.get(&args[1]).copied();
+            let option = args.get(3).map(|option| option.to_ascii_uppercase());
+
+            // A key without a TTL counts as never expiring for GT and LT.
+            let applies = exists
+                && match option.as_deref() {
+                    None => true,
+                    Some(b"NX") => current.is_none(),
+                    Some(b"XX") => current.is_some(),
+                    Some(b"GT") => current.is_some_and(|current| seconds > current),
+                    Some(b"LT") => current.is_none_or(|current| seconds < current),
+                    Some(_) => return b"-ERR Unsupported option\r\n".to_vec(),
+                };
+            if applies {
+                expiries.insert(args[1].clone(), seconds);
+            }
+            format!(":{}\r\n", applies as i64).into_bytes()
+        }
+        ("SMEMBERS", 2) => {
+            let members = sets.get(&args[1]).cloned().unwrap_or_default();
+            let mut reply = format!("*{}\r\n", members.len()).into_bytes();
+            for member in members {
+                reply.extend(bulk(&member));
+            }
+            reply
+        }
+        ("SCRIPT", 3) if args[1].eq_ignore_ascii_case(b"LOAD") => {
+            let source = String::from_utf8_lossy(&args[2]);
+            let hash = Script::new(&source).get_hash().to_owned();
+            state
+                .scripts
+                .lock()
+                .unwrap()
+                .insert(hash.clone(), args[2].clone());
+            bulk(hash.as_bytes())
+        }
+        ("EVALSHA", n) if n >= 3 => {
+            let hash = String::from_utf8_lossy(&args[1]).into_owned();
+            match state.scripts.lock().unwrap().get(&hash).cloned() {
+                Some(source) => eval(state, &source, &args[2..]),
+                None => b"-NOSCRIPT No matching script. Please use EVAL.\r\n".to_vec(),
+            }
+        }
+        ("EVAL", n) if n >= 3 => eval(state, &args[1], &args[2..]),
+        _ => format!("-ERR unknown command '{}'\r\n", name).into_bytes(),
+    }
+}
+
+// args: numkeys, keys..., argv...
+fn eval(state: &State, source: &[u8], args: &[Vec<u8>]) -> Vec<u8> {
+    state.evals.fetch_add(1, Ordering::SeqCst);
+
+    if source != TOKEN_BUCKET.as_bytes() {
+        return b"-ERR fake redis only runs the rate limit script\r\n".to_vec();
+    }
+
+    let text = |arg: &Vec<u8>| String::from_utf8_lossy(arg).into_owned();
+    let (key, burst, per_second) = (&args[1], text(&args[2]), text(&args[3]));
+    let limit = Limit {
+        burst: burst.parse().unwrap(),
+        per_second: per_second.parse().unwrap(),
+    };
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64;
+
+    let mut buckets = state.buckets.lock().unwrap();
+    let (allowed, bucket) = limit.take(buckets.get(key).copied(), now_ms);
+    buckets.insert(key.clone(), bucket);
+
+    let mut reply = format!("*2\r\n:{}\r\n", allowed as i64).into_bytes();
+    reply.extend(bulk(bucket.tokens.to_string().as_bytes()));
+    reply
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut reply = format!("${}\r\n", value.len()).into_bytes();
+    reply.extend_from_slice(value);
+    reply.extend_from_slice(b"\r\n");
+    reply
+}
@@ -0,0 +1,54 @@
+#![allow(dead_code)]
+
+pub mod fake_redis;
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::web;
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
+use w_collider::{
+    common::{
+        cache::{LeveledCache, envelope::Envelope, noop_store::NoopStore},
+        config::{AuthConfig, CacheConfig},
+    },
+    contexts::auth::infrastructure::{api_keys::ApiKeys, api_keys_repo::ApiKeysRepo},
+};
+
+/// Keys backed by a pool that never connects, for tests that stop before a
+/// lookup.
+pub fn api_keys(enabled: bool) -> web::Data<ApiKeys> {
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .expect("lazy pool");
+    let cache = CacheConfig::default();
+
+    web::Data::new(ApiKeys::create(
+        ApiKeysRepo::create(pool),
+        LeveledCache::create(
+            Arc::new(NoopStore),
+            Envelope::create(cache.codec, cache.compress_threshold),
+        ),
+        AuthConfig {
+            enabled,
+            ..AuthConfig::default()
+        },
+    ))
+}
+
+/// A pool on the migrated database at `DATABASE_URL`, or `None` when there
+/// is none to reach, in which case the calling test skips itself.
+pub async fn database() -> Option<Pool<Postgres>> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+
+    match PgPoolOptions::new()
+        .acquire_timeout(Duration::from_secs(2))
+        .connect(&url)
+        .await
+    {
+        Ok(pool) => Some(pool),
+        Err(error) => {
+            eprintln!("skipping, no database at DATABASE_URL: {}", error);
+            None
+        }
+    }
+}
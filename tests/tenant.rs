@@ -0,0 +1,287 @@
+mod support;
+
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{
+    App, ResponseError,
+    http::{StatusCode, header::HeaderValue},
+    test::{self, TestRequest},
+    web,
+};
+use serde_json::{Value, json};
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
+use w_collider::{
+    common::{
+        cache::{
+            CacheDeleteKey, CacheSetKey, LeveledCache,
+            envelope::{Codec, Envelope},
+            memory_store::MemoryStore,
+        },
+        command_bus::CommandBus,
+        config::MetadataConfig,
+        error::AppError,
+        metadata_policy::MetadataPolicies,
+        snowflake::next_id,
+        tenant::Tenant,
+    },
+    contexts::events::infrastructure::{
+        cached_projection::{CacheTtls, EventsProj},
+        repo::EventsRepo,
+    },
+    init_routes,
+};
+
+fn cache() -> LeveledCache {
+    LeveledCache::create(
+        Arc::new(MemoryStore::create(1)),
+        Envelope::create(Codec::Lz4, 64),
+    )
+}
+
+// A repo that never connects: anything the cache does not answer fails.
+fn projection(cache: LeveledCache) -> EventsProj {
+    let pool = PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .expect("lazy pool");
+
+    EventsProj::create(cache, EventsRepo::create(pool), CacheTtls::default())
+}
+
+#[test]
+fn bound_keys_decide_the_tenant_and_the_header_only_repeats_it() {
+    let header = |value: &'static str| HeaderValue::from_static(value);
+
+    assert_eq!(Tenant::resolve(None, None).unwrap(), Tenant(0));
+    assert_eq!(
+        Tenant::resolve(Some(&header("7")), None).unwrap(),
+        Tenant(7)
+    );
+    assert_eq!(Tenant::resolve(None, Some(3)).unwrap(), Tenant(3));
+    assert_eq!(
+        Tenant::resolve(Some(&header(" 3 ")), Some(3)).unwrap(),
+        Tenant(3)
+    );
+
+    let error = Tenant::resolve(Some(&header("4")), Some(3)).unwrap_err();
+    assert!(matches!(error, AppError::WrongTenant));
+    assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+
+    for invalid in ["-1", "one", ""] {
+        let error = Tenant::resolve(Some(&header(invalid)), None).unwrap_err();
+        assert!(matches!(error, AppError::Validation { .. }));
+    }
+}
+
+#[test]
+fn every_cache_key_names_its_tenant() {
+    let (one, two) = (Tenant(1), Tenant(2));
+
+    for family in ["total_events", "event_types", "users_id"] {
+        assert_ne!(one.key(family), two.key(family));
+    }
+    assert_eq!(one.user_events_key(42), "user_events_1_42");
+    assert_ne!(one.user_events_key(42), two.user_events_key(42));
+    assert_ne!(one.stats_pattern(), two.stats_pattern());
+    assert_ne!(one.page_pattern(), two.page_pattern());
+}
+
+#[tokio::test]
+async fn cached_events_of_one_tenant_are_not_served_to_another() {
+    let cache = cache();
+    cache
+        .save(
+            CacheSetKey::Exact(Tenant(1).user_events_key(42)),
+            b"[\"tenant one\"]".to_vec(),
+            60,
+        )
+        .await
+        .unwrap();
+    let proj = projection(cache);
+
+    assert_eq!(
+        proj.get_thousand_user_events(Tenant(1), 42).await.unwrap(),
+        b"[\"tenant one\"]".to_vec()
+    );
+    // Tenant 2 misses the cache and goes to the database instead.
+    assert!(proj.get_thousand_user_events(Tenant(2), 42).await.is_err());
+}
+
+#[tokio::test]
+async fn invalidating_one_tenant_keeps_the_pages_of_another() {
+    let cache = cache();
+    for tenant in [Tenant(1), Tenant(2)] {
+        cache
+            .save(
+                CacheSetKey::Pattern(tenant.page_pattern(), vec!["1".into(), "100".into()]),
+                tenant.0.to_string().into_bytes(),
+                60,
+            )
+            .await
+            .unwrap();
+    }
+
+    cache
+        .invalidate(CacheDeleteKey::Pattern(Tenant(1).page_pattern()))
+        .await
+        .unwrap();
+
+    let proj = projection(cache);
+    assert!(proj.paginate_events(Tenant(1), 1, 100).await.is_err());
+    assert_eq!(
+        proj.paginate_events(Tenant(2), 1, 100).await.unwrap(),
+        b"2".to_vec()
+    );
+}
+
+#[actix_web::test]
+async fn users_of_one_tenant_are_unknown_to_another() {
+    let cache = cache();
+    for (tenant, users) in [(Tenant(1), "[42]"), (Tenant(2), "[]")] {
+        cache
+            .save(
+                CacheSetKey::Exact(tenant.key("users_id")),
+                users.as_bytes().to_vec(),
+                60,
+            )
+            .await
+            .unwrap();
+    }
+    cache
+        .save(
+            CacheSetKey::Exact(Tenant(1).user_events_key(42)),
+            b"[]".to_vec(),
+            60,
+        )
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(false))
+            .app_data(web::Data::new(projection(cache)))
+            .configure(init_routes),
+    )
+    .await;
+
+    let request = |tenant: &str| {
+        TestRequest::get()
+            .uri("/users/42/events")
+            .insert_header(("X-Tenant-Id", tenant))
+            .to_request()
+    };
+
+    let response = test::call_service(&app, request("1")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response
+            .headers()
+            .get("vary")
+            .is_some_and(|vary| vary.to_str().unwrap().contains("X-Tenant-Id"))
+    );
+
+    let response = test::call_service(&app, request("2")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["code"], "user_not_found");
+
+    let response = test::call_service(&app, request("two")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+async fn stored_events(pool: &Pool<Postgres>, tenant: i64, expected: i64) {
+    for _ in 0..100 {
+        let stored: i64 = sqlx::query_scalar("SELECT count(*) FROM events WHERE tenant_id = $1")
+            .bind(tenant)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        if stored == expected {
+            // Let the flush callback update the cache.
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("events of tenant {} never reached {}", tenant, expected);
+}
+
+#[actix_web::test]
+async fn flushed_events_are_counted_once() {
+    let Some(pool) = support::database().await else {
+        return;
+    };
+    let tenant = Tenant(next_id());
+    let (user_id, type_id) = (next_id(), next_id());
+
+    sqlx::query("INSERT INTO users (id, name, tenant_id) VALUES ($1, 'counted', $2)")
+        .bind(user_id)
+        .bind(tenant.0)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO event_types (id, name, tenant_id) VALUES ($1, 'order.created', $2)")
+        .bind(type_id)
+        .bind(tenant.0)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let cache = cache();
+    let proj = EventsProj::create(
+        cache.clone(),
+        EventsRepo::create(pool.clone()),
+        CacheTtls::default(),
+    );
+    let bus = Arc::new(CommandBus::init(
+        Duration::from_millis(20),
+        2000,
+        false,
+        pool.clone(),
+    ));
+    let app = test::init_service(
+        App::new()
+            .app_data(support::api_keys(false))
+            .app_data(web::Data::new(proj.clone()))
+            .app_data(web::Data::new(bus))
+            .app_data(web::Data::new(cache.clone()))
+            .app_data(web::Data::new(
+                MetadataPolicies::create(&MetadataConfig::default()).unwrap(),
+            ))
+            .configure(init_routes),
+    )
+    .await;
+    let post = || {
+        TestRequest::post()
+            .uri("/event")
+            .insert_header(("X-Tenant-Id", tenant.0.to_string()))
+            .set_json(json!({
+                "user_id": user_id,
+                "event_type": "order.created",
+                "timestamp": "2025-01-05T10:00:00Z",
+                "metadata": {"page": "/cart"}
+            }))
+            .to_request()
+    };
+
+    // A cold count is left for the next read, which already sees the event.
+    let response = test::call_service(&app, post()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    stored_events(&pool, tenant.0, 1).await;
+    assert_eq!(cache.try_get(tenant.key("total_events")).await, None);
+    assert_eq!(proj.get_events_count(tenant).await.unwrap(), 1);
+
+    // A cached one is bumped by the batch.
+    let response = test::call_service(&app, post()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    stored_events(&pool, tenant.0, 2).await;
+    assert!(cache.try_get(tenant.key("total_events")).await.is_some());
+    assert_eq!(proj.get_events_count(tenant).await.unwrap(), 2);
+
+    for table in ["events", "users", "event_types"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE tenant_id = $1", table))
+            .bind(tenant.0)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}